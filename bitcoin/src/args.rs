@@ -1,11 +1,57 @@
-use clap::{Parser, Subcommand};
+use bdk::bitcoin::secp256k1::PublicKey;
+use bdk::bitcoin::{Address, Network, OutPoint, Txid};
+use clap::{ArgGroup, Parser, Subcommand};
 use std::path::PathBuf;
 
+use crate::constants::{DEFAULT_CONFIRMATION_TIMEOUT_SECS, DEFAULT_FUNDING_POLL_INTERVAL_SECS, SWAPS_DIR};
+use crate::contract::HashType;
+use crate::escrow::KeyAggregation;
+use crate::transaction::{CoinSelection, parse_fee_rate};
+use crate::wallet::{DescriptorType, parse_amount};
+
 #[derive(Parser)]
 #[command(name = "bitcoin-cli")]
 #[command(about = "Bitcoin wallet CLI with HTLC support")]
 #[command(version)]
 pub struct Args {
+    /// TOML file with RPC connection settings (url, username, password, stop_gap)
+    #[arg(long, global = true)]
+    pub rpc_config: Option<PathBuf>,
+
+    /// Seconds to wait for the node to answer while connecting (default 30)
+    #[arg(long, global = true, value_name = "SECS", alias = "rpc-connect-timeout")]
+    pub rpc_timeout: Option<u64>,
+
+    /// SOCKS5 proxy for Electrum and Esplora connections, e.g. 127.0.0.1:9050 for Tor
+    #[arg(long, global = true, value_name = "HOST:PORT")]
+    pub proxy: Option<String>,
+
+    /// Unused addresses scanned past the last revealed one when syncing (default 20)
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub gap_limit: Option<u32>,
+
+    /// Only warn, instead of failing, when --fee-rate is below the node's minimum relay fee
+    #[arg(long, global = true)]
+    pub allow_low_fee: bool,
+
+    /// Diagnostic log level on stderr: off, error, warn, info, debug or trace
+    /// (overrides RUST_LOG, default warn)
+    #[arg(
+        long,
+        global = true,
+        value_name = "LEVEL",
+        value_parser = |level: &str| level.parse::<log::LevelFilter>().map_err(|e| e.to_string())
+    )]
+    pub log_level: Option<log::LevelFilter>,
+
+    /// Print each command's result as one JSON object on stdout (progress goes to stderr)
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Build and sign transactions, then print them (hex, fee, vsize) instead of broadcasting
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -17,24 +63,284 @@ pub enum Commands {
         /// Path to wallet config file
         #[arg(short, long)]
         wallet: PathBuf,
+        /// Show confirmed, pending and immature amounts separately
+        #[arg(short, long)]
+        verbose: bool,
+        /// Only count outputs with at least this many confirmations as spendable
+        #[arg(long)]
+        min_conf: Option<u32>,
     },
-    /// Send Bitcoin to another wallet
+    /// Show the balances of several wallets side by side, syncing them concurrently
+    Balances {
+        /// Wallet config files
+        #[arg(required = true, num_args = 1..)]
+        wallets: Vec<PathBuf>,
+    },
+    /// Send Bitcoin to another wallet or address
     Send {
         /// Source wallet config file
         #[arg(short, long)]
         from: PathBuf,
-        /// Destination wallet config file
+        /// Destination Bitcoin address or wallet config file
         #[arg(short, long)]
-        to: PathBuf,
-        /// Amount in BTC to send
+        to: String,
+        /// Amount to send: BTC by default, or with a unit like 1.5btc or 150000sat
+        #[arg(short, long, required_unless_present = "max", value_parser = parse_amount)]
+        amount: Option<u64>,
+        /// Send the whole wallet balance minus fees, ignoring --amount
+        #[arg(long)]
+        max: bool,
+        /// Fee rate in sat/vB (defaults to 20)
+        #[arg(long, value_parser = parse_fee_rate)]
+        fee_rate: Option<f32>,
+        /// Estimate the fee rate from the node for confirmation within this many blocks
+        #[arg(long, conflicts_with = "fee_rate")]
+        conf_target: Option<u16>,
+        /// Write the unsigned transaction to this file as a base64 PSBT instead of sending it
+        #[arg(long)]
+        psbt_out: Option<PathBuf>,
+        /// Hex data (up to 80 bytes) to embed in a zero-value OP_RETURN output
+        #[arg(long)]
+        op_return: Option<String>,
+        /// Sign the transaction and check the node would accept it, without broadcasting
+        #[arg(long, conflicts_with = "psbt_out")]
+        check: bool,
+        /// How to choose the wallet UTXOs that fund the transaction
+        #[arg(long, value_enum, default_value_t = CoinSelection::Default)]
+        coin_selection: CoinSelection,
+        /// Spend only this wallet UTXO (repeatable); with --max, send its whole value
+        #[arg(long = "utxo", value_name = "TXID:VOUT")]
+        utxos: Vec<OutPoint>,
+        /// Send the change to this address instead of a fresh address of the source wallet
+        #[arg(long, conflicts_with = "max")]
+        change_to: Option<Address>,
+    },
+    /// Show how much `send --max` would deliver to a destination after the fee
+    #[command(name = "max-send")]
+    MaxSend {
+        /// Wallet config file to send from
         #[arg(short, long)]
-        amount: f64,
+        wallet: PathBuf,
+        /// Destination Bitcoin address or wallet config file
+        #[arg(short, long)]
+        to: String,
+        /// Fee rate in sat/vB (defaults to 20)
+        #[arg(long, value_parser = parse_fee_rate)]
+        fee_rate: Option<f32>,
+    },
+    /// Sign a base64 PSBT with a wallet's keys, without broadcasting it
+    #[command(name = "sign-psbt")]
+    SignPsbt {
+        /// Wallet config file holding the signing keys
+        #[arg(short, long)]
+        wallet: PathBuf,
+        /// PSBT file to sign
+        #[arg(long)]
+        psbt_in: PathBuf,
+        /// File to write the signed PSBT to
+        #[arg(long)]
+        psbt_out: PathBuf,
+    },
+    /// Show which inputs of a PSBT each wallet can sign and which it already has
+    #[command(name = "inspect-psbt")]
+    InspectPsbt {
+        /// PSBT file to inspect
+        #[arg(long)]
+        psbt: PathBuf,
+        /// Wallet config files to check, one per --wallet
+        #[arg(short, long = "wallet", required = true)]
+        wallets: Vec<PathBuf>,
+    },
+    /// Broadcast a finalized PSBT, e.g. one signed with sign-psbt
+    Broadcast {
+        /// Finalized PSBT file
+        #[arg(long)]
+        psbt: PathBuf,
+        /// Network to broadcast on
+        #[arg(short, long, default_value = "regtest")]
+        network: Network,
+    },
+    /// Pay several addresses in a single transaction
+    #[command(name = "send-batch")]
+    SendBatch {
+        /// Source wallet config file
+        #[arg(short, long)]
+        from: PathBuf,
+        /// CSV (`address,amount` per line) or JSON file of recipients, amounts in BTC
+        #[arg(short, long)]
+        recipients_file: PathBuf,
+        /// Fee rate in sat/vB (defaults to 20)
+        #[arg(long, value_parser = parse_fee_rate)]
+        fee_rate: Option<f32>,
+    },
+    /// List the wallet's past sends and receives
+    History {
+        /// Path to wallet config file
+        #[arg(short, long)]
+        wallet: PathBuf,
+    },
+    /// Replace a stuck unconfirmed transaction with one paying a higher fee rate
+    #[command(name = "bump-fee")]
+    BumpFee {
+        /// Wallet config file that sent the transaction
+        #[arg(short, long)]
+        wallet: PathBuf,
+        /// Transaction ID of the unconfirmed transaction to replace
+        #[arg(long)]
+        txid: Txid,
+        /// New fee rate in sat/vB, must exceed the original
+        #[arg(long, value_parser = parse_fee_rate)]
+        fee_rate: f32,
+    },
+    /// List unspent outputs controlled by a wallet
+    #[command(name = "list-utxos")]
+    Utxos {
+        /// Path to wallet config file
+        #[arg(short, long)]
+        wallet: PathBuf,
+    },
+    /// Generate a new wallet config with a fresh mnemonic
+    #[command(name = "generate-wallet")]
+    Generate {
+        /// Path to write the wallet config file to
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Number of mnemonic words (12 or 24)
+        #[arg(long, default_value = "12")]
+        word_count: usize,
+        /// Network the wallet operates on
+        #[arg(short, long, default_value = "regtest")]
+        network: Network,
+        /// Descriptor type for the wallet's addresses
+        #[arg(long, value_enum, default_value = "wpkh")]
+        descriptor_type: DescriptorType,
+        /// Overwrite the output file if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the wallet's output descriptor with its checksum
+    #[command(name = "export-descriptor")]
+    ExportDescriptor {
+        /// Path to wallet config file
+        #[arg(short, long)]
+        wallet: PathBuf,
+        /// Only include the xpub, so the descriptor is safe to share
+        #[arg(long)]
+        public: bool,
     },
     /// Get wallet address
     Address {
         /// Path to wallet config file
         #[arg(short, long)]
         wallet: PathBuf,
+        /// Reveal and list the next N unused addresses instead
+        #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+        count: Option<u32>,
+    },
+    /// Check that the configured node is reachable, on the expected network and synced
+    Ping {
+        /// Network the node must be on
+        #[arg(short, long, default_value = "regtest")]
+        network: Network,
+    },
+    /// Check that an address is well formed and belongs to a network, and show its type
+    #[command(name = "validate-address")]
+    ValidateAddress {
+        /// Address to check
+        address: String,
+        /// Network the address must belong to
+        #[arg(short, long, default_value = "regtest")]
+        network: Network,
+    },
+    /// Sign a message with the key of the wallet's receiving address (p2wpkh only)
+    #[command(name = "sign-message")]
+    SignMessage {
+        /// Path to wallet config file
+        #[arg(short, long)]
+        wallet: PathBuf,
+        /// Message to sign
+        message: String,
+    },
+    /// Check a base64 message signature against a p2wpkh address
+    #[command(name = "verify-message")]
+    VerifyMessage {
+        /// Address that should have signed the message
+        address: String,
+        /// Message that was signed
+        message: String,
+        /// Base64 signature from sign-message
+        signature: String,
+    },
+    /// Decode a hex-encoded raw transaction locally, without asking the node
+    #[command(name = "decode-tx")]
+    DecodeTx {
+        /// Raw transaction hex
+        hex: String,
+    },
+    /// List the swap records saved in a swaps directory
+    Swaps {
+        /// Directory holding the swap records
+        #[arg(short, long, default_value = SWAPS_DIR)]
+        dir: PathBuf,
+    },
+    /// Write the shareable part of a swap record, without its secret, for the counterparty
+    #[command(name = "export-offer")]
+    ExportOffer {
+        /// Swap record JSON file
+        #[arg(short, long)]
+        record: PathBuf,
+        /// File to write the offer to
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+    /// Check whether a saved swap was funded, claimed or refunded
+    #[command(name = "swap-status")]
+    SwapStatus {
+        /// Swap record JSON file
+        #[arg(short, long)]
+        record: PathBuf,
+    },
+    /// Reclaim a taproot swap through its revocation timelock once it has matured
+    #[command(name = "swap-refund")]
+    SwapRefund {
+        /// Swap record JSON file
+        #[arg(short, long)]
+        record: PathBuf,
+        /// Sender wallet config file holding the revocation key
+        #[arg(short, long)]
+        wallet: PathBuf,
+        /// Fee rate in sat/vB for the refund (defaults to 20)
+        #[arg(long, value_parser = parse_fee_rate)]
+        fee_rate: Option<f32>,
+    },
+    /// Spend a swap funded with `swap --deterministic` without its swap record
+    #[command(name = "recover-swap")]
+    #[command(group(ArgGroup::new("recipient_key").required(true).args(["recipient", "recipient_pubkey"])))]
+    RecoverSwap {
+        /// Sender wallet config file whose seed derived the swap secret
+        #[arg(short, long)]
+        wallet: PathBuf,
+        /// Recipient wallet config file of the swap, whose key signs the withdrawal
+        #[arg(short, long, conflicts_with = "refund")]
+        recipient: Option<PathBuf>,
+        /// Recipient's public key as hex, enough to rebuild the contract for `--refund`
+        #[arg(long, requires = "refund")]
+        recipient_pubkey: Option<PublicKey>,
+        /// Swap secret index printed by `swap --deterministic`
+        #[arg(long)]
+        index: u32,
+        /// Contract address printed when the swap was funded
+        #[arg(long)]
+        contract_address: Address,
+        /// Timelock duration in blocks from the original swap
+        #[arg(long, default_value = "144")]
+        timelock: u32,
+        /// Fee rate in sat/vB for the withdrawal or refund (defaults to 20)
+        #[arg(long, value_parser = parse_fee_rate)]
+        fee_rate: Option<f32>,
+        /// Refund the sender through the revocation timelock instead of paying the recipient
+        #[arg(long)]
+        refund: bool,
     },
     /// Create atomic swap HTLC
     Swap {
@@ -44,12 +350,84 @@ pub enum Commands {
         /// Destination wallet config file
         #[arg(short, long)]
         to: PathBuf,
-        /// Amount in BTC to swap
-        #[arg(short, long)]
-        amount: f64,
+        /// Amount to swap: BTC by default, or with a unit like 1.5btc or 150000sat
+        #[arg(short, long, value_parser = parse_amount)]
+        amount: u64,
         /// Timelock duration in blocks
         #[arg(long, default_value = "144")]
         timelock: u32,
+        /// Fee rate in sat/vB (defaults to 20)
+        #[arg(long, value_parser = parse_fee_rate)]
+        fee_rate: Option<f32>,
+        /// Fund the swap even if a record shows the same swap was already funded
+        #[arg(long)]
+        force: bool,
+        /// Derive the swap secret from the source wallet's seed instead of generating it
+        /// randomly, so the mnemonic alone can recover it
+        #[arg(long)]
+        deterministic: bool,
+        /// Fund without asking for confirmation of the amount and fee
+        #[arg(short, long)]
+        yes: bool,
+        #[command(flatten)]
+        confirmations: ConfirmationArgs,
+    },
+    /// Generate a random HTLC secret and print its hash commitments
+    #[command(name = "gen-secret")]
+    GenSecret {
+        /// Length of the secret in bytes
+        #[arg(long, default_value_t = 32)]
+        bytes: usize,
+    },
+    /// Create and fund a P2WSH HTLC locked to the hash of a secret
+    #[command(name = "htlc-create")]
+    HtlcCreate {
+        /// Sender wallet config file (funds the contract, can refund after timeout)
+        #[arg(short, long)]
+        from: PathBuf,
+        /// Recipient wallet config file (can claim with the secret)
+        #[arg(short, long)]
+        to: PathBuf,
+        /// Amount in BTC to lock
+        #[arg(short, long)]
+        amount: f64,
+        /// Secret preimage whose hash locks the contract (0x-prefixed hex or text)
+        #[arg(long)]
+        secret: String,
+        /// Absolute block height after which the sender can refund
+        #[arg(long)]
+        timeout: u32,
+        /// Treat --timeout as a number of blocks after funding (OP_CSV)
+        #[arg(long)]
+        relative: bool,
+        /// Hash function of the hash lock
+        #[arg(long, value_enum, default_value_t = HashType::Sha256)]
+        hash_type: HashType,
+        /// Fee rate in sat/vB (defaults to 20)
+        #[arg(long, value_parser = parse_fee_rate)]
+        fee_rate: Option<f32>,
+    },
+    /// Claim a P2WSH HTLC by revealing the secret
+    Claim {
+        #[command(flatten)]
+        htlc: HtlcSpendArgs,
+        /// Recipient wallet config file (receives the funds)
+        #[arg(long)]
+        recipient_wallet: PathBuf,
+        /// Sender wallet config file (to rebuild the contract)
+        #[arg(long)]
+        sender_wallet: PathBuf,
+    },
+    /// Refund a P2WSH HTLC to the sender after its timeout
+    Refund {
+        #[command(flatten)]
+        htlc: HtlcSpendArgs,
+        /// Sender wallet config file (receives the refund)
+        #[arg(long)]
+        sender_wallet: PathBuf,
+        /// Recipient wallet config file (to rebuild the contract)
+        #[arg(long)]
+        recipient_wallet: PathBuf,
     },
     /// Withdraw from atomic swap HTLC
     Withdraw {
@@ -59,14 +437,71 @@ pub enum Commands {
         /// Original sender wallet config file (for network info)
         #[arg(short, long)]
         sender: PathBuf,
-        /// Amount in BTC of the original swap
-        #[arg(short, long)]
-        amount: f64,
+        /// Amount of the original swap: BTC by default, or with a unit like 1.5btc or 150000sat
+        #[arg(short, long, value_parser = parse_amount)]
+        amount: u64,
         /// Timelock duration in blocks from original swap
         #[arg(long, default_value = "144")]
         timelock: u32,
         /// Swap secret key (hex string from swap creation)
         #[arg(long)]
         swap_secret: String,
+        /// Fee rate in sat/vB (defaults to 20)
+        #[arg(long, value_parser = parse_fee_rate)]
+        fee_rate: Option<f32>,
+        /// Keep re-syncing for up to this many seconds while the contract is unfunded
+        #[arg(long)]
+        wait_secs: Option<u64>,
+        /// Seconds between re-syncs when --wait-secs is set
+        #[arg(long, default_value_t = DEFAULT_FUNDING_POLL_INTERVAL_SECS)]
+        poll_interval_secs: u64,
+        /// Spend the contract output before its funding transaction confirms
+        #[arg(long)]
+        include_unconfirmed: bool,
+        /// How the escrow key was aggregated, as the swap record or offer's `key_aggregation` says
+        #[arg(long, value_enum, default_value_t = KeyAggregation::Musig)]
+        key_aggregation: KeyAggregation,
+        #[command(flatten)]
+        confirmations: ConfirmationArgs,
     },
-}
\ No newline at end of file
+}
+
+/// Contract parameters shared by the claim and refund commands
+#[derive(clap::Args, Debug)]
+pub struct HtlcSpendArgs {
+    /// Funding transaction ID printed as the contract ID
+    #[arg(long)]
+    pub contract_txid: Txid,
+    /// Contract output index; looked up from the funding transaction if omitted
+    #[arg(long)]
+    pub vout: Option<u32>,
+    /// Amount in BTC locked in the contract
+    #[arg(short, long)]
+    pub amount: f64,
+    /// Secret preimage the contract was created with (0x-prefixed hex or text)
+    #[arg(long)]
+    pub secret: String,
+    /// Refund block height the contract was created with
+    #[arg(long)]
+    pub timeout: u32,
+    /// The contract was created with a relative (OP_CSV) timeout
+    #[arg(long)]
+    pub relative: bool,
+    /// Hash function the contract was created with
+    #[arg(long, value_enum, default_value_t = HashType::Sha256)]
+    pub hash_type: HashType,
+    /// Fee rate in sat/vB for the spending transaction (defaults to 20)
+    #[arg(long, value_parser = parse_fee_rate)]
+    pub fee_rate: Option<f32>,
+}
+
+/// Waiting for a broadcast transaction to confirm, shared by swap and withdraw
+#[derive(clap::Args, Debug, Clone, Copy)]
+pub struct ConfirmationArgs {
+    /// After broadcasting, wait until the transaction has this many confirmations
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub wait_confirmations: Option<u32>,
+    /// Seconds --wait-confirmations waits before giving up
+    #[arg(long, value_name = "SECS", default_value_t = DEFAULT_CONFIRMATION_TIMEOUT_SECS)]
+    pub confirmation_timeout_secs: u64,
+}
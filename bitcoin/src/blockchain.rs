@@ -1,16 +1,54 @@
-use bdk::blockchain::{ConfigurableBlockchain, RpcBlockchain};
-use bdk::bitcoin::Network;
-use eyre::Result;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
-use crate::constants::{DEFAULT_RPC_URL, DEFAULT_RPC_USERNAME, DEFAULT_RPC_PASSWORD};
+use bdk::FeeRate;
+use bdk::bitcoincore_rpc::RpcApi;
+use bdk::bitcoincore_rpc::jsonrpc;
+use bdk::blockchain::{
+    Blockchain, Capability, ConfigurableBlockchain, ElectrumBlockchain, EsploraBlockchain,
+    GetBlockHash, GetHeight, GetTx, Progress, RpcBlockchain, WalletSync,
+};
+use bdk::blockchain::electrum::ElectrumBlockchainConfig;
+use bdk::blockchain::esplora::EsploraBlockchainConfig;
+use bdk::blockchain::rpc::{RpcConfig, RpcSyncParams};
+use bdk::bitcoin::consensus::encode::serialize_hex;
+use bdk::bitcoin::{BlockHash, Network, Transaction, Txid};
+use bdk::database::BatchDatabase;
+use bdk::electrum_client::ElectrumApi;
+use eyre::{Result, WrapErr, eyre};
+use serde::Deserialize;
+
+use crate::constants::{
+    CONFIRMATION_POLL_INTERVAL_MS, DEFAULT_FEE_RATE_SAT_PER_VB, DEFAULT_RPC_CONNECT_ATTEMPTS, DEFAULT_RPC_PASSWORD,
+    DEFAULT_RPC_RETRY_BASE_DELAY_MS, DEFAULT_RPC_TIMEOUT_SECS, DEFAULT_RPC_URL, DEFAULT_RPC_USERNAME,
+    DEFAULT_STOP_GAP, RPC_PASSWORD_ENV, RPC_URL_ENV, RPC_USERNAME_ENV,
+};
+use crate::error::{BlockchainError, TransactionError};
 
 /// Configuration for Bitcoin RPC connection
-#[derive(Debug, Clone)]
+///
+/// Can be loaded from a TOML file with `url`, `username`, `password`,
+/// `stop_gap`, `timeout_secs` and `proxy` keys; missing keys fall back to the
+/// built-in defaults. The network always comes from the wallet being used.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct BitcoinRpcConfig {
     pub url: String,
     pub username: String,
     pub password: String,
+    #[serde(skip)]
     pub network: Network,
+    /// Unused addresses scanned past the last used or revealed one before sync stops looking
+    pub stop_gap: usize,
+    /// Seconds to wait for the node to answer while connecting; Electrum and
+    /// Esplora also apply it to every later request
+    pub timeout_secs: u64,
+    /// SOCKS5 proxy as `host:port`, e.g. `127.0.0.1:9050` for a local Tor daemon
+    pub proxy: Option<String>,
 }
 
 impl Default for BitcoinRpcConfig {
@@ -20,18 +58,352 @@ impl Default for BitcoinRpcConfig {
             username: DEFAULT_RPC_USERNAME.to_string(),
             password: DEFAULT_RPC_PASSWORD.to_string(),
             network: Network::Regtest,
+            stop_gap: DEFAULT_STOP_GAP,
+            timeout_secs: DEFAULT_RPC_TIMEOUT_SECS,
+            proxy: None,
+        }
+    }
+}
+
+/// RPC settings loaded from the `--rpc-config` file, if one was given
+static RPC_CONFIG_FILE: OnceLock<BitcoinRpcConfig> = OnceLock::new();
+
+/// Timeout from the `--rpc-timeout` flag, if one was given
+static RPC_TIMEOUT_OVERRIDE: OnceLock<u64> = OnceLock::new();
+
+/// Proxy from the `--proxy` flag, if one was given
+static RPC_PROXY_OVERRIDE: OnceLock<String> = OnceLock::new();
+
+/// Gap limit from the `--gap-limit` flag, if one was given
+static GAP_LIMIT_OVERRIDE: OnceLock<usize> = OnceLock::new();
+
+/// Set by `--allow-low-fee`: a fee rate under the node's relay minimum only warns
+static ALLOW_LOW_FEE: AtomicBool = AtomicBool::new(false);
+
+impl BitcoinRpcConfig {
+    /// Default connection settings for a node on the given network
+    pub fn for_network(network: Network) -> Self {
+        Self {
+            network,
+            ..Self::default()
+        }
+    }
+
+    /// Reads RPC settings from a TOML file
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read RPC config {}", path.display()))?;
+        toml::from_str(&contents)
+            .wrap_err_with(|| format!("Failed to parse RPC config {}", path.display()))
+    }
+
+    /// Default settings with any `BITCOIN_RPC_*` environment variables applied
+    pub fn from_env(network: Network) -> Self {
+        Self::from_env_lookup(network, |key| std::env::var(key).ok())
+    }
+
+    fn from_env_lookup(network: Network, lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let defaults = Self::for_network(network);
+        Self {
+            url: lookup(RPC_URL_ENV).unwrap_or(defaults.url),
+            username: lookup(RPC_USERNAME_ENV).unwrap_or(defaults.username),
+            password: lookup(RPC_PASSWORD_ENV).unwrap_or(defaults.password),
+            ..defaults
+        }
+    }
+
+    /// Connection settings for the given network, in order of precedence:
+    ///
+    /// 1. the TOML file passed with `--rpc-config`
+    /// 2. the `BITCOIN_RPC_URL`, `BITCOIN_RPC_USERNAME` and
+    ///    `BITCOIN_RPC_PASSWORD` environment variables
+    /// 3. the `DEFAULT_RPC_*` constants
+    ///
+    /// The `--rpc-timeout`, `--proxy` and `--gap-limit` flags override the
+    /// timeout, proxy and stop gap from any of them.
+    pub fn resolve(network: Network) -> Self {
+        let mut config = match RPC_CONFIG_FILE.get() {
+            Some(config) => Self {
+                network,
+                ..config.clone()
+            },
+            None => Self::from_env(network),
+        };
+        if let Some(timeout_secs) = RPC_TIMEOUT_OVERRIDE.get() {
+            config.timeout_secs = *timeout_secs;
+        }
+        if let Some(proxy) = RPC_PROXY_OVERRIDE.get() {
+            config.proxy = Some(proxy.clone());
+        }
+        if let Some(gap_limit) = GAP_LIMIT_OVERRIDE.get() {
+            config.stop_gap = *gap_limit;
+        }
+        config
+    }
+
+    /// How long to wait for the node to answer while connecting
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    /// Whether the URL points at an Electrum server rather than Bitcoin Core
+    pub fn is_electrum(&self) -> bool {
+        self.url.starts_with("tcp://") || self.url.starts_with("ssl://")
+    }
+
+    /// Whether the URL points at an Esplora HTTP API
+    pub fn is_esplora(&self) -> bool {
+        self.url.starts_with("https://")
+    }
+
+    /// Whether the URL's host is a Tor onion service
+    pub fn is_onion(&self) -> bool {
+        let authority = self.url.split_once("://").map_or(self.url.as_str(), |(_, rest)| rest);
+        let authority = authority.split(['/', '?']).next().unwrap_or_default();
+        let host_and_port = authority.rsplit('@').next().unwrap_or_default();
+        let host = host_and_port.split(':').next().unwrap_or_default();
+        host.to_ascii_lowercase().ends_with(".onion")
+    }
+
+    /// The proxy as a `socks5://` URL, which Esplora's HTTP client requires
+    fn proxy_url(&self) -> Option<String> {
+        self.proxy.as_ref().map(|proxy| {
+            if proxy.contains("://") {
+                proxy.clone()
+            } else {
+                format!("socks5://{}", proxy)
+            }
+        })
+    }
+}
+
+/// How often to retry connecting to a node that isn't accepting connections yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of connection attempts, including the first
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled after every failed attempt
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_delay: Duration) -> Self {
+        Self {
+            max_attempts,
+            base_delay,
         }
     }
+
+    /// Delay to wait after the given failed attempt (0-based)
+    fn delay_after(&self, attempt: u32) -> Duration {
+        self.base_delay.saturating_mul(2u32.saturating_pow(attempt))
+    }
+}
+
+/// Blockchain backend used for wallet sync and broadcast
+///
+/// bdk's `Blockchain` trait has generic methods and can't be used as a trait
+/// object, so this enum is the dispatch point between the supported backends.
+pub enum Backend {
+    /// Bitcoin Core over JSON-RPC
+    Core(Box<RpcBlockchain>),
+    /// Electrum or electrs server
+    Electrum(Box<ElectrumBlockchain>),
+    /// Esplora HTTP API, e.g. blockstream.info
+    Esplora(Box<EsploraBlockchain>),
 }
 
-/// Creates a configured Bitcoin RPC blockchain client
-pub fn create_bitcoin_rpc_client() -> Result<RpcBlockchain> {
-    create_bitcoin_rpc_client_with_config(BitcoinRpcConfig::default())
+macro_rules! delegate {
+    ($backend:expr, $inner:ident => $call:expr) => {
+        match $backend {
+            Backend::Core($inner) => $call,
+            Backend::Electrum($inner) => $call,
+            Backend::Esplora($inner) => $call,
+        }
+    };
+}
+
+impl Blockchain for Backend {
+    fn get_capabilities(&self) -> HashSet<Capability> {
+        delegate!(self, inner => inner.get_capabilities())
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<(), bdk::Error> {
+        match self {
+            Backend::Esplora(client) => broadcast_via_esplora(client, tx),
+            Backend::Core(inner) => inner.broadcast(tx),
+            Backend::Electrum(inner) => inner.broadcast(tx),
+        }
+    }
+
+    fn estimate_fee(&self, target: usize) -> Result<FeeRate, bdk::Error> {
+        delegate!(self, inner => inner.estimate_fee(target))
+    }
+}
+
+impl GetHeight for Backend {
+    fn get_height(&self) -> Result<u32, bdk::Error> {
+        delegate!(self, inner => inner.get_height())
+    }
+}
+
+impl GetTx for Backend {
+    fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>, bdk::Error> {
+        delegate!(self, inner => inner.get_tx(txid))
+    }
+}
+
+impl GetBlockHash for Backend {
+    fn get_block_hash(&self, height: u64) -> Result<BlockHash, bdk::Error> {
+        delegate!(self, inner => inner.get_block_hash(height))
+    }
+}
+
+impl WalletSync for Backend {
+    fn wallet_setup<D: BatchDatabase>(
+        &self,
+        database: &RefCell<D>,
+        progress_update: Box<dyn Progress>,
+    ) -> Result<(), bdk::Error> {
+        delegate!(self, inner => inner.wallet_setup(database, progress_update))
+    }
+
+    fn wallet_sync<D: BatchDatabase>(
+        &self,
+        database: &RefCell<D>,
+        progress_update: Box<dyn Progress>,
+    ) -> Result<(), bdk::Error> {
+        delegate!(self, inner => inner.wallet_sync(database, progress_update))
+    }
+}
+
+/// Loads the `--rpc-config` file used by every backend created afterwards
+pub fn load_rpc_config_file(path: &Path) -> Result<()> {
+    let config = BitcoinRpcConfig::from_file(path)?;
+    RPC_CONFIG_FILE
+        .set(config)
+        .map_err(|_| eyre!("RPC config has already been loaded"))
+}
+
+/// Sets the `--rpc-timeout` used by every backend created afterwards
+pub fn set_rpc_timeout(timeout_secs: u64) -> Result<()> {
+    if timeout_secs == 0 {
+        return Err(eyre!("RPC timeout must be at least one second"));
+    }
+    RPC_TIMEOUT_OVERRIDE
+        .set(timeout_secs)
+        .map_err(|_| eyre!("RPC timeout has already been set"))
+}
+
+/// Sets the `--proxy` used by every backend created afterwards
+pub fn set_rpc_proxy(proxy: String) -> Result<()> {
+    RPC_PROXY_OVERRIDE
+        .set(proxy)
+        .map_err(|_| eyre!("RPC proxy has already been set"))
+}
+
+/// Sets the `--gap-limit` used by every wallet sync afterwards
+pub fn set_gap_limit(gap_limit: usize) -> Result<()> {
+    if gap_limit == 0 {
+        return Err(eyre!("Gap limit must be at least one address"));
+    }
+    GAP_LIMIT_OVERRIDE
+        .set(gap_limit)
+        .map_err(|_| eyre!("Gap limit has already been set"))
+}
+
+/// Creates the blockchain backend for the given network
+///
+/// Connection settings are resolved with [`BitcoinRpcConfig::resolve`].
+pub fn create_blockchain_backend(network: Network) -> Result<Backend> {
+    create_blockchain_backend_with_config(BitcoinRpcConfig::resolve(network))
+}
+
+/// Creates a blockchain backend from the URL scheme: Electrum for
+/// `tcp://`/`ssl://`, Esplora for `https://` and Bitcoin Core otherwise
+///
+/// Electrum and Esplora connect through the configured SOCKS5 proxy, if any.
+/// An onion URL without a proxy fails up front rather than with a DNS error.
+pub fn create_blockchain_backend_with_config(config: BitcoinRpcConfig) -> Result<Backend> {
+    let proxyable = config.is_electrum() || config.is_esplora();
+    if config.is_onion() && config.proxy.is_none() && proxyable {
+        return Err(BlockchainError::ConnectionFailed(format!(
+            "{} is a Tor onion service; pass --proxy 127.0.0.1:9050 (or set `proxy` in the RPC config) to reach it through Tor",
+            config.url
+        ))
+        .into());
+    }
+
+    if config.is_electrum() {
+        let electrum_config = ElectrumBlockchainConfig {
+            socks5: config.proxy.clone(),
+            url: config.url,
+            retry: 3,
+            // Electrum takes whole seconds in a u8
+            timeout: Some(u8::try_from(config.timeout_secs).unwrap_or(u8::MAX)),
+            stop_gap: config.stop_gap,
+            validate_domain: true,
+        };
+        return Ok(Backend::Electrum(Box::new(ElectrumBlockchain::from_config(
+            &electrum_config,
+        )?)));
+    }
+
+    if config.is_esplora() {
+        let esplora_config = EsploraBlockchainConfig {
+            base_url: config.url.trim_end_matches('/').to_string(),
+            proxy: config.proxy_url(),
+            concurrency: None,
+            stop_gap: config.stop_gap,
+            timeout: Some(config.timeout_secs),
+        };
+        return Ok(Backend::Esplora(Box::new(EsploraBlockchain::from_config(
+            &esplora_config,
+        )?)));
+    }
+
+    Ok(Backend::Core(Box::new(create_bitcoin_rpc_client_with_retries(
+        config,
+        DEFAULT_RPC_CONNECT_ATTEMPTS,
+    )?)))
+}
+
+/// Creates a Bitcoin RPC blockchain client, retrying with exponential backoff
+/// while the node refuses connections or is still warming up
+pub fn create_bitcoin_rpc_client_with_retries(
+    config: BitcoinRpcConfig,
+    max_attempts: u32,
+) -> Result<RpcBlockchain> {
+    let retry_policy = RetryPolicy::new(
+        max_attempts,
+        Duration::from_millis(DEFAULT_RPC_RETRY_BASE_DELAY_MS),
+    );
+    create_bitcoin_rpc_client_with_config(config, Some(retry_policy))
 }
 
 /// Creates a Bitcoin RPC blockchain client with custom configuration
-pub fn create_bitcoin_rpc_client_with_config(config: BitcoinRpcConfig) -> Result<RpcBlockchain> {
-    let rpc_config = bdk::blockchain::rpc::RpcConfig {
+///
+/// Without a retry policy the connection is attempted once. With one,
+/// connection-refused and warm-up errors are retried up to
+/// `max_attempts` times; any other error is returned immediately. A node
+/// that does not answer within the timeout while connecting fails with
+/// [`BlockchainError::NetworkError`].
+///
+/// bdk builds the RPC transport itself, so it cannot be routed through a
+/// proxy; a configured proxy or an onion URL is rejected instead.
+pub fn create_bitcoin_rpc_client_with_config(
+    config: BitcoinRpcConfig,
+    retry_policy: Option<RetryPolicy>,
+) -> Result<RpcBlockchain> {
+    if config.proxy.is_some() || config.is_onion() {
+        return Err(BlockchainError::ConnectionFailed(format!(
+            "{}: Bitcoin Core RPC cannot go through a proxy; proxies and onion services are only supported for Electrum (tcp://, ssl://) and Esplora (https://) URLs",
+            config.url
+        ))
+        .into());
+    }
+    let timeout = config.timeout();
+    let rpc_config = RpcConfig {
         url: config.url,
         auth: bdk::blockchain::rpc::Auth::UserPass {
             username: config.username,
@@ -39,8 +411,579 @@ pub fn create_bitcoin_rpc_client_with_config(config: BitcoinRpcConfig) -> Result
         },
         network: config.network,
         wallet_name: String::new(), // Use empty wallet name to avoid import issues
-        sync_params: None,
+        // Core only scans the scripts it imported, so import at least one gap's worth
+        sync_params: Some(RpcSyncParams {
+            start_script_count: RpcSyncParams::default().start_script_count.max(config.stop_gap),
+            ..RpcSyncParams::default()
+        }),
     };
 
-    Ok(RpcBlockchain::from_config(&rpc_config)?)
-}
\ No newline at end of file
+    let max_attempts = retry_policy.map_or(1, |policy| policy.max_attempts.max(1));
+    let mut attempt = 0;
+    loop {
+        match probe_node(&rpc_config, timeout).and_then(|()| RpcBlockchain::from_config(&rpc_config)) {
+            Ok(client) => return Ok(client),
+            Err(e) if is_timeout(&e) => {
+                return Err(BlockchainError::NetworkError(format!(
+                    "timed out after {}s waiting for {}",
+                    timeout.as_secs(),
+                    rpc_config.url
+                ))
+                .into());
+            }
+            Err(e) if attempt + 1 < max_attempts && is_node_starting(&e) => {
+                if let Some(policy) = retry_policy {
+                    std::thread::sleep(policy.delay_after(attempt));
+                }
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(e).wrap_err_with(|| {
+                    format!(
+                        "Failed to connect to {} after {} attempt(s)",
+                        rpc_config.url,
+                        attempt + 1
+                    )
+                });
+            }
+        }
+    }
+}
+
+/// Ask the node for its version over a transport that gives up after `timeout`
+///
+/// bdk builds the RPC transport itself, with a fixed 15 second socket
+/// timeout, so an unresponsive node is caught here before bdk connects.
+fn probe_node(rpc_config: &RpcConfig, timeout: Duration) -> Result<(), bdk::Error> {
+    let auth = bdk::bitcoincore_rpc::Auth::from(rpc_config.auth.clone());
+    let (username, password) = auth.get_user_pass()?;
+    let mut transport = jsonrpc::simple_http::SimpleHttpTransport::builder()
+        .url(&rpc_config.url)
+        .map_err(|e| bdk::Error::Rpc(bdk::bitcoincore_rpc::Error::JsonRpc(e.into())))?
+        .timeout(timeout);
+    if let Some(username) = username {
+        transport = transport.auth(username, password);
+    }
+    let client = bdk::bitcoincore_rpc::Client::from_jsonrpc(jsonrpc::Client::with_transport(transport.build()));
+    client.version()?;
+    Ok(())
+}
+
+/// Whether an RPC error is the socket timing out
+fn is_timeout(error: &bdk::Error) -> bool {
+    let bdk::Error::Rpc(bdk::bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Transport(transport))) = error else {
+        return false;
+    };
+    matches!(
+        transport.downcast_ref::<jsonrpc::simple_http::Error>(),
+        Some(jsonrpc::simple_http::Error::SocketError(io_error))
+            if matches!(io_error.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock)
+    )
+}
+
+/// Whether an RPC error means the node is not up yet rather than misconfigured
+fn is_node_starting(error: &bdk::Error) -> bool {
+    /// Bitcoin Core's RPC_IN_WARMUP code, returned while loading the block index
+    const RPC_IN_WARMUP: i32 = -28;
+
+    let bdk::Error::Rpc(bdk::bitcoincore_rpc::Error::JsonRpc(rpc_error)) = error else {
+        return false;
+    };
+
+    match rpc_error {
+        jsonrpc::Error::Rpc(response) => response.code == RPC_IN_WARMUP,
+        jsonrpc::Error::Transport(transport) => matches!(
+            transport.downcast_ref::<jsonrpc::simple_http::Error>(),
+            Some(jsonrpc::simple_http::Error::SocketError(io_error))
+                if matches!(
+                    io_error.kind(),
+                    std::io::ErrorKind::ConnectionRefused | std::io::ErrorKind::ConnectionReset
+                )
+        ),
+        _ => false,
+    }
+}
+
+/// Posts a transaction to Esplora's `/tx` endpoint
+///
+/// bdk's Esplora client drops the response body on HTTP errors, leaving only
+/// the status code. The body carries the node's reject reason (e.g.
+/// `min relay fee not met`), so the request is made directly to keep it.
+fn broadcast_via_esplora(client: &EsploraBlockchain, tx: &Transaction) -> Result<(), bdk::Error> {
+    let response = client
+        .agent()
+        .post(&format!("{}/tx", client.url()))
+        .send_string(&serialize_hex(tx));
+
+    match response {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(code, response)) => {
+            let message = response.into_string().unwrap_or_default();
+            Err(bdk::Error::Generic(format!(
+                "Esplora rejected transaction (HTTP {}): {}",
+                code,
+                message.trim()
+            )))
+        }
+        Err(e) => Err(bdk::Error::Generic(format!("Esplora request failed: {}", e))),
+    }
+}
+
+/// Estimates a fee rate for confirmation within `target_blocks`
+///
+/// Bitcoin Core is queried with `estimatesmartfee`, Electrum with
+/// `blockchain.estimatefee` and Esplora with `/fee-estimates`. Falls back to the default fee rate when the
+/// backend has no estimate, which is the norm on regtest where there is no
+/// fee market.
+pub fn estimate_fee_rate(backend: &Backend, target_blocks: u16) -> Result<FeeRate> {
+    let default_fee_rate = FeeRate::from_sat_per_vb(DEFAULT_FEE_RATE_SAT_PER_VB);
+
+    match backend {
+        Backend::Core(client) => {
+            let estimate = client
+                .estimate_smart_fee(target_blocks, None)
+                .map_err(|e| eyre!("estimatesmartfee failed: {}", e))?;
+
+            Ok(match estimate.fee_rate {
+                // The node reports BTC per kvB
+                Some(fee_per_kvb) => {
+                    FeeRate::from_sat_per_vb(fee_per_kvb.to_sat() as f32 / 1000.0)
+                }
+                None => default_fee_rate,
+            })
+        }
+        Backend::Electrum(_) | Backend::Esplora(_) => {
+            let estimate = backend.estimate_fee(target_blocks as usize)?;
+            // Electrum reports -1 and Esplora an empty map when there is no estimate
+            if estimate.as_sat_per_vb() > 0.0 {
+                Ok(estimate)
+            } else {
+                Ok(default_fee_rate)
+            }
+        }
+    }
+}
+
+/// Lowest fee rate the node relays, or `None` if the backend cannot tell
+///
+/// Bitcoin Core reports its `minrelaytxfee` as `relayfee` in `getnetworkinfo`
+/// and Electrum through `blockchain.relayfee`; Esplora has no equivalent.
+pub fn min_relay_fee_rate(backend: &Backend) -> Result<Option<FeeRate>> {
+    match backend {
+        Backend::Core(client) => {
+            let info = client
+                .get_network_info()
+                .map_err(|e| BlockchainError::RpcError(format!("getnetworkinfo failed: {}", e)))?;
+            Ok(Some(FeeRate::from_btc_per_kvb(info.relay_fee.to_btc() as f32)))
+        }
+        Backend::Electrum(client) => {
+            let btc_per_kvb = client
+                .relay_fee()
+                .map_err(|e| BlockchainError::RpcError(format!("Electrum relay fee failed: {}", e)))?;
+            Ok(Some(FeeRate::from_btc_per_kvb(btc_per_kvb as f32)))
+        }
+        Backend::Esplora(_) => Ok(None),
+    }
+}
+
+/// Make [`ensure_relayable_fee_rate`] warn instead of failing
+pub fn set_allow_low_fee(allow_low_fee: bool) {
+    ALLOW_LOW_FEE.store(allow_low_fee, Ordering::Relaxed);
+}
+
+/// Check `fee_rate` against the node's minimum relay fee before building with it
+///
+/// The node would reject a transaction paying less, but only at broadcast.
+/// Fails suggesting the minimum instead, or just warns under `--allow-low-fee`.
+pub fn ensure_relayable_fee_rate(backend: &Backend, fee_rate: FeeRate) -> Result<()> {
+    match min_relay_fee_rate(backend)? {
+        Some(min_fee_rate) => check_relayable(fee_rate, min_fee_rate, ALLOW_LOW_FEE.load(Ordering::Relaxed)),
+        None => Ok(()),
+    }
+}
+
+fn check_relayable(fee_rate: FeeRate, min_fee_rate: FeeRate, allow_low_fee: bool) -> Result<()> {
+    if fee_rate.as_sat_per_vb() >= min_fee_rate.as_sat_per_vb() {
+        return Ok(());
+    }
+    let message = format!(
+        "Fee rate {} sat/vB is below the node's minimum relay fee of {} sat/vB; use --fee-rate {} or higher",
+        fee_rate.as_sat_per_vb(),
+        min_fee_rate.as_sat_per_vb(),
+        min_fee_rate.as_sat_per_vb()
+    );
+    if allow_low_fee {
+        log::warn!("{}", message);
+        return Ok(());
+    }
+    Err(TransactionError::InvalidAmount(message).into())
+}
+
+/// What a node reports about its view of the chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeStatus {
+    /// Chain name from `getblockchaininfo` (`main`, `test`, `signet`, `regtest`);
+    /// Electrum and Esplora servers do not report it
+    pub chain: Option<String>,
+    /// Height of the best block
+    pub height: u32,
+    /// Estimated fraction of the chain verified, 0 to 1 (Bitcoin Core only)
+    pub verification_progress: Option<f64>,
+    /// Whether the node is still in initial block download (Bitcoin Core only)
+    pub initial_block_download: Option<bool>,
+}
+
+impl NodeStatus {
+    /// Whether the node has caught up with the chain, as far as the backend can tell
+    pub fn is_synced(&self) -> bool {
+        self.initial_block_download != Some(true)
+    }
+}
+
+/// Connect to the configured node for `network` and report its status
+///
+/// Fails with [`BlockchainError::ConnectionFailed`] if the node cannot be
+/// reached or is on another network.
+pub fn check_node(network: Network) -> Result<NodeStatus> {
+    check_node_with_config(BitcoinRpcConfig::resolve(network))
+}
+
+/// [`check_node`] with explicit connection settings
+pub fn check_node_with_config(config: BitcoinRpcConfig) -> Result<NodeStatus> {
+    let backend = create_blockchain_backend_with_config(config)
+        .map_err(|e| BlockchainError::ConnectionFailed(format!("{:#}", e)))?;
+    node_status(&backend)
+}
+
+/// Ask a connected backend for its chain, height and sync progress
+pub fn node_status(backend: &Backend) -> Result<NodeStatus> {
+    if let Backend::Core(client) = backend {
+        let info = client
+            .get_blockchain_info()
+            .map_err(|e| BlockchainError::RpcError(format!("getblockchaininfo failed: {}", e)))?;
+        return Ok(NodeStatus {
+            chain: Some(info.chain),
+            height: info.blocks as u32,
+            verification_progress: Some(info.verification_progress),
+            initial_block_download: Some(info.initial_block_download),
+        });
+    }
+
+    let height = backend
+        .get_height()
+        .map_err(|e| BlockchainError::RpcError(format!("Failed to get the chain tip: {}", e)))?;
+    Ok(NodeStatus {
+        chain: None,
+        height,
+        verification_progress: None,
+        initial_block_download: None,
+    })
+}
+
+/// Number of confirmations `txid` has right now
+///
+/// Unconfirmed transactions, and ones the backend does not know about yet,
+/// have zero. Bitcoin Core needs `-txindex` to find confirmed transactions
+/// that do not belong to its own wallet.
+pub fn get_confirmations(backend: &Backend, txid: &Txid) -> Result<u32> {
+    let tip_height = backend.get_height()?;
+    let block_height = match backend {
+        Backend::Core(client) => {
+            return match client.get_raw_transaction_info(txid, None) {
+                Ok(info) => Ok(info.confirmations.unwrap_or(0)),
+                // RPC_INVALID_ADDRESS_OR_KEY: no such mempool or blockchain transaction
+                Err(bdk::bitcoincore_rpc::Error::JsonRpc(jsonrpc::Error::Rpc(ref e)))
+                    if e.code == -5 =>
+                {
+                    Ok(0)
+                }
+                Err(e) => {
+                    Err(BlockchainError::RpcError(format!("getrawtransaction failed: {}", e)).into())
+                }
+            };
+        }
+        Backend::Esplora(client) => client
+            .get_tx_status(txid)
+            .map_err(|e| BlockchainError::RpcError(format!("Esplora tx status failed: {}", e)))?
+            .and_then(|status| status.block_height),
+        Backend::Electrum(client) => {
+            // Electrum only reports heights through the history of a script
+            let Ok(transaction) = client.transaction_get(txid) else {
+                return Ok(0);
+            };
+            let Some(output) = transaction.output.first() else {
+                return Ok(0);
+            };
+            client
+                .script_get_history(&output.script_pubkey)
+                .map_err(|e| BlockchainError::RpcError(format!("Electrum history failed: {}", e)))?
+                .into_iter()
+                .find(|entry| entry.tx_hash == *txid && entry.height > 0)
+                .map(|entry| entry.height as u32)
+        }
+    };
+    Ok(block_height.map_or(0, |height| confirmations_at(tip_height, height)))
+}
+
+/// Confirmations of a transaction mined at `block_height` with the chain at `tip_height`
+fn confirmations_at(tip_height: u32, block_height: u32) -> u32 {
+    (tip_height + 1).saturating_sub(block_height)
+}
+
+/// Poll until `txid` has at least `confirmations` confirmations
+///
+/// Returns the confirmation count once it is reached, checking every
+/// [`CONFIRMATION_POLL_INTERVAL_MS`]. Fails if `timeout` runs out first.
+pub async fn wait_for_confirmations(
+    client: &Backend,
+    txid: &Txid,
+    confirmations: u32,
+    timeout: Duration,
+) -> Result<u32> {
+    let poll_interval = Duration::from_millis(CONFIRMATION_POLL_INTERVAL_MS);
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let current = get_confirmations(client, txid)?;
+        if current >= confirmations {
+            return Ok(current);
+        }
+        if Instant::now() + poll_interval > deadline {
+            return Err(eyre!(
+                "Timed out after {:?} waiting for {} to reach {} confirmations (has {})",
+                timeout,
+                txid,
+                confirmations,
+                current
+            ));
+        }
+        log::debug!(
+            "{} has {}/{} confirmations, checking again in {:?}",
+            txid,
+            current,
+            confirmations,
+            poll_interval
+        );
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::str::FromStr;
+
+    /// Serve a fixed Esplora chain tip and status for every transaction
+    ///
+    /// Each request gets its own connection, which is closed after the reply.
+    fn fake_esplora(tip_height: u32, tx_block_height: u32) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                BufReader::new(&stream).read_line(&mut request_line).unwrap();
+                let body = if request_line.contains("/blocks/tip/height") {
+                    tip_height.to_string()
+                } else {
+                    format!(r#"{{"confirmed": true, "block_height": {}}}"#, tx_block_height)
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn waiting_returns_confirmations_or_times_out() {
+        let txid =
+            Txid::from_str("e8b0e1a1f4c1b0d5a6f3e2c9d8b7a6f5e4d3c2b1a0f9e8d7c6b5a4f3e2d1c0b9").unwrap();
+        // Mined in block 100 with the tip at 102
+        let backend =
+            Backend::Esplora(Box::new(EsploraBlockchain::new(&fake_esplora(102, 100), 20)));
+
+        let confirmations = wait_for_confirmations(&backend, &txid, 2, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(confirmations, 3);
+
+        let started = Instant::now();
+        let err = wait_for_confirmations(&backend, &txid, 6, Duration::from_secs(1))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("has 3"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn node_status_reports_the_tip_and_unreachable_nodes_fail_to_connect() {
+        let backend =
+            Backend::Esplora(Box::new(EsploraBlockchain::new(&fake_esplora(321, 0), 20)));
+        let status = node_status(&backend).unwrap();
+        assert_eq!(status.height, 321);
+        assert_eq!(status.chain, None);
+        assert!(status.is_synced());
+
+        // Nothing listens on port 1
+        let config = BitcoinRpcConfig {
+            url: "http://127.0.0.1:1".to_string(),
+            ..BitcoinRpcConfig::default()
+        };
+        let err = check_node_with_config(config).unwrap_err();
+        assert!(
+            matches!(err.downcast_ref::<BlockchainError>(), Some(BlockchainError::ConnectionFailed(_))),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn https_url_selects_esplora_backend() {
+        let config = BitcoinRpcConfig {
+            url: "https://blockstream.info/testnet/api/".to_string(),
+            network: Network::Testnet,
+            stop_gap: 50,
+            ..BitcoinRpcConfig::default()
+        };
+        assert!(config.is_esplora());
+        assert!(!config.is_electrum());
+
+        let backend = create_blockchain_backend_with_config(config).unwrap();
+        match backend {
+            Backend::Esplora(client) => {
+                assert_eq!(client.url(), "https://blockstream.info/testnet/api")
+            }
+            _ => panic!("expected Esplora backend"),
+        }
+    }
+
+    #[test]
+    fn onion_urls_need_a_proxy_and_core_cannot_use_one() {
+        let onion = "https://explorerzydxu5ecjrkwceayqybizmpjjznk5izmitf2modhcusuqlid.onion/api";
+        let config = BitcoinRpcConfig {
+            url: onion.to_string(),
+            ..BitcoinRpcConfig::default()
+        };
+        assert!(config.is_onion());
+        let err = create_blockchain_backend_with_config(config.clone()).err().unwrap();
+        assert!(err.to_string().contains("--proxy"), "{}", err);
+
+        // Esplora's client is built without connecting, so the proxy is only checked later
+        let proxied = BitcoinRpcConfig {
+            proxy: Some("127.0.0.1:9050".to_string()),
+            ..config
+        };
+        assert_eq!(proxied.proxy_url().as_deref(), Some("socks5://127.0.0.1:9050"));
+        assert!(matches!(
+            create_blockchain_backend_with_config(proxied).unwrap(),
+            Backend::Esplora(_)
+        ));
+
+        let core = BitcoinRpcConfig {
+            url: "http://127.0.0.1:18443".to_string(),
+            proxy: Some("127.0.0.1:9050".to_string()),
+            ..BitcoinRpcConfig::default()
+        };
+        assert!(!core.is_onion());
+        let err = create_bitcoin_rpc_client_with_config(core, None).err().unwrap();
+        assert!(err.to_string().contains("Electrum"), "{}", err);
+    }
+
+    #[test]
+    fn unresponsive_node_times_out() {
+        // The listener completes the TCP handshake but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = BitcoinRpcConfig {
+            url: format!("http://{}", listener.local_addr().unwrap()),
+            timeout_secs: 1,
+            ..BitcoinRpcConfig::default()
+        };
+
+        let started = std::time::Instant::now();
+        let err = create_bitcoin_rpc_client_with_config(config, None).err().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        match err.downcast_ref::<BlockchainError>() {
+            Some(BlockchainError::NetworkError(message)) => {
+                assert!(message.contains("timed out"), "{}", message)
+            }
+            _ => panic!("expected a timeout, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn retry_delay_doubles_after_each_attempt() {
+        let policy = RetryPolicy::new(4, Duration::from_millis(100));
+        assert_eq!(policy.delay_after(0), Duration::from_millis(100));
+        assert_eq!(policy.delay_after(1), Duration::from_millis(200));
+        assert_eq!(policy.delay_after(2), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn connection_refused_is_retried_until_attempts_run_out() {
+        // Nothing listens on port 1, so every attempt is refused
+        let config = BitcoinRpcConfig {
+            url: "http://127.0.0.1:1".to_string(),
+            ..BitcoinRpcConfig::default()
+        };
+        let policy = RetryPolicy::new(3, Duration::from_millis(1));
+
+        let Err(error) = create_bitcoin_rpc_client_with_config(config, Some(policy)) else {
+            panic!("connection should be refused");
+        };
+        assert!(error.to_string().contains("after 3 attempt(s)"));
+    }
+
+    #[test]
+    fn environment_variables_override_default_rpc_settings() {
+        let config = BitcoinRpcConfig::from_env_lookup(Network::Regtest, |key| match key {
+            RPC_URL_ENV => Some("http://10.0.0.5:18443".to_string()),
+            RPC_USERNAME_ENV => Some("alice".to_string()),
+            _ => None,
+        });
+
+        assert_eq!(config.url, "http://10.0.0.5:18443");
+        assert_eq!(config.username, "alice");
+        assert_eq!(config.password, DEFAULT_RPC_PASSWORD);
+        assert_eq!(config.network, Network::Regtest);
+    }
+
+    #[test]
+    fn rpc_config_file_fills_missing_keys_with_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rpc.toml");
+        std::fs::write(&path, "url = \"ssl://electrum.example.com:50002\"\nstop_gap = 100\n")
+            .unwrap();
+
+        let config = BitcoinRpcConfig::from_file(&path).unwrap();
+        assert_eq!(config.url, "ssl://electrum.example.com:50002");
+        assert_eq!(config.stop_gap, 100);
+        assert_eq!(config.username, DEFAULT_RPC_USERNAME);
+        assert_eq!(config.timeout_secs, DEFAULT_RPC_TIMEOUT_SECS);
+    }
+
+    #[test]
+    fn fee_rates_below_the_relay_minimum_are_rejected_with_a_suggestion() {
+        let min_fee_rate = FeeRate::from_btc_per_kvb(0.00001);
+        assert!(check_relayable(FeeRate::from_sat_per_vb(1.0), min_fee_rate, false).is_ok());
+        assert!(check_relayable(FeeRate::from_sat_per_vb(20.0), min_fee_rate, false).is_ok());
+
+        let err = check_relayable(FeeRate::from_sat_per_vb(0.5), min_fee_rate, false).unwrap_err();
+        assert!(
+            matches!(err.downcast_ref::<TransactionError>(), Some(TransactionError::InvalidAmount(_))),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains("use --fee-rate 1 or higher"), "{}", err);
+
+        // --allow-low-fee only warns
+        assert!(check_relayable(FeeRate::from_sat_per_vb(0.5), min_fee_rate, true).is_ok());
+    }
+}
@@ -1,10 +1,62 @@
 /// Default fee rate for transactions (20 sat/vByte, equivalent to 0.0002 BTC/kB)
 pub const DEFAULT_FEE_RATE_SAT_PER_VB: f32 = 20.0;
 
+/// Largest OP_RETURN payload relayed by default Bitcoin Core policy
+pub const MAX_OP_RETURN_DATA_LEN: usize = 80;
+
 /// Satoshis per Bitcoin
 pub const SATOSHIS_PER_BTC: u64 = 100_000_000;
 
+/// Total bitcoin supply in satoshis, the largest valid amount
+pub const MAX_MONEY_SAT: u64 = 21_000_000 * SATOSHIS_PER_BTC;
+
+/// Default account derivation paths when a wallet config omits `derivation_path`
+///
+/// Mainnet uses coin type 0; testnet, signet and regtest share coin type 1.
+pub const MAINNET_BIP84_PATH: &str = "m/84h/0h/0h";
+pub const REGTEST_BIP84_PATH: &str = "m/84h/1h/0h";
+pub const MAINNET_BIP49_PATH: &str = "m/49h/0h/0h";
+pub const REGTEST_BIP49_PATH: &str = "m/49h/1h/0h";
+pub const MAINNET_BIP86_PATH: &str = "m/86h/0h/0h";
+pub const REGTEST_BIP86_PATH: &str = "m/86h/1h/0h";
+
+/// Hardened purpose under the master key for deterministic swap secrets, ASCII "SWAP"
+///
+/// Swap secrets are derived at `m/<purpose>h/<index>h`, away from every BIP44-style account.
+pub const SWAP_SECRET_PURPOSE: u32 = 0x5357_4150;
+
 /// Default RPC configuration
 pub const DEFAULT_RPC_URL: &str = "http://127.0.0.1:18443";
 pub const DEFAULT_RPC_USERNAME: &str = "bitcoin";
 pub const DEFAULT_RPC_PASSWORD: &str = "bitcoin";
+
+/// Connection attempts made while the node is still starting up
+pub const DEFAULT_RPC_CONNECT_ATTEMPTS: u32 = 5;
+/// Delay before the first RPC connection retry, doubled on each further retry
+pub const DEFAULT_RPC_RETRY_BASE_DELAY_MS: u64 = 250;
+/// Seconds to wait for a node to answer while connecting before giving up
+pub const DEFAULT_RPC_TIMEOUT_SECS: u64 = 30;
+
+/// Environment variables that override the default RPC configuration
+pub const RPC_URL_ENV: &str = "BITCOIN_RPC_URL";
+pub const RPC_USERNAME_ENV: &str = "BITCOIN_RPC_USERNAME";
+pub const RPC_PASSWORD_ENV: &str = "BITCOIN_RPC_PASSWORD";
+
+/// Number of consecutive unused addresses after which Electrum sync stops scanning
+pub const DEFAULT_STOP_GAP: usize = 20;
+
+/// Directory where a `SwapRecord` is written for every funded atomic swap
+pub const SWAPS_DIR: &str = "swaps";
+/// Copy of the most recent swap record, read by the prover and the 1inch resolver
+pub const LATEST_SWAP_RECORD_FILE: &str = "swap_bitcoin.json";
+
+/// Environment variable holding the log level (error, warn, info, debug, trace)
+pub const LOG_LEVEL_ENV: &str = "RUST_LOG";
+
+/// Seconds between wallet re-syncs while waiting for a contract to be funded
+pub const DEFAULT_FUNDING_POLL_INTERVAL_SECS: u64 = 5;
+
+/// Delay between status checks while waiting for a transaction to confirm
+pub const CONFIRMATION_POLL_INTERVAL_MS: u64 = 500;
+/// Seconds `--wait-confirmations` waits before giving up
+pub const DEFAULT_CONFIRMATION_TIMEOUT_SECS: u64 = 3600;
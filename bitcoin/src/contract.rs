@@ -0,0 +1,1286 @@
+//! P2WSH Hash Time Locked Contracts (HTLCs)
+//!
+//! A classic script-based HTLC: the recipient can spend by revealing the
+//! preimage of the hash lock, the sender can take the funds back once the
+//! chain reaches the timeout height, or once the funding output is a given
+//! number of blocks deep. The hash lock is either SHA256 or, for
+//! counterparties such as Lightning, HASH160 (`RIPEMD160(SHA256(preimage))`).
+//!
+//! ```text
+//! OP_IF
+//!     <OP_SHA256|OP_HASH160> <hash_lock> OP_EQUALVERIFY <recipient_pubkey> OP_CHECKSIG
+//! OP_ELSE
+//!     <timeout> <OP_CHECKLOCKTIMEVERIFY|OP_CHECKSEQUENCEVERIFY> OP_DROP <sender_pubkey> OP_CHECKSIG
+//! OP_ENDIF
+//! ```
+//!
+//! [`HTLCContract::to_taproot`] gives the same contract as a SegWit v1
+//! output, [`TaprootHtlc`]: each branch becomes a tapscript leaf under an
+//! unspendable internal key, so a spend only reveals the branch it uses.
+
+use bdk::bitcoin::blockdata::opcodes::All as Opcode;
+use bdk::bitcoin::blockdata::opcodes::all::{
+    OP_CHECKSIG, OP_CLTV, OP_CSV, OP_DROP, OP_ELSE, OP_ENDIF, OP_EQUALVERIFY, OP_HASH160, OP_IF,
+    OP_SHA256,
+};
+use bdk::FeeRate;
+use bdk::bitcoin::blockdata::script::Builder;
+use bdk::bitcoin::hashes::{Hash, hash160, sha256};
+use bdk::bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+use bdk::bitcoin::util::sighash::SighashCache;
+use bdk::bitcoin::{
+    Address, Amount, EcdsaSig, EcdsaSighashType, Network, OutPoint, PackedLockTime, PublicKey,
+    Script, Sequence, Transaction, TxIn, TxOut, Witness,
+};
+use rand::{CryptoRng, RngCore};
+
+// Only the taproot encoding, which no command funds yet, needs these
+#[cfg(test)]
+use bdk::bitcoin::secp256k1::{KeyPair, XOnlyPublicKey};
+#[cfg(test)]
+use bdk::bitcoin::util::sighash::Prevouts;
+#[cfg(test)]
+use bdk::bitcoin::util::taproot::{LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo};
+#[cfg(test)]
+use bdk::bitcoin::{SchnorrSig, SchnorrSighashType};
+
+/// Upper bound on a DER-encoded ECDSA signature plus its sighash byte
+const MAX_SIGNATURE_LEN: usize = 73;
+
+/// Largest secret that fits in a single standard witness push
+pub const MAX_SECRET_LEN: usize = 520;
+
+/// Secrets shorter than this are easy to brute force from the hash lock
+pub const MIN_RECOMMENDED_SECRET_LEN: usize = 16;
+
+/// Length of a BIP340 signature with the default sighash type
+pub(crate) const SCHNORR_SIGNATURE_LEN: usize = 64;
+
+/// BIP341's provably unspendable point `H`, the taproot internal key that disables the key path
+#[cfg(test)]
+const UNSPENDABLE_INTERNAL_KEY: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+
+/// Hash function the contract's hash lock commits to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum HashType {
+    /// 32-byte SHA256 of the preimage
+    #[default]
+    Sha256,
+    /// 20-byte RIPEMD160(SHA256) of the preimage
+    Hash160,
+}
+
+impl HashType {
+    /// Hash lock committing to `preimage`
+    pub fn hash(&self, preimage: &[u8]) -> Vec<u8> {
+        match self {
+            HashType::Sha256 => sha256::Hash::hash(preimage).to_vec(),
+            HashType::Hash160 => hash160::Hash::hash(preimage).to_vec(),
+        }
+    }
+
+    /// Opcode that hashes the preimage on the stack
+    fn opcode(&self) -> Opcode {
+        match self {
+            HashType::Sha256 => OP_SHA256,
+            HashType::Hash160 => OP_HASH160,
+        }
+    }
+}
+
+/// When the sender may take the funds back through the timeout path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelockKind {
+    /// Absolute block height, enforced with OP_CHECKLOCKTIMEVERIFY
+    Absolute(u32),
+    /// Blocks after the funding output confirms, enforced with OP_CHECKSEQUENCEVERIFY
+    ///
+    /// BIP68 limits relative height locks to 65535 blocks.
+    Relative(u32),
+}
+
+impl TimelockKind {
+    /// Block count or height pushed into the script
+    pub fn value(&self) -> u32 {
+        match self {
+            TimelockKind::Absolute(height) | TimelockKind::Relative(height) => *height,
+        }
+    }
+
+    /// Check a refund can satisfy this timelock, see [`Self::refund_lock`]
+    pub fn validate(&self) -> Result<(), String> {
+        self.refund_lock().map(|_| ())
+    }
+
+    /// Lock time and input sequence a refund transaction needs to satisfy this timelock
+    fn refund_lock(&self) -> Result<(PackedLockTime, Sequence), String> {
+        match self {
+            // Lock time is only enforced when the input is not final
+            TimelockKind::Absolute(height) => {
+                Ok((PackedLockTime(*height), Sequence::ENABLE_LOCKTIME_NO_RBF))
+            }
+            TimelockKind::Relative(0) => {
+                Err("Relative timelock must be at least 1 block".to_string())
+            }
+            TimelockKind::Relative(blocks) => {
+                let blocks = u16::try_from(*blocks).map_err(|_| {
+                    format!(
+                        "Relative timelock of {} blocks exceeds the BIP68 maximum of {}",
+                        blocks,
+                        u16::MAX
+                    )
+                })?;
+                Ok((PackedLockTime(0), Sequence::from_height(blocks)))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for TimelockKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimelockKind::Absolute(height) => write!(f, "block {}", height),
+            TimelockKind::Relative(blocks) => write!(f, "{} blocks after funding", blocks),
+        }
+    }
+}
+
+/// Fee and signature settings for a claim or refund transaction
+#[derive(Debug, Clone, Copy)]
+pub struct SpendOptions {
+    /// Fee rate the fee is computed from
+    pub fee_rate: FeeRate,
+    /// Which parts of the transaction the signature commits to
+    ///
+    /// `ALL` locks the whole transaction. The `ANYONECANPAY` variants only
+    /// commit to the contract input, so others can add inputs, e.g. to pay a
+    /// higher fee, without invalidating the signature.
+    pub sighash_type: EcdsaSighashType,
+}
+
+impl SpendOptions {
+    /// Sign with `SIGHASH_ALL` at `fee_rate`
+    pub fn new(fee_rate: FeeRate) -> Self {
+        Self {
+            fee_rate,
+            sighash_type: EcdsaSighashType::All,
+        }
+    }
+}
+
+/// A P2WSH HTLC between a sender and a recipient
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HTLCContract {
+    /// Hash function of the hash lock
+    pub hash_type: HashType,
+    /// Hash of the secret preimage (32 bytes for SHA256, 20 for HASH160)
+    pub hash_lock: Vec<u8>,
+    /// Key that can claim with the preimage
+    pub recipient_pubkey: PublicKey,
+    /// Key that can refund after the timeout
+    pub sender_pubkey: PublicKey,
+    /// Absolute or relative timelock after which the sender can refund
+    pub timelock: TimelockKind,
+}
+
+impl HTLCContract {
+    pub fn new(
+        hash_type: HashType,
+        hash_lock: Vec<u8>,
+        recipient_pubkey: PublicKey,
+        sender_pubkey: PublicKey,
+        timelock: TimelockKind,
+    ) -> Self {
+        Self {
+            hash_type,
+            hash_lock,
+            recipient_pubkey,
+            sender_pubkey,
+            timelock,
+        }
+    }
+
+    /// Build the witness script locking the contract funds
+    pub fn create_script(&self) -> Script {
+        Builder::new()
+            .push_opcode(OP_IF)
+            .push_opcode(self.hash_type.opcode())
+            .push_slice(&self.hash_lock)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_key(&self.recipient_pubkey)
+            .push_opcode(OP_CHECKSIG)
+            .push_opcode(OP_ELSE)
+            .push_int(self.timelock.value() as i64)
+            .push_opcode(match self.timelock {
+                TimelockKind::Absolute(_) => OP_CLTV,
+                TimelockKind::Relative(_) => OP_CSV,
+            })
+            .push_opcode(OP_DROP)
+            .push_key(&self.sender_pubkey)
+            .push_opcode(OP_CHECKSIG)
+            .push_opcode(OP_ENDIF)
+            .into_script()
+    }
+
+    /// Witness script revealed when spending the contract, see [`Self::create_script`]
+    pub fn witness_script(&self) -> Script {
+        self.create_script()
+    }
+
+    /// P2WSH address that funds the contract on `network`
+    pub fn address(&self, network: Network) -> Address {
+        Address::p2wsh(&self.witness_script(), network)
+    }
+
+    /// The same contract as a taproot output, one tapscript leaf per branch
+    #[cfg(test)]
+    pub fn to_taproot(&self) -> TaprootHtlc {
+        TaprootHtlc {
+            hash_type: self.hash_type,
+            hash_lock: self.hash_lock.clone(),
+            recipient_pubkey: self.recipient_pubkey.inner.x_only_public_key().0,
+            sender_pubkey: self.sender_pubkey.inner.x_only_public_key().0,
+            timelock: self.timelock,
+        }
+    }
+
+    /// Check that `transaction` funds this contract with exactly `amount`, returning the output index
+    ///
+    /// Lets a counterparty confirm the on-chain output locks to the agreed
+    /// script before acting on it, rather than trusting a transaction ID.
+    pub fn verify_funding(&self, transaction: &Transaction, amount: Amount) -> Result<u32, String> {
+        let contract_script = self.witness_script().to_v0_p2wsh();
+        let mut contract_outputs = transaction
+            .output
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.script_pubkey == contract_script)
+            .peekable();
+        if contract_outputs.peek().is_none() {
+            return Err(format!(
+                "transaction {} has no output paying the contract script",
+                transaction.txid()
+            ));
+        }
+
+        let mut paid_amounts = Vec::new();
+        for (vout, output) in contract_outputs {
+            if output.value == amount.to_sat() {
+                return Ok(vout as u32);
+            }
+            paid_amounts.push(format!("{} sats at vout {}", output.value, vout));
+        }
+        Err(format!(
+            "transaction {} pays the contract {} instead of the agreed {} sats",
+            transaction.txid(),
+            paid_amounts.join(", "),
+            amount.to_sat()
+        ))
+    }
+
+    /// Virtual size of a claim transaction revealing a `secret_len`-byte secret
+    ///
+    /// Assumes the largest possible signature, so the estimate is never below
+    /// the size of the signed transaction.
+    pub fn estimate_claim_vsize(&self, secret_len: usize, destination: &Address) -> usize {
+        let witness = self.claim_witness(vec![0; MAX_SIGNATURE_LEN], vec![0; secret_len]);
+        estimate_vsize(destination, witness)
+    }
+
+    /// Virtual size of a refund transaction, assuming the largest possible signature
+    pub fn estimate_refund_vsize(&self, destination: &Address) -> usize {
+        let witness = self.refund_witness(vec![0; MAX_SIGNATURE_LEN]);
+        estimate_vsize(destination, witness)
+    }
+
+    /// Build and sign a transaction spending the contract through the secret path
+    ///
+    /// `amount` is the value of the contract output being spent; the fee is
+    /// `fee_rate` times the estimated claim vsize and is deducted from it.
+    pub fn create_claim_transaction(
+        &self,
+        contract_outpoint: OutPoint,
+        amount: Amount,
+        destination: &Address,
+        secret: &[u8],
+        recipient_secret_key: &SecretKey,
+        fee_rate: FeeRate,
+    ) -> Result<Transaction, String> {
+        self.create_claim_transaction_with_options(
+            contract_outpoint,
+            amount,
+            destination,
+            secret,
+            recipient_secret_key,
+            SpendOptions::new(fee_rate),
+        )
+    }
+
+    /// [`Self::create_claim_transaction`] signing with `options.sighash_type`
+    pub fn create_claim_transaction_with_options(
+        &self,
+        contract_outpoint: OutPoint,
+        amount: Amount,
+        destination: &Address,
+        secret: &[u8],
+        recipient_secret_key: &SecretKey,
+        options: SpendOptions,
+    ) -> Result<Transaction, String> {
+        if self.hash_type.hash(secret) != self.hash_lock {
+            return Err("Secret does not match the contract hash lock".to_string());
+        }
+
+        let fee_sat = options.fee_rate.fee_vb(self.estimate_claim_vsize(secret.len(), destination));
+        let mut transaction = spending_transaction(
+            contract_outpoint,
+            amount,
+            destination,
+            fee_sat,
+            PackedLockTime(0),
+            Sequence::ENABLE_RBF_NO_LOCKTIME,
+        )?;
+
+        let signature = self.sign_input(&transaction, amount, recipient_secret_key, options.sighash_type)?;
+        transaction.input[0].witness = self.claim_witness(signature, secret.to_vec());
+
+        Ok(transaction)
+    }
+
+    /// Build and sign a transaction spending the contract through the timeout path
+    ///
+    /// For an absolute timelock the transaction's lock time is set to the contract
+    /// timeout, so it is only valid once the chain has reached that height; for a
+    /// relative one the input sequence carries the BIP68 block count instead. The
+    /// fee is `fee_rate` times the estimated refund vsize.
+    pub fn create_refund_transaction(
+        &self,
+        contract_outpoint: OutPoint,
+        amount: Amount,
+        destination: &Address,
+        sender_secret_key: &SecretKey,
+        fee_rate: FeeRate,
+    ) -> Result<Transaction, String> {
+        self.create_refund_transaction_with_options(
+            contract_outpoint,
+            amount,
+            destination,
+            sender_secret_key,
+            SpendOptions::new(fee_rate),
+        )
+    }
+
+    /// [`Self::create_refund_transaction`] signing with `options.sighash_type`
+    pub fn create_refund_transaction_with_options(
+        &self,
+        contract_outpoint: OutPoint,
+        amount: Amount,
+        destination: &Address,
+        sender_secret_key: &SecretKey,
+        options: SpendOptions,
+    ) -> Result<Transaction, String> {
+        let fee_sat = options.fee_rate.fee_vb(self.estimate_refund_vsize(destination));
+        let (lock_time, sequence) = self.timelock.refund_lock()?;
+        let mut transaction = spending_transaction(
+            contract_outpoint,
+            amount,
+            destination,
+            fee_sat,
+            lock_time,
+            sequence,
+        )?;
+
+        let signature = self.sign_input(&transaction, amount, sender_secret_key, options.sighash_type)?;
+        transaction.input[0].witness = self.refund_witness(signature);
+
+        Ok(transaction)
+    }
+
+    /// Recover the secret revealed by a claim transaction
+    ///
+    /// Looks for the input of `transaction` spending output `vout` of the contract
+    /// through the secret path and returns the preimage if it opens the hash lock.
+    #[cfg(test)]
+    pub fn extract_preimage(&self, transaction: &Transaction, vout: u32) -> Option<Vec<u8>> {
+        let witness_script = self.create_script().to_bytes();
+
+        transaction
+            .input
+            .iter()
+            .filter(|input| input.previous_output.vout == vout)
+            .find_map(|input| match input.witness.to_vec().as_slice() {
+                [_signature, preimage, branch, script]
+                    if branch == &[1] && script == &witness_script =>
+                {
+                    Some(preimage.clone())
+                }
+                _ => None,
+            })
+            .filter(|preimage| self.hash_type.hash(preimage) == self.hash_lock)
+    }
+
+    /// Witness stack spending the secret path
+    fn claim_witness(&self, signature: Vec<u8>, secret: Vec<u8>) -> Witness {
+        Witness::from_vec(vec![
+            signature,
+            secret,
+            vec![1], // selects the OP_IF branch
+            self.create_script().to_bytes(),
+        ])
+    }
+
+    /// Witness stack spending the timeout path
+    fn refund_witness(&self, signature: Vec<u8>) -> Witness {
+        Witness::from_vec(vec![
+            signature,
+            vec![], // selects the OP_ELSE branch
+            self.create_script().to_bytes(),
+        ])
+    }
+
+    /// Serialized ECDSA signature over input 0, with `sighash_type` appended
+    fn sign_input(
+        &self,
+        transaction: &Transaction,
+        amount: Amount,
+        secret_key: &SecretKey,
+        sighash_type: EcdsaSighashType,
+    ) -> Result<Vec<u8>, String> {
+        let sighash = SighashCache::new(transaction)
+            .segwit_signature_hash(0, &self.create_script(), amount.to_sat(), sighash_type)
+            .map_err(|e| format!("Failed to compute sighash: {}", e))?;
+        let message = Message::from_slice(&sighash[..])
+            .map_err(|e| format!("Invalid sighash message: {}", e))?;
+
+        let signature = Secp256k1::new().sign_ecdsa(&message, secret_key);
+        Ok(EcdsaSig {
+            sig: signature,
+            hash_ty: sighash_type,
+        }
+        .to_vec())
+    }
+}
+
+/// Unsigned single-input, single-output transaction sweeping the contract
+fn spending_transaction(
+    contract_outpoint: OutPoint,
+    amount: Amount,
+    destination: &Address,
+    fee_sat: u64,
+    lock_time: PackedLockTime,
+    sequence: Sequence,
+) -> Result<Transaction, String> {
+    let output_value = amount.to_sat().checked_sub(fee_sat).ok_or_else(|| {
+        format!(
+            "Invalid amount: contract amount {} sats does not cover the {} sat fee",
+            amount.to_sat(),
+            fee_sat
+        )
+    })?;
+    let script_pubkey = destination.script_pubkey();
+    check_dust(output_value, &script_pubkey)?;
+
+    Ok(Transaction {
+        version: 2,
+        lock_time,
+        input: vec![TxIn {
+            previous_output: contract_outpoint,
+            script_sig: Script::new(),
+            sequence,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: output_value,
+            script_pubkey,
+        }],
+    })
+}
+
+/// An [`HTLCContract`] encoded as a SegWit v1 (taproot) output
+///
+/// The internal key is BIP341's unspendable point, so the only ways to spend
+/// are the two leaves, with the P2WSH contract's semantics:
+///
+/// ```text
+/// claim:  <OP_SHA256|OP_HASH160> <hash_lock> OP_EQUALVERIFY <recipient_xonly> OP_CHECKSIG
+/// refund: <timeout> <OP_CHECKLOCKTIMEVERIFY|OP_CHECKSEQUENCEVERIFY> OP_DROP <sender_xonly> OP_CHECKSIG
+/// ```
+///
+/// A spend reveals only its own leaf and signs with a 64-byte Schnorr
+/// signature, so both paths are cheaper than their P2WSH equivalents.
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaprootHtlc {
+    /// Hash function of the hash lock
+    pub hash_type: HashType,
+    /// Hash of the secret preimage (32 bytes for SHA256, 20 for HASH160)
+    pub hash_lock: Vec<u8>,
+    /// Key that can claim with the preimage
+    pub recipient_pubkey: XOnlyPublicKey,
+    /// Key that can refund after the timeout
+    pub sender_pubkey: XOnlyPublicKey,
+    /// Absolute or relative timelock after which the sender can refund
+    pub timelock: TimelockKind,
+}
+
+#[cfg(test)]
+impl TaprootHtlc {
+    /// Leaf script spent by the recipient with the preimage
+    pub fn claim_script(&self) -> Script {
+        Builder::new()
+            .push_opcode(self.hash_type.opcode())
+            .push_slice(&self.hash_lock)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_x_only_key(&self.recipient_pubkey)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    /// Leaf script spent by the sender once the timelock has passed
+    pub fn refund_script(&self) -> Script {
+        Builder::new()
+            .push_int(self.timelock.value() as i64)
+            .push_opcode(match self.timelock {
+                TimelockKind::Absolute(_) => OP_CLTV,
+                TimelockKind::Relative(_) => OP_CSV,
+            })
+            .push_opcode(OP_DROP)
+            .push_x_only_key(&self.sender_pubkey)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    /// Script tree of both leaves, committed to by the output key
+    pub fn spend_info(&self) -> TaprootSpendInfo {
+        let internal_key = XOnlyPublicKey::from_slice(&UNSPENDABLE_INTERNAL_KEY)
+            .expect("BIP341's H is a valid x-only key");
+        TaprootBuilder::new()
+            .add_leaf(1, self.claim_script())
+            .and_then(|builder| builder.add_leaf(1, self.refund_script()))
+            .expect("two leaves at depth 1 form a complete tree")
+            .finalize(&Secp256k1::verification_only(), internal_key)
+            .expect("a complete tree always finalizes")
+    }
+
+    /// P2TR address that funds the contract on `network`
+    pub fn address(&self, network: Network) -> Address {
+        Address::p2tr_tweaked(self.spend_info().output_key(), network)
+    }
+
+    /// Virtual size of a claim transaction revealing a `secret_len`-byte secret
+    pub fn estimate_claim_vsize(&self, secret_len: usize, destination: &Address) -> usize {
+        let witness = self.leaf_witness(
+            vec![vec![0; SCHNORR_SIGNATURE_LEN], vec![0; secret_len]],
+            self.claim_script(),
+        );
+        estimate_vsize(destination, witness)
+    }
+
+    /// Virtual size of a refund transaction
+    pub fn estimate_refund_vsize(&self, destination: &Address) -> usize {
+        let witness = self.leaf_witness(vec![vec![0; SCHNORR_SIGNATURE_LEN]], self.refund_script());
+        estimate_vsize(destination, witness)
+    }
+
+    /// Build and sign a transaction spending the contract through the claim leaf
+    ///
+    /// As [`HTLCContract::create_claim_transaction`]: `amount` is the value of
+    /// the contract output and the fee is deducted from it.
+    pub fn create_claim_transaction(
+        &self,
+        contract_outpoint: OutPoint,
+        amount: Amount,
+        destination: &Address,
+        secret: &[u8],
+        recipient_secret_key: &SecretKey,
+        fee_rate: FeeRate,
+    ) -> Result<Transaction, String> {
+        if self.hash_type.hash(secret) != self.hash_lock {
+            return Err("Secret does not match the contract hash lock".to_string());
+        }
+
+        let fee_sat = fee_rate.fee_vb(self.estimate_claim_vsize(secret.len(), destination));
+        let mut transaction = spending_transaction(
+            contract_outpoint,
+            amount,
+            destination,
+            fee_sat,
+            PackedLockTime(0),
+            Sequence::ENABLE_RBF_NO_LOCKTIME,
+        )?;
+
+        let claim_script = self.claim_script();
+        let signature = self.sign_leaf(&transaction, amount, &claim_script, recipient_secret_key)?;
+        transaction.input[0].witness = self.leaf_witness(vec![signature, secret.to_vec()], claim_script);
+
+        Ok(transaction)
+    }
+
+    /// Build and sign a transaction spending the contract through the refund leaf
+    ///
+    /// Sets the lock time or input sequence the timelock needs, as
+    /// [`HTLCContract::create_refund_transaction`] does.
+    pub fn create_refund_transaction(
+        &self,
+        contract_outpoint: OutPoint,
+        amount: Amount,
+        destination: &Address,
+        sender_secret_key: &SecretKey,
+        fee_rate: FeeRate,
+    ) -> Result<Transaction, String> {
+        let fee_sat = fee_rate.fee_vb(self.estimate_refund_vsize(destination));
+        let (lock_time, sequence) = self.timelock.refund_lock()?;
+        let mut transaction =
+            spending_transaction(contract_outpoint, amount, destination, fee_sat, lock_time, sequence)?;
+
+        let refund_script = self.refund_script();
+        let signature = self.sign_leaf(&transaction, amount, &refund_script, sender_secret_key)?;
+        transaction.input[0].witness = self.leaf_witness(vec![signature], refund_script);
+
+        Ok(transaction)
+    }
+
+    /// Recover the secret revealed by a claim transaction
+    ///
+    /// Looks for the input of `transaction` spending output `vout` of the contract
+    /// through the claim leaf and returns the preimage if it opens the hash lock.
+    pub fn extract_preimage(&self, transaction: &Transaction, vout: u32) -> Option<Vec<u8>> {
+        let claim_script = self.claim_script().to_bytes();
+
+        transaction
+            .input
+            .iter()
+            .filter(|input| input.previous_output.vout == vout)
+            .find_map(|input| match input.witness.to_vec().as_slice() {
+                [_signature, preimage, script, _control_block] if script == &claim_script => {
+                    Some(preimage.clone())
+                }
+                _ => None,
+            })
+            .filter(|preimage| self.hash_type.hash(preimage) == self.hash_lock)
+    }
+
+    /// Witness stack spending `leaf_script`: its inputs, the script and its control block
+    fn leaf_witness(&self, mut stack: Vec<Vec<u8>>, leaf_script: Script) -> Witness {
+        let control_block = self
+            .spend_info()
+            .control_block(&(leaf_script.clone(), LeafVersion::TapScript))
+            .expect("both leaves are in the tree");
+        stack.push(leaf_script.to_bytes());
+        stack.push(control_block.serialize());
+        Witness::from_vec(stack)
+    }
+
+    /// BIP340 signature over input 0 spending `leaf_script`, with the default sighash
+    fn sign_leaf(
+        &self,
+        transaction: &Transaction,
+        amount: Amount,
+        leaf_script: &Script,
+        secret_key: &SecretKey,
+    ) -> Result<Vec<u8>, String> {
+        let contract_output = TxOut {
+            value: amount.to_sat(),
+            script_pubkey: self.address(Network::Bitcoin).script_pubkey(),
+        };
+        let sighash = SighashCache::new(transaction)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&[contract_output]),
+                TapLeafHash::from_script(leaf_script, LeafVersion::TapScript),
+                SchnorrSighashType::Default,
+            )
+            .map_err(|e| format!("Failed to compute sighash: {}", e))?;
+        let message = Message::from_slice(&sighash[..])
+            .map_err(|e| format!("Invalid sighash message: {}", e))?;
+
+        let secp = Secp256k1::new();
+        let keypair = KeyPair::from_secret_key(&secp, secret_key);
+        Ok(SchnorrSig {
+            sig: secp.sign_schnorr(&message, &keypair),
+            hash_ty: SchnorrSighashType::Default,
+        }
+        .to_vec())
+    }
+}
+
+/// Virtual size of a single-input transaction paying `destination` with `witness`
+fn estimate_vsize(destination: &Address, witness: Witness) -> usize {
+    Transaction {
+        version: 2,
+        lock_time: PackedLockTime(0),
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig: Script::new(),
+            sequence: Sequence::MAX,
+            witness,
+        }],
+        output: vec![TxOut {
+            value: 0,
+            script_pubkey: destination.script_pubkey(),
+        }],
+    }
+    .vsize()
+}
+
+/// Reject an output of `value` sats that relay policy treats as dust for `script`
+///
+/// The threshold depends on the script type, e.g. 546 sats for P2PKH and 294 for P2WPKH.
+pub fn check_dust(value: u64, script: &Script) -> Result<(), String> {
+    let dust_limit = script.dust_value().to_sat();
+    if value < dust_limit {
+        return Err(format!(
+            "Invalid amount: output of {} sats is below the {} sat dust limit",
+            value, dust_limit
+        ));
+    }
+    Ok(())
+}
+
+/// Check that `secret` can be revealed in a claim witness
+///
+/// Rejects an empty secret, which anyone could claim with, and secrets longer
+/// than [`MAX_SECRET_LEN`]. Short secrets are accepted with a warning.
+pub fn validate_secret(secret: &[u8]) -> Result<(), String> {
+    if secret.is_empty() {
+        return Err("Invalid secret: the preimage must not be empty".to_string());
+    }
+    if secret.len() > MAX_SECRET_LEN {
+        return Err(format!(
+            "Invalid secret: preimage is {} bytes, the maximum witness push is {} bytes",
+            secret.len(),
+            MAX_SECRET_LEN
+        ));
+    }
+    if secret.len() < MIN_RECOMMENDED_SECRET_LEN {
+        log::warn!(
+            "Secret is only {} bytes; use at least {} bytes so the hash lock cannot be brute forced",
+            secret.len(),
+            MIN_RECOMMENDED_SECRET_LEN
+        );
+    }
+    Ok(())
+}
+
+/// Create an HTLC locked to the SHA256 hash of `secret`, refundable from block `timeout`
+#[cfg(test)]
+pub fn create_htlc_contract(
+    secret: &[u8],
+    recipient_pubkey: PublicKey,
+    sender_pubkey: PublicKey,
+    timeout: u32,
+) -> Result<HTLCContract, String> {
+    create_htlc_contract_with_hash_type(
+        secret,
+        HashType::Sha256,
+        recipient_pubkey,
+        sender_pubkey,
+        TimelockKind::Absolute(timeout),
+    )
+}
+
+/// Generate a random `len`-byte secret for a new hash lock
+pub fn generate_secret<R: RngCore + CryptoRng>(len: usize, rng: &mut R) -> Result<Vec<u8>, String> {
+    let mut secret = vec![0u8; len];
+    rng.fill_bytes(&mut secret);
+    validate_secret(&secret)?;
+    Ok(secret)
+}
+
+/// Parse a `--secret` argument into the preimage bytes
+///
+/// `0x`-prefixed hex, as printed by `gen-secret`, is decoded; any other string
+/// is used as its UTF-8 bytes.
+pub fn parse_secret(secret: &str) -> Result<Vec<u8>, String> {
+    match secret.strip_prefix("0x") {
+        Some(secret_hex) => hex::decode(secret_hex)
+            .map_err(|e| format!("Invalid secret: {} is not valid hex: {}", secret, e)),
+        None => Ok(secret.as_bytes().to_vec()),
+    }
+}
+
+/// Create an HTLC locked to the `hash_type` hash of `secret`
+///
+/// Fails for a relative timelock no refund could satisfy, so funds are never
+/// locked in a contract only the recipient can spend.
+pub fn create_htlc_contract_with_hash_type(
+    secret: &[u8],
+    hash_type: HashType,
+    recipient_pubkey: PublicKey,
+    sender_pubkey: PublicKey,
+    timelock: TimelockKind,
+) -> Result<HTLCContract, String> {
+    validate_secret(secret)?;
+    timelock.validate()?;
+    Ok(HTLCContract::new(
+        hash_type,
+        hash_type.hash(secret),
+        recipient_pubkey,
+        sender_pubkey,
+        timelock,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk::bitcoin::hashes::hex::FromHex;
+    use bdk::bitcoin::secp256k1::ecdsa::Signature;
+
+    const SECRET: &[u8] = b"atomic-secret-2024";
+
+    fn test_fee_rate() -> FeeRate {
+        FeeRate::from_sat_per_vb(5.0)
+    }
+
+    fn keypair(byte: u8) -> (SecretKey, PublicKey) {
+        let secret_key = SecretKey::from_slice(&[byte; 32]).unwrap();
+        let public_key = PublicKey::new(secret_key.public_key(&Secp256k1::new()));
+        (secret_key, public_key)
+    }
+
+    fn test_contract() -> (HTLCContract, SecretKey, SecretKey) {
+        let (recipient_sk, recipient_pk) = keypair(1);
+        let (sender_sk, sender_pk) = keypair(2);
+        let contract = create_htlc_contract(SECRET, recipient_pk, sender_pk, 200).unwrap();
+        (contract, recipient_sk, sender_sk)
+    }
+
+    fn test_outpoint() -> OutPoint {
+        OutPoint {
+            txid: bdk::bitcoin::Txid::from_hex(
+                "e8b0e1a1f4c1b0d5a6f3e2c9d8b7a6f5e4d3c2b1a0f9e8d7c6b5a4f3e2d1c0b9",
+            )
+            .unwrap(),
+            vout: 1,
+        }
+    }
+
+    fn destination() -> Address {
+        let (_, public_key) = keypair(3);
+        Address::p2wpkh(&public_key, Network::Regtest).unwrap()
+    }
+
+    /// Check the first witness element is a valid signature by `public_key` over input 0
+    fn assert_signed_by(
+        contract: &HTLCContract,
+        transaction: &Transaction,
+        amount: Amount,
+        public_key: &PublicKey,
+    ) {
+        assert_signed_with_sighash(contract, transaction, amount, public_key, EcdsaSighashType::All);
+    }
+
+    /// [`assert_signed_by`] for a signature committing with `sighash_type`
+    fn assert_signed_with_sighash(
+        contract: &HTLCContract,
+        transaction: &Transaction,
+        amount: Amount,
+        public_key: &PublicKey,
+        sighash_type: EcdsaSighashType,
+    ) {
+        let witness = transaction.input[0].witness.to_vec();
+        let (signature, sighash_byte) = witness[0].split_at(witness[0].len() - 1);
+        assert_eq!(sighash_byte, [sighash_type.to_u32() as u8]);
+
+        let sighash = SighashCache::new(transaction)
+            .segwit_signature_hash(0, &contract.create_script(), amount.to_sat(), sighash_type)
+            .unwrap();
+        let message = Message::from_slice(&sighash[..]).unwrap();
+        Secp256k1::new()
+            .verify_ecdsa(&message, &Signature::from_der(signature).unwrap(), &public_key.inner)
+            .unwrap();
+    }
+
+    #[test]
+    fn claim_transaction_reveals_secret_and_is_signed_by_recipient() {
+        let (contract, recipient_sk, _) = test_contract();
+        let amount = Amount::from_sat(150_000_000);
+
+        let claim = contract
+            .create_claim_transaction(test_outpoint(), amount, &destination(), SECRET, &recipient_sk, test_fee_rate())
+            .unwrap();
+
+        let witness = claim.input[0].witness.to_vec();
+        assert_eq!(witness.len(), 4);
+        assert_eq!(witness[1], SECRET);
+        assert_eq!(witness[3], contract.create_script().to_bytes());
+        let expected_fee = test_fee_rate().fee_vb(contract.estimate_claim_vsize(SECRET.len(), &destination()));
+        assert_eq!(claim.output[0].value, amount.to_sat() - expected_fee);
+        assert_signed_by(&contract, &claim, amount, &contract.recipient_pubkey);
+    }
+
+    #[test]
+    fn single_anyonecanpay_claim_stays_valid_when_inputs_are_added() {
+        let (contract, recipient_sk, _) = test_contract();
+        let amount = Amount::from_sat(150_000_000);
+        let options = SpendOptions {
+            fee_rate: test_fee_rate(),
+            sighash_type: EcdsaSighashType::SinglePlusAnyoneCanPay,
+        };
+
+        let mut claim = contract
+            .create_claim_transaction_with_options(test_outpoint(), amount, &destination(), SECRET, &recipient_sk, options)
+            .unwrap();
+        assert_signed_with_sighash(
+            &contract,
+            &claim,
+            amount,
+            &contract.recipient_pubkey,
+            EcdsaSighashType::SinglePlusAnyoneCanPay,
+        );
+
+        // A third party attaches its own input and change output to bump the fee
+        claim.input.push(TxIn {
+            previous_output: OutPoint { vout: 7, ..test_outpoint() },
+            ..Default::default()
+        });
+        claim.output.push(TxOut {
+            value: 10_000,
+            script_pubkey: destination().script_pubkey(),
+        });
+        assert_signed_with_sighash(
+            &contract,
+            &claim,
+            amount,
+            &contract.recipient_pubkey,
+            EcdsaSighashType::SinglePlusAnyoneCanPay,
+        );
+    }
+
+    #[test]
+    fn funding_is_verified_against_script_and_amount() {
+        let (contract, _, _) = test_contract();
+        let amount = Amount::from_sat(150_000);
+        let output = |script_pubkey: Script, value: u64| TxOut { value, script_pubkey };
+        let funding = |output: Vec<TxOut>| Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![],
+            output,
+        };
+        let contract_script = contract.address(Network::Regtest).script_pubkey();
+
+        let valid = funding(vec![
+            output(destination().script_pubkey(), 150_000),
+            output(contract_script.clone(), 150_000),
+        ]);
+        assert_eq!(contract.verify_funding(&valid, amount), Ok(1));
+
+        let short = funding(vec![output(contract_script, 149_999)]);
+        let err = contract.verify_funding(&short, amount).unwrap_err();
+        assert!(err.contains("149999 sats at vout 0"), "{}", err);
+
+        let (_, other_recipient) = keypair(4);
+        let (_, sender_pk) = keypair(2);
+        let other = create_htlc_contract(SECRET, other_recipient, sender_pk, 200).unwrap();
+        let wrong_script = funding(vec![output(other.address(Network::Regtest).script_pubkey(), 150_000)]);
+        let err = contract.verify_funding(&wrong_script, amount).unwrap_err();
+        assert!(err.contains("no output paying the contract"), "{}", err);
+    }
+
+    #[test]
+    fn address_is_the_p2wsh_of_the_witness_script() {
+        let (contract, _, _) = test_contract();
+        let witness_program = sha256::Hash::hash(contract.witness_script().as_bytes());
+
+        let regtest = contract.address(Network::Regtest);
+        assert_eq!(regtest, Address::p2wsh(&contract.create_script(), Network::Regtest));
+        assert_eq!(regtest.script_pubkey().as_bytes()[2..], witness_program[..]);
+        assert!(regtest.to_string().starts_with("bcrt1q"));
+        assert!(contract.address(Network::Bitcoin).to_string().starts_with("bc1q"));
+    }
+
+    #[test]
+    fn sha256_hash_lock_matches_the_prover_commitment() {
+        // The swap secret and btc_hash_lock pinned in prover/lib's tests
+        let swap_secret =
+            Vec::<u8>::from_hex("242b7a112ced4f1e688d117f358e3534e92f9e5fc89a5d0b2f843afebb9742f6").unwrap();
+        let btc_hash_lock =
+            Vec::<u8>::from_hex("810c6ee49f812f99284b37fb0f367b37efa20fcdaf89da05689d3058dd289c17").unwrap();
+        let (_, recipient_pk) = keypair(1);
+        let (_, sender_pk) = keypair(2);
+
+        let contract = create_htlc_contract(&swap_secret, recipient_pk, sender_pk, 200).unwrap();
+        assert_eq!(contract.hash_lock, btc_hash_lock);
+    }
+
+    #[test]
+    fn hash160_contract_commits_to_20_bytes_and_claims_with_secret() {
+        let (_, recipient_pk) = keypair(1);
+        let (_, sender_pk) = keypair(2);
+        let (recipient_sk, _) = keypair(1);
+        let contract =
+            create_htlc_contract_with_hash_type(
+                SECRET,
+                HashType::Hash160,
+                recipient_pk,
+                sender_pk,
+                TimelockKind::Absolute(200),
+            )
+            .unwrap();
+
+        assert_eq!(contract.hash_lock, hash160::Hash::hash(SECRET).to_vec());
+        let script = contract.create_script().to_bytes();
+        assert_eq!(script[1], OP_HASH160.to_u8());
+        assert_eq!(script[2], 20); // push of the 20-byte commitment
+
+        let amount = Amount::from_sat(100_000);
+        let claim = contract
+            .create_claim_transaction(test_outpoint(), amount, &destination(), SECRET, &recipient_sk, test_fee_rate())
+            .unwrap();
+        assert_eq!(claim.input[0].witness.to_vec()[1], SECRET);
+        assert_signed_by(&contract, &claim, amount, &contract.recipient_pubkey);
+
+        let sha256_contract = create_htlc_contract(SECRET, recipient_pk, sender_pk, 200).unwrap();
+        assert_ne!(contract.create_script(), sha256_contract.create_script());
+    }
+
+    #[test]
+    fn preimage_is_recovered_from_claim_transaction() {
+        let (contract, recipient_sk, sender_sk) = test_contract();
+        let amount = Amount::from_sat(100_000);
+
+        let claim = contract
+            .create_claim_transaction(test_outpoint(), amount, &destination(), SECRET, &recipient_sk, test_fee_rate())
+            .unwrap();
+        assert_eq!(contract.extract_preimage(&claim, test_outpoint().vout), Some(SECRET.to_vec()));
+        assert_eq!(contract.extract_preimage(&claim, test_outpoint().vout + 1), None);
+
+        let refund = contract
+            .create_refund_transaction(test_outpoint(), amount, &destination(), &sender_sk, test_fee_rate())
+            .unwrap();
+        assert_eq!(contract.extract_preimage(&refund, test_outpoint().vout), None);
+    }
+
+    #[test]
+    fn claim_with_wrong_secret_is_rejected() {
+        let (contract, recipient_sk, _) = test_contract();
+
+        let result = contract.create_claim_transaction(
+            test_outpoint(),
+            Amount::from_sat(100_000),
+            &destination(),
+            b"wrong-secret",
+            &recipient_sk,
+            test_fee_rate(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn refund_transaction_is_locked_until_timeout() {
+        let (contract, _, sender_sk) = test_contract();
+        let amount = Amount::from_sat(100_000);
+
+        let refund = contract
+            .create_refund_transaction(test_outpoint(), amount, &destination(), &sender_sk, test_fee_rate())
+            .unwrap();
+
+        assert_eq!(refund.lock_time, PackedLockTime(200));
+        assert!(refund.input[0].sequence.enables_absolute_lock_time());
+        assert!(refund.input[0].witness.to_vec()[1].is_empty());
+        assert_signed_by(&contract, &refund, amount, &contract.sender_pubkey);
+    }
+
+    #[test]
+    fn fee_leaving_less_than_dust_is_rejected() {
+        let (contract, recipient_sk, sender_sk) = test_contract();
+        let amount = Amount::from_sat(2000);
+        let (_, public_key) = keypair(3);
+        let p2pkh_destination = Address::p2pkh(&public_key, Network::Regtest);
+
+        // Roughly a 1500 sat fee, leaving less than the 546 sat P2PKH dust limit
+        let claim_vsize = contract.estimate_claim_vsize(SECRET.len(), &p2pkh_destination);
+        let claim = contract.create_claim_transaction(
+            test_outpoint(),
+            amount,
+            &p2pkh_destination,
+            SECRET,
+            &recipient_sk,
+            FeeRate::from_sat_per_vb(1500.0 / claim_vsize as f32),
+        );
+        assert!(claim.unwrap_err().contains("dust limit"));
+
+        let refund = contract.create_refund_transaction(
+            test_outpoint(),
+            amount,
+            &destination(),
+            &sender_sk,
+            FeeRate::from_sat_per_vb(100.0),
+        );
+        assert!(refund.unwrap_err().contains("Invalid amount"));
+    }
+
+    #[test]
+    fn vsize_estimates_cover_signed_transactions() {
+        let (contract, recipient_sk, sender_sk) = test_contract();
+        let amount = Amount::from_sat(100_000);
+
+        let claim_estimate = contract.estimate_claim_vsize(SECRET.len(), &destination());
+        let refund_estimate = contract.estimate_refund_vsize(&destination());
+        assert_eq!(claim_estimate, 135);
+        assert_eq!(refund_estimate, 130);
+
+        // Signatures are 71-73 bytes, so the signed size is at most a vbyte below the estimate
+        let claim = contract
+            .create_claim_transaction(test_outpoint(), amount, &destination(), SECRET, &recipient_sk, test_fee_rate())
+            .unwrap();
+        let refund = contract
+            .create_refund_transaction(test_outpoint(), amount, &destination(), &sender_sk, test_fee_rate())
+            .unwrap();
+        assert!(claim_estimate - claim.vsize() <= 1);
+        assert!(refund_estimate - refund.vsize() <= 1);
+    }
+
+    #[test]
+    fn dust_limit_depends_on_script_type() {
+        let (_, public_key) = keypair(3);
+        let p2pkh = Address::p2pkh(&public_key, Network::Regtest).script_pubkey();
+        let p2wpkh = destination().script_pubkey();
+
+        assert!(check_dust(545, &p2pkh).is_err());
+        assert!(check_dust(546, &p2pkh).is_ok());
+        assert!(check_dust(293, &p2wpkh).is_err());
+        assert!(check_dust(294, &p2wpkh).is_ok());
+    }
+
+    #[test]
+    fn relative_timelock_uses_csv_and_refund_sequence() {
+        let (recipient_sk, recipient_pk) = keypair(1);
+        let (sender_sk, sender_pk) = keypair(2);
+        let contract = create_htlc_contract_with_hash_type(
+            SECRET,
+            HashType::Sha256,
+            recipient_pk,
+            sender_pk,
+            TimelockKind::Relative(144),
+        )
+        .unwrap();
+        let script = contract.create_script().to_bytes();
+        assert!(script.contains(&OP_CSV.to_u8()));
+        assert!(!script.contains(&OP_CLTV.to_u8()));
+
+        let amount = Amount::from_sat(100_000);
+        let refund = contract
+            .create_refund_transaction(test_outpoint(), amount, &destination(), &sender_sk, test_fee_rate())
+            .unwrap();
+        assert_eq!(refund.lock_time, PackedLockTime(0));
+        assert_eq!(refund.input[0].sequence, Sequence::from_height(144));
+        assert!(refund.input[0].sequence.is_relative_lock_time());
+        assert_signed_by(&contract, &refund, amount, &contract.sender_pubkey);
+
+        // The claim path does not depend on the timelock kind
+        let claim = contract
+            .create_claim_transaction(test_outpoint(), amount, &destination(), SECRET, &recipient_sk, test_fee_rate())
+            .unwrap();
+        assert_eq!(claim.input[0].sequence, Sequence::ENABLE_RBF_NO_LOCKTIME);
+
+        let create = |timelock| {
+            create_htlc_contract_with_hash_type(SECRET, HashType::Sha256, recipient_pk, sender_pk, timelock)
+        };
+        assert!(create(TimelockKind::Relative(u16::MAX as u32)).is_ok());
+        let err = create(TimelockKind::Relative(70_000)).unwrap_err();
+        assert!(err.contains("BIP68 maximum of 65535"), "{}", err);
+        assert!(create(TimelockKind::Relative(0)).is_err());
+
+        // Contracts built around the check still refuse to refund, in either output type
+        let too_long = HTLCContract {
+            timelock: TimelockKind::Relative(70_000),
+            ..contract
+        };
+        assert!(too_long
+            .create_refund_transaction(test_outpoint(), amount, &destination(), &sender_sk, test_fee_rate())
+            .is_err());
+        assert!(too_long
+            .to_taproot()
+            .create_refund_transaction(test_outpoint(), amount, &destination(), &sender_sk, test_fee_rate())
+            .is_err());
+    }
+
+    #[test]
+    fn secret_length_is_validated() {
+        let (_, recipient_pk) = keypair(1);
+        let (_, sender_pk) = keypair(2);
+        assert!(create_htlc_contract(b"", recipient_pk, sender_pk, 200).is_err());
+        assert!(create_htlc_contract(&[7u8; MAX_SECRET_LEN + 1], recipient_pk, sender_pk, 200).is_err());
+        assert!(create_htlc_contract(&[7u8; MAX_SECRET_LEN], recipient_pk, sender_pk, 200).is_ok());
+    }
+
+    #[test]
+    fn generated_secrets_round_trip_through_parse_secret() {
+        let mut rng = rand::thread_rng();
+        let secret = generate_secret(32, &mut rng).unwrap();
+        assert_eq!(secret.len(), 32);
+        assert_ne!(secret, generate_secret(32, &mut rng).unwrap());
+        assert!(generate_secret(0, &mut rng).is_err());
+        assert!(generate_secret(MAX_SECRET_LEN + 1, &mut rng).is_err());
+
+        let argument = format!("0x{}", hex::encode(&secret));
+        assert_eq!(parse_secret(&argument).unwrap(), secret);
+        assert_eq!(parse_secret("atomic-secret-2024").unwrap(), b"atomic-secret-2024");
+        assert!(parse_secret("0xnot-hex").is_err());
+    }
+
+    #[test]
+    fn taproot_contract_claims_and_refunds_through_its_leaves() {
+        let (contract, recipient_sk, sender_sk) = test_contract();
+        let taproot = contract.to_taproot();
+        let amount = Amount::from_sat(100_000);
+        let secp = Secp256k1::new();
+
+        let address = taproot.address(Network::Regtest);
+        assert_eq!(address.address_type(), Some(bdk::bitcoin::AddressType::P2tr));
+        assert_ne!(address, contract.address(Network::Regtest));
+
+        let claim = taproot
+            .create_claim_transaction(test_outpoint(), amount, &destination(), SECRET, &recipient_sk, test_fee_rate())
+            .unwrap();
+        assert_eq!(taproot.extract_preimage(&claim, test_outpoint().vout), Some(SECRET.to_vec()));
+        assert_eq!(taproot.extract_preimage(&claim, test_outpoint().vout + 1), None);
+        // The P2WSH contract does not mistake a taproot witness for its own
+        assert_eq!(contract.extract_preimage(&claim, test_outpoint().vout), None);
+        let wrong_secret = taproot.create_claim_transaction(
+            test_outpoint(),
+            amount,
+            &destination(),
+            b"wrong-secret",
+            &recipient_sk,
+            test_fee_rate(),
+        );
+        assert!(wrong_secret.is_err());
+
+        // The control block commits the leaf to the output key and the signature is the recipient's
+        let witness = claim.input[0].witness.to_vec();
+        let control_block = bdk::bitcoin::util::taproot::ControlBlock::from_slice(&witness[3]).unwrap();
+        let output_key = taproot.spend_info().output_key().to_inner();
+        assert!(control_block.verify_taproot_commitment(&secp, output_key, &taproot.claim_script()));
+        let contract_output = TxOut { value: amount.to_sat(), script_pubkey: address.script_pubkey() };
+        let sighash = SighashCache::new(&claim)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&[contract_output]),
+                TapLeafHash::from_script(&taproot.claim_script(), LeafVersion::TapScript),
+                SchnorrSighashType::Default,
+            )
+            .unwrap();
+        let signature = SchnorrSig::from_slice(&witness[0]).unwrap();
+        secp.verify_schnorr(&signature.sig, &Message::from_slice(&sighash[..]).unwrap(), &taproot.recipient_pubkey)
+            .unwrap();
+        assert!(claim.vsize() <= taproot.estimate_claim_vsize(SECRET.len(), &destination()));
+
+        let refund = taproot
+            .create_refund_transaction(test_outpoint(), amount, &destination(), &sender_sk, test_fee_rate())
+            .unwrap();
+        assert_eq!(refund.lock_time, PackedLockTime(200));
+        assert_eq!(taproot.extract_preimage(&refund, test_outpoint().vout), None);
+        let witness = refund.input[0].witness.to_vec();
+        assert_eq!(witness[1], taproot.refund_script().to_bytes());
+        let control_block = bdk::bitcoin::util::taproot::ControlBlock::from_slice(&witness[2]).unwrap();
+        assert!(control_block.verify_taproot_commitment(&secp, output_key, &taproot.refund_script()));
+        assert!(refund.vsize() <= taproot.estimate_refund_vsize(&destination()));
+    }
+}
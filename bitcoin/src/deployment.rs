@@ -0,0 +1,116 @@
+//! Funding and locating P2WSH HTLC contracts on chain
+
+use bdk::bitcoin::{Address, Amount, OutPoint, Transaction, Txid};
+use bdk::blockchain::GetTx;
+use bdk::database::BatchDatabase;
+use bdk::{FeeRate, Wallet};
+use eyre::{Result, WrapErr, eyre};
+
+use crate::blockchain::Backend;
+use crate::contract::HTLCContract;
+use crate::error::FundingError;
+use crate::transaction::TransactionUtils;
+
+/// A funded HTLC contract
+#[derive(Debug, Clone)]
+pub struct HtlcDeployment {
+    /// P2WSH address the contract was funded at
+    pub address: Address,
+    /// Funding transaction ID, used as the contract ID
+    pub txid: Txid,
+    /// Index of the contract output in the funding transaction
+    pub vout: u32,
+    /// Fee paid by the funding transaction in satoshis
+    pub fee_sat: u64,
+}
+
+impl HtlcDeployment {
+    /// Outpoint of the contract output, spent by claim and refund
+    pub fn outpoint(&self) -> OutPoint {
+        OutPoint::new(self.txid, self.vout)
+    }
+}
+
+/// Fund an HTLC contract from the sender's wallet
+pub async fn deploy_htlc<D: BatchDatabase>(
+    blockchain_client: &Backend,
+    sender_wallet: &Wallet<D>,
+    contract: &HTLCContract,
+    amount: Amount,
+    fee_rate: FeeRate,
+) -> Result<HtlcDeployment> {
+    let contract_address = contract.address(sender_wallet.network());
+
+    let funding = TransactionUtils::create_and_broadcast_with_fee_rate(
+        blockchain_client,
+        sender_wallet,
+        contract_address.clone(),
+        amount.to_sat(),
+        fee_rate,
+    )
+    .await
+    .wrap_err_with(|| {
+        format!(
+            "Failed to send {} satoshis to HTLC contract address {}",
+            amount.to_sat(),
+            contract_address
+        )
+    })?;
+
+    let vout = find_contract_vout(&funding.transaction, &contract_address)?;
+
+    Ok(HtlcDeployment {
+        address: contract_address,
+        txid: funding.txid,
+        vout,
+        fee_sat: funding.fee_sat,
+    })
+}
+
+/// Locate the contract output of a funding transaction fetched from the node
+pub fn find_htlc_outpoint(
+    blockchain_client: &Backend,
+    funding_txid: &Txid,
+    contract_address: &Address,
+) -> Result<OutPoint> {
+    let funding_transaction = blockchain_client
+        .get_tx(funding_txid)?
+        .ok_or_else(|| eyre!("Funding transaction {} not found", funding_txid))?;
+    let vout = find_contract_vout(&funding_transaction, contract_address)?;
+    Ok(OutPoint::new(*funding_txid, vout))
+}
+
+/// Fetch a funding transaction and check it pays `contract` exactly `amount`
+///
+/// Used before claiming, so the recipient relies on what is on chain rather
+/// than on the contract ID the sender handed over.
+pub fn verify_htlc_funding(
+    blockchain_client: &Backend,
+    funding_txid: &Txid,
+    contract: &HTLCContract,
+    amount: Amount,
+) -> Result<OutPoint> {
+    let funding_transaction = blockchain_client
+        .get_tx(funding_txid)?
+        .ok_or_else(|| FundingError::NeverFunded(format!("transaction {} not found", funding_txid)))?;
+    let vout = contract
+        .verify_funding(&funding_transaction, amount)
+        .map_err(FundingError::NeverFunded)?;
+    Ok(OutPoint::new(*funding_txid, vout))
+}
+
+fn find_contract_vout(transaction: &Transaction, contract_address: &Address) -> Result<u32> {
+    let contract_script = contract_address.script_pubkey();
+    transaction
+        .output
+        .iter()
+        .position(|output| output.script_pubkey == contract_script)
+        .map(|index| index as u32)
+        .ok_or_else(|| {
+            eyre!(
+                "Transaction {} has no output paying {}",
+                transaction.txid(),
+                contract_address
+            )
+        })
+}
@@ -1,4 +1,4 @@
-#![allow(dead_code)]
+use bdk::bitcoin::secp256k1;
 use std::fmt;
 
 /// Custom error types for the Bitcoin wallet CLI
@@ -12,10 +12,16 @@ pub enum BitcoinWalletError {
     Transaction(TransactionError),
     /// Blockchain/RPC related errors
     Blockchain(BlockchainError),
+    /// Contract funding state errors
+    Funding(FundingError),
+    /// Errors from the underlying BDK wallet
+    Bdk(bdk::Error),
+    /// Invalid keys or signatures
+    Secp256k1(secp256k1::Error),
+    /// Wallet or RPC config files that are not valid TOML
+    Toml(toml::de::Error),
     /// IO related errors
     Io(std::io::Error),
-    /// Generic error with message
-    Generic(String),
 }
 
 #[derive(Debug)]
@@ -28,10 +34,6 @@ pub enum ConfigFileError {
 
 #[derive(Debug)]
 pub enum WalletError {
-    CreationFailed(String),
-    SyncFailed(String),
-    BalanceRetrievalFailed(String),
-    AddresGenerationFailed(String),
     KeyDerivationFailed(String),
 }
 
@@ -52,6 +54,77 @@ pub enum BlockchainError {
     NetworkError(String),
 }
 
+/// Why a contract cannot be funded or has no output that can be spent yet
+#[derive(Debug)]
+pub enum FundingError {
+    /// A contract with the same parameters was already funded
+    AlreadyFunded(String),
+    /// No transaction paying the contract was found, confirmed or not
+    NeverFunded(String),
+    /// The contract is funded but its outputs are still unconfirmed
+    NotYetConfirmed(String),
+    /// The contract outputs are confirmed but their refund timelock has not run out
+    TimelockNotMatured(String),
+}
+
+/// Process exit codes for each error category, so scripts can tell failures apart
+pub const EXIT_GENERIC: i32 = 1;
+pub const EXIT_CONFIG: i32 = 2;
+pub const EXIT_WALLET: i32 = 3;
+pub const EXIT_TRANSACTION: i32 = 4;
+pub const EXIT_BLOCKCHAIN: i32 = 5;
+pub const EXIT_FUNDING: i32 = 6;
+
+impl BitcoinWalletError {
+    /// Process exit code for this error's category
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            BitcoinWalletError::ConfigFile(_) | BitcoinWalletError::Toml(_) => EXIT_CONFIG,
+            BitcoinWalletError::Wallet(_) | BitcoinWalletError::Secp256k1(_) => EXIT_WALLET,
+            BitcoinWalletError::Bdk(err) => bdk_exit_code(err),
+            BitcoinWalletError::Transaction(_) => EXIT_TRANSACTION,
+            BitcoinWalletError::Blockchain(_) => EXIT_BLOCKCHAIN,
+            BitcoinWalletError::Funding(_) => EXIT_FUNDING,
+            BitcoinWalletError::Io(_) => EXIT_GENERIC,
+        }
+    }
+}
+
+fn bdk_exit_code(err: &bdk::Error) -> i32 {
+    match err {
+        bdk::Error::InsufficientFunds { .. } => EXIT_TRANSACTION,
+        bdk::Error::Rpc(_) | bdk::Error::Electrum(_) | bdk::Error::Esplora(_) => EXIT_BLOCKCHAIN,
+        _ => EXIT_WALLET,
+    }
+}
+
+/// Exit code for an error report, taken from the first categorised error in its chain
+///
+/// Reports that carry none of this module's errors, nor a bdk, secp256k1 or
+/// toml error, exit with [`EXIT_GENERIC`].
+pub fn exit_code(report: &eyre::Report) -> i32 {
+    report
+        .chain()
+        .find_map(|cause| {
+            if let Some(err) = cause.downcast_ref::<BitcoinWalletError>() {
+                Some(err.exit_code())
+            } else if cause.is::<ConfigFileError>() || cause.is::<toml::de::Error>() {
+                Some(EXIT_CONFIG)
+            } else if cause.is::<WalletError>() || cause.is::<secp256k1::Error>() {
+                Some(EXIT_WALLET)
+            } else if cause.is::<TransactionError>() {
+                Some(EXIT_TRANSACTION)
+            } else if cause.is::<BlockchainError>() {
+                Some(EXIT_BLOCKCHAIN)
+            } else if cause.is::<FundingError>() {
+                Some(EXIT_FUNDING)
+            } else {
+                cause.downcast_ref::<bdk::Error>().map(bdk_exit_code)
+            }
+        })
+        .unwrap_or(EXIT_GENERIC)
+}
+
 impl fmt::Display for BitcoinWalletError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -59,8 +132,11 @@ impl fmt::Display for BitcoinWalletError {
             BitcoinWalletError::Wallet(err) => write!(f, "Wallet error: {}", err),
             BitcoinWalletError::Transaction(err) => write!(f, "Transaction error: {}", err),
             BitcoinWalletError::Blockchain(err) => write!(f, "Blockchain error: {}", err),
+            BitcoinWalletError::Funding(err) => write!(f, "Funding error: {}", err),
+            BitcoinWalletError::Bdk(err) => write!(f, "BDK error: {}", err),
+            BitcoinWalletError::Secp256k1(err) => write!(f, "Key error: {}", err),
+            BitcoinWalletError::Toml(err) => write!(f, "Invalid TOML: {}", err),
             BitcoinWalletError::Io(err) => write!(f, "IO error: {}", err),
-            BitcoinWalletError::Generic(msg) => write!(f, "Error: {}", msg),
         }
     }
 }
@@ -79,10 +155,6 @@ impl fmt::Display for ConfigFileError {
 impl fmt::Display for WalletError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            WalletError::CreationFailed(msg) => write!(f, "Wallet creation failed: {}", msg),
-            WalletError::SyncFailed(msg) => write!(f, "Wallet sync failed: {}", msg),
-            WalletError::BalanceRetrievalFailed(msg) => write!(f, "Balance retrieval failed: {}", msg),
-            WalletError::AddresGenerationFailed(msg) => write!(f, "Address generation failed: {}", msg),
             WalletError::KeyDerivationFailed(msg) => write!(f, "Key derivation failed: {}", msg),
         }
     }
@@ -111,11 +183,37 @@ impl fmt::Display for BlockchainError {
     }
 }
 
-impl std::error::Error for BitcoinWalletError {}
+impl fmt::Display for FundingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FundingError::AlreadyFunded(msg) => write!(f, "Contract was already funded: {}", msg),
+            FundingError::NeverFunded(msg) => write!(f, "Contract was never funded: {}", msg),
+            FundingError::NotYetConfirmed(msg) => write!(f, "Contract funding not yet confirmed: {}", msg),
+            FundingError::TimelockNotMatured(msg) => write!(f, "Contract timelock has not matured: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for BitcoinWalletError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BitcoinWalletError::ConfigFile(err) => Some(err),
+            BitcoinWalletError::Wallet(err) => Some(err),
+            BitcoinWalletError::Transaction(err) => Some(err),
+            BitcoinWalletError::Blockchain(err) => Some(err),
+            BitcoinWalletError::Funding(err) => Some(err),
+            BitcoinWalletError::Bdk(err) => Some(err),
+            BitcoinWalletError::Secp256k1(err) => Some(err),
+            BitcoinWalletError::Toml(err) => Some(err),
+            BitcoinWalletError::Io(err) => Some(err),
+        }
+    }
+}
 impl std::error::Error for ConfigFileError {}
 impl std::error::Error for WalletError {}
 impl std::error::Error for TransactionError {}
 impl std::error::Error for BlockchainError {}
+impl std::error::Error for FundingError {}
 
 impl From<std::io::Error> for BitcoinWalletError {
     fn from(err: std::io::Error) -> Self {
@@ -123,6 +221,24 @@ impl From<std::io::Error> for BitcoinWalletError {
     }
 }
 
+impl From<bdk::Error> for BitcoinWalletError {
+    fn from(err: bdk::Error) -> Self {
+        BitcoinWalletError::Bdk(err)
+    }
+}
+
+impl From<secp256k1::Error> for BitcoinWalletError {
+    fn from(err: secp256k1::Error) -> Self {
+        BitcoinWalletError::Secp256k1(err)
+    }
+}
+
+impl From<toml::de::Error> for BitcoinWalletError {
+    fn from(err: toml::de::Error) -> Self {
+        BitcoinWalletError::Toml(err)
+    }
+}
+
 impl From<ConfigFileError> for BitcoinWalletError {
     fn from(err: ConfigFileError) -> Self {
         BitcoinWalletError::ConfigFile(err)
@@ -147,5 +263,50 @@ impl From<BlockchainError> for BitcoinWalletError {
     }
 }
 
+impl From<FundingError> for BitcoinWalletError {
+    fn from(err: FundingError) -> Self {
+        BitcoinWalletError::Funding(err)
+    }
+}
+
 /// Type alias for Results using our custom error type
-pub type Result<T> = std::result::Result<T, BitcoinWalletError>;
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, BitcoinWalletError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn underlying_errors_are_kept_as_source() {
+        let toml_err = toml::from_str::<toml::Table>("not = = toml").unwrap_err();
+        let err: BitcoinWalletError = toml_err.clone().into();
+        let source = err.source().unwrap();
+        assert_eq!(source.to_string(), toml_err.to_string());
+        assert!(source.downcast_ref::<toml::de::Error>().is_some());
+
+        let err: BitcoinWalletError = secp256k1::SecretKey::from_slice(&[0u8; 32]).unwrap_err().into();
+        assert!(err.source().unwrap().downcast_ref::<secp256k1::Error>().is_some());
+
+        let err: BitcoinWalletError = FundingError::NeverFunded("txid".to_string()).into();
+        assert!(err.source().unwrap().downcast_ref::<FundingError>().is_some());
+    }
+
+    #[test]
+    fn exit_codes_follow_the_error_category() {
+        let not_found = eyre::Report::new(ConfigFileError::NotFound("wallet.toml".to_string()));
+        assert_eq!(exit_code(&not_found), EXIT_CONFIG);
+
+        let insufficient = eyre::Report::new(bdk::Error::InsufficientFunds {
+            needed: 10,
+            available: 5,
+        })
+        .wrap_err("Failed to build transaction");
+        assert_eq!(exit_code(&insufficient), EXIT_TRANSACTION);
+
+        let wrapped: BitcoinWalletError = BlockchainError::RpcError("timeout".to_string()).into();
+        assert_eq!(exit_code(&eyre::Report::new(wrapped)), EXIT_BLOCKCHAIN);
+
+        assert_eq!(exit_code(&eyre::eyre!("something else")), EXIT_GENERIC);
+    }
+}
@@ -0,0 +1,208 @@
+//! Key aggregation for the 2-of-2 atomic swap escrow key
+//!
+//! Adding the two public keys directly lets whichever party reveals its key
+//! last pick `P_own - P_other` and control the sum alone (key cancellation).
+//! Each key is instead multiplied by a coefficient committing to both keys,
+//! as in MuSig: `a_i = H(H(P_swap || P_recipient) || P_i)` and
+//! `P_escrow = a_swap * P_swap + a_recipient * P_recipient`.
+//! Swaps funded before that still use plain addition, see [`KeyAggregation`].
+
+use bdk::bitcoin::hashes::{Hash, HashEngine, sha256};
+use bdk::bitcoin::secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey};
+use eyre::{Result, WrapErr, eyre};
+use serde::{Deserialize, Serialize};
+
+use crate::error::BitcoinWalletError;
+
+/// How a swap's escrow key was aggregated from the swap and recipient keys
+///
+/// Swap records saved before the scheme was recorded used plain addition, so
+/// that is what a missing field means.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyAggregation {
+    /// `P_swap + P_recipient`, open to key cancellation; only for existing swaps
+    #[default]
+    Legacy,
+    /// Coefficient-weighted sum, see [`aggregate_public_keys`]
+    Musig,
+}
+
+impl KeyAggregation {
+    /// The escrow public key of a swap aggregated with this scheme
+    pub fn public_key(self, swap_public_key: &PublicKey, recipient_public_key: &PublicKey) -> Result<PublicKey> {
+        match self {
+            KeyAggregation::Legacy => swap_public_key
+                .combine(recipient_public_key)
+                .map_err(BitcoinWalletError::from)
+                .wrap_err("Failed to combine escrow public keys"),
+            KeyAggregation::Musig => aggregate_public_keys(swap_public_key, recipient_public_key),
+        }
+    }
+
+    /// The escrow secret key matching [`Self::public_key`]
+    pub fn secret_key(self, swap_secret_key: &SecretKey, recipient_secret_key: &SecretKey) -> Result<SecretKey> {
+        match self {
+            KeyAggregation::Legacy => {
+                let recipient_scalar = Scalar::from_be_bytes(recipient_secret_key.secret_bytes())
+                    .map_err(|_| eyre!("Recipient secret key is out of range"))?;
+                swap_secret_key
+                    .add_tweak(&recipient_scalar)
+                    .map_err(BitcoinWalletError::from)
+                    .wrap_err("Failed to combine escrow secret keys")
+            }
+            KeyAggregation::Musig => aggregate_secret_keys(swap_secret_key, recipient_secret_key),
+        }
+    }
+}
+
+/// Derives the coefficients `(a_swap, a_recipient)` committing to both keys
+fn key_coefficients(
+    swap_public_key: &PublicKey,
+    recipient_public_key: &PublicKey,
+) -> Result<(Scalar, Scalar)> {
+    let mut key_list_engine = sha256::Hash::engine();
+    key_list_engine.input(&swap_public_key.serialize());
+    key_list_engine.input(&recipient_public_key.serialize());
+    let key_list_hash = sha256::Hash::from_engine(key_list_engine);
+
+    let coefficient = |public_key: &PublicKey| -> Result<Scalar> {
+        let mut engine = sha256::Hash::engine();
+        engine.input(&key_list_hash.into_inner());
+        engine.input(&public_key.serialize());
+        Scalar::from_be_bytes(sha256::Hash::from_engine(engine).into_inner())
+            .map_err(|_| eyre!("Escrow key coefficient is out of range"))
+    };
+
+    Ok((
+        coefficient(swap_public_key)?,
+        coefficient(recipient_public_key)?,
+    ))
+}
+
+/// Aggregates the swap and recipient public keys into the escrow public key
+pub fn aggregate_public_keys(
+    swap_public_key: &PublicKey,
+    recipient_public_key: &PublicKey,
+) -> Result<PublicKey> {
+    let secp_context = Secp256k1::new();
+    let (swap_coefficient, recipient_coefficient) =
+        key_coefficients(swap_public_key, recipient_public_key)?;
+
+    let weighted_swap_key = swap_public_key
+        .mul_tweak(&secp_context, &swap_coefficient)
+        .map_err(BitcoinWalletError::from)
+        .wrap_err("Failed to weight swap public key")?;
+    let weighted_recipient_key = recipient_public_key
+        .mul_tweak(&secp_context, &recipient_coefficient)
+        .map_err(BitcoinWalletError::from)
+        .wrap_err("Failed to weight recipient public key")?;
+
+    weighted_swap_key
+        .combine(&weighted_recipient_key)
+        .map_err(BitcoinWalletError::from)
+        .wrap_err("Failed to combine escrow public keys")
+}
+
+/// Aggregates the swap and recipient secret keys into the escrow secret key
+///
+/// The result is the private key for `aggregate_public_keys` of the matching public keys.
+pub fn aggregate_secret_keys(
+    swap_secret_key: &SecretKey,
+    recipient_secret_key: &SecretKey,
+) -> Result<SecretKey> {
+    let secp_context = Secp256k1::new();
+    let (swap_coefficient, recipient_coefficient) = key_coefficients(
+        &swap_secret_key.public_key(&secp_context),
+        &recipient_secret_key.public_key(&secp_context),
+    )?;
+
+    let weighted_swap_key = swap_secret_key
+        .mul_tweak(&swap_coefficient)
+        .map_err(BitcoinWalletError::from)
+        .wrap_err("Failed to weight swap secret key")?;
+    let weighted_recipient_key = recipient_secret_key
+        .mul_tweak(&recipient_coefficient)
+        .map_err(BitcoinWalletError::from)
+        .wrap_err("Failed to weight recipient secret key")?;
+
+    let recipient_scalar = Scalar::from_be_bytes(weighted_recipient_key.secret_bytes())
+        .map_err(|_| eyre!("Weighted recipient secret key is out of range"))?;
+    weighted_swap_key
+        .add_tweak(&recipient_scalar)
+        .map_err(BitcoinWalletError::from)
+        .wrap_err("Failed to combine escrow secret keys")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk::bitcoin::{Address, Network};
+
+    #[test]
+    fn aggregate_secret_key_controls_aggregate_address() {
+        let secp_context = Secp256k1::new();
+        let swap_secret_key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let recipient_secret_key = SecretKey::from_slice(&[0x22; 32]).unwrap();
+
+        let escrow_public_key = aggregate_public_keys(
+            &swap_secret_key.public_key(&secp_context),
+            &recipient_secret_key.public_key(&secp_context),
+        )
+        .unwrap();
+        let escrow_secret_key =
+            aggregate_secret_keys(&swap_secret_key, &recipient_secret_key).unwrap();
+        assert_eq!(escrow_secret_key.public_key(&secp_context), escrow_public_key);
+
+        let address_from_public_key = Address::p2tr(
+            &secp_context,
+            escrow_public_key.x_only_public_key().0,
+            None,
+            Network::Regtest,
+        );
+        let address_from_secret_key = Address::p2tr(
+            &secp_context,
+            escrow_secret_key.x_only_public_key(&secp_context).0,
+            None,
+            Network::Regtest,
+        );
+        assert_eq!(address_from_public_key, address_from_secret_key);
+    }
+
+    #[test]
+    fn aggregate_differs_from_plain_key_addition() {
+        let secp_context = Secp256k1::new();
+        let swap_public_key = SecretKey::from_slice(&[0x11; 32])
+            .unwrap()
+            .public_key(&secp_context);
+        let recipient_public_key = SecretKey::from_slice(&[0x22; 32])
+            .unwrap()
+            .public_key(&secp_context);
+
+        let escrow_public_key =
+            aggregate_public_keys(&swap_public_key, &recipient_public_key).unwrap();
+        assert_ne!(
+            escrow_public_key,
+            swap_public_key.combine(&recipient_public_key).unwrap()
+        );
+    }
+
+    #[test]
+    fn each_scheme_pairs_its_secret_and_public_keys() {
+        let secp_context = Secp256k1::new();
+        let swap_secret_key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let recipient_secret_key = SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let swap_public_key = swap_secret_key.public_key(&secp_context);
+        let recipient_public_key = recipient_secret_key.public_key(&secp_context);
+
+        for scheme in [KeyAggregation::Legacy, KeyAggregation::Musig] {
+            let escrow_public_key = scheme.public_key(&swap_public_key, &recipient_public_key).unwrap();
+            let escrow_secret_key = scheme.secret_key(&swap_secret_key, &recipient_secret_key).unwrap();
+            assert_eq!(escrow_secret_key.public_key(&secp_context), escrow_public_key, "{:?}", scheme);
+        }
+        assert_eq!(
+            KeyAggregation::Legacy.public_key(&swap_public_key, &recipient_public_key).unwrap(),
+            swap_public_key.combine(&recipient_public_key).unwrap()
+        );
+    }
+}
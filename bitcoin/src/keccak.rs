@@ -0,0 +1,103 @@
+//! Keccak-256 as used by Ethereum, for hash locks on the counterparty chain
+//!
+//! This is the original Keccak submission with `0x01` padding, not the
+//! standardised SHA3-256, which pads with `0x06` and gives different digests.
+
+/// Bytes absorbed per permutation: 1600 bits minus a 512-bit capacity
+const RATE: usize = 136;
+
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+    0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+    0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+
+/// Rotation of each lane in the combined rho and pi steps, in `PI_LANES` order
+const ROTATIONS: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+
+/// Lane visited at each step of the pi permutation, starting from lane 1
+const PI_LANES: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+/// Keccak-256 digest of `data`
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut state = [0u64; 25];
+
+    let mut padded = data.to_vec();
+    padded.push(0x01);
+    padded.resize(padded.len().div_ceil(RATE) * RATE, 0);
+    *padded.last_mut().expect("padding is never empty") |= 0x80;
+
+    for block in padded.chunks(RATE) {
+        for (lane, bytes) in state.iter_mut().zip(block.chunks(8)) {
+            *lane ^= u64::from_le_bytes(bytes.try_into().expect("rate is a whole number of lanes"));
+        }
+        keccak_f(&mut state);
+    }
+
+    let mut digest = [0u8; 32];
+    for (bytes, lane) in digest.chunks_mut(8).zip(state) {
+        bytes.copy_from_slice(&lane.to_le_bytes());
+    }
+    digest
+}
+
+/// The Keccak-f[1600] permutation
+fn keccak_f(state: &mut [u64; 25]) {
+    for round_constant in ROUND_CONSTANTS {
+        // Theta: mix each column's parity into its neighbours
+        let mut parity = [0u64; 5];
+        for (x, column) in parity.iter_mut().enumerate() {
+            *column = (0..25).step_by(5).fold(0, |acc, row| acc ^ state[row + x]);
+        }
+        for x in 0..5 {
+            let mix = parity[(x + 4) % 5] ^ parity[(x + 1) % 5].rotate_left(1);
+            for row in (0..25).step_by(5) {
+                state[row + x] ^= mix;
+            }
+        }
+
+        // Rho and pi: rotate each lane and move it to its new position
+        let mut carried = state[1];
+        for (&lane, &rotation) in PI_LANES.iter().zip(&ROTATIONS) {
+            let displaced = state[lane];
+            state[lane] = carried.rotate_left(rotation);
+            carried = displaced;
+        }
+
+        // Chi: the only non-linear step, applied row by row
+        for row in (0..25).step_by(5) {
+            let lanes: [u64; 5] = state[row..row + 5].try_into().expect("a row has five lanes");
+            for x in 0..5 {
+                state[row + x] = lanes[x] ^ (!lanes[(x + 1) % 5] & lanes[(x + 2) % 5]);
+            }
+        }
+
+        // Iota
+        state[0] ^= round_constant;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digests_match_known_vectors() {
+        let cases: [(&[u8], &str); 3] = [
+            (b"", "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"),
+            (b"abc", "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"),
+            // Exactly one block, so the padding fills a second one
+            (&[0x61; RATE], "a6c4d403279fe3e0af03729caada8374b5ca54d8065329a3ebcaeb4b60aa386e"),
+        ];
+        for (data, expected) in cases {
+            assert_eq!(hex::encode(keccak256(data)), expected, "{} bytes", data.len());
+        }
+    }
+}
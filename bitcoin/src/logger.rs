@@ -0,0 +1,46 @@
+//! Minimal stderr logger for diagnostic output
+//!
+//! Progress and debug details stay hidden during normal runs; pass
+//! `--log-level info` (or `debug`, `trace`, ...) or set `RUST_LOG` to surface
+//! them on stderr. Each line names the module it came from, so one swap can be
+//! followed across the wallet, contract and blockchain code.
+
+use log::{LevelFilter, Log, Metadata, Record};
+use std::str::FromStr;
+
+use crate::constants::LOG_LEVEL_ENV;
+
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// Installs the stderr logger
+///
+/// The level is `level` (from `--log-level`) if given, else `RUST_LOG`, else warn.
+pub fn init(level: Option<LevelFilter>) {
+    let level = level
+        .or_else(|| {
+            std::env::var(LOG_LEVEL_ENV)
+                .ok()
+                .and_then(|value| LevelFilter::from_str(&value).ok())
+        })
+        .unwrap_or(LevelFilter::Warn);
+
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
@@ -6,73 +6,373 @@
 mod args;
 mod blockchain;
 pub mod constants;
+mod contract;
+mod deployment;
 mod error;
+mod escrow;
+mod keccak;
+mod logger;
+mod message;
+mod output;
 mod primitives;
 mod taproot;
 mod transaction;
 mod wallet;
 
-use args::{Args, Commands};
+use args::{Args, Commands, ConfirmationArgs, HtlcSpendArgs};
 use clap::Parser;
+use std::io::{IsTerminal, Write};
 use std::str::FromStr;
+use std::time::Duration;
 
-use crate::blockchain::create_bitcoin_rpc_client;
-use crate::primitives::SwapInfo;
-use crate::taproot::{new_atomic_swap, withdraw_from_taproot_htlc};
-use crate::transaction::send_bitcoin_to_address;
-use crate::wallet::{BitcoinWallet, WalletFactory, format_satoshis_to_btc, btc_to_satoshis};
-use bdk::bitcoin::secp256k1::SecretKey;
+use crate::blockchain::{
+    Backend, check_node, create_blockchain_backend, ensure_relayable_fee_rate, estimate_fee_rate,
+    load_rpc_config_file, set_allow_low_fee, set_gap_limit, set_rpc_proxy, set_rpc_timeout,
+    wait_for_confirmations,
+};
+use crate::constants::{DEFAULT_FEE_RATE_SAT_PER_VB, SWAPS_DIR};
+use crate::contract::{
+    HashType, TimelockKind, create_htlc_contract_with_hash_type, generate_secret, parse_secret,
+};
+use crate::escrow::KeyAggregation;
+use crate::keccak::keccak256;
+use crate::deployment::{deploy_htlc, find_htlc_outpoint, verify_htlc_funding};
+use crate::message::{sign_message, verify_message};
+use crate::output::say;
+use crate::primitives::{SwapInfo, SwapRecord};
+use crate::error::{FundingError, TransactionError};
+use crate::taproot::{
+    FundingPoll, SpendPath, SwapSpendPath, WithdrawalOptions, check_swap_status, contract_balance,
+    new_atomic_swap, rebuild_swap_info, refund_taproot_htlc, withdraw_from_taproot_htlc,
+};
+use crate::transaction::{
+    InputSelection, InputSignature, SendOptions, TransactionUtils, amount_paid_to, decode_raw_transaction,
+    extract_finalized_tx, is_dry_run, load_recipients, parse_op_return_data, publish_transaction, read_psbt, script_type_name,
+    write_psbt,
+};
+use crate::wallet::{
+    BitcoinWallet, DescriptorType, WalletConfig, WalletFactory, address_type_name,
+    btc_to_satoshis, derive_swap_secret, ensure_address_network, format_satoshis_to_btc,
+    net_amount, parse_address_for_network,
+};
+use bdk::{FeeRate, KeychainKind};
+use serde_json::json;
+use bdk::bitcoin::secp256k1::{Secp256k1, SecretKey};
+use bdk::bitcoin::{Address, Amount, Network, OutPoint, PublicKey, Txid};
+use bdk::blockchain::GetHeight;
 
 /// Application entry point
+///
+/// Failures exit with the code of their error category, see [`error::exit_code`].
+/// With `--json` the error and its exit code are also printed as a JSON object.
 #[tokio::main]
-async fn main() -> eyre::Result<()> {
+async fn main() {
     let cli_args = Args::parse();
+    logger::init(cli_args.log_level);
+    output::init(cli_args.json);
+    transaction::set_dry_run(cli_args.dry_run);
+
+    if let Err(report) = run(cli_args).await {
+        let exit_code = error::exit_code(&report);
+        eprintln!("Error: {:?}", report);
+        output::emit(&serde_json::json!({
+            "error": format!("{:#}", report),
+            "exit_code": exit_code,
+        }));
+        std::process::exit(exit_code);
+    }
+}
+
+/// Run the requested subcommand
+async fn run(cli_args: Args) -> eyre::Result<()> {
+    if let Some(rpc_config_path) = &cli_args.rpc_config {
+        load_rpc_config_file(rpc_config_path)?;
+    }
+    if let Some(timeout_secs) = cli_args.rpc_timeout {
+        set_rpc_timeout(timeout_secs)?;
+    }
+    if let Some(proxy) = cli_args.proxy {
+        set_rpc_proxy(proxy)?;
+    }
+    if let Some(gap_limit) = cli_args.gap_limit {
+        set_gap_limit(gap_limit as usize)?;
+    }
+    set_allow_low_fee(cli_args.allow_low_fee);
 
     match cli_args.command {
         Commands::Balance {
             wallet: wallet_config_path,
+            verbose,
+            min_conf,
         } => {
-            handle_balance_command(wallet_config_path).await?;
+            handle_balance_command(wallet_config_path, verbose, min_conf).await?;
+        }
+        Commands::Balances { wallets } => {
+            handle_balances_command(wallets).await?;
         }
         Commands::Send {
             from: source_wallet_path,
-            to: destination_wallet_path,
-            amount: btc_amount,
+            to: destination,
+            amount: requested_satoshis,
+            max,
+            fee_rate,
+            conf_target,
+            psbt_out,
+            op_return,
+            check,
+            coin_selection,
+            utxos,
+            change_to,
+        } => {
+            // --max sends everything, so any --amount given alongside it is ignored
+            let requested_satoshis = if max { None } else { requested_satoshis };
+            let output = match psbt_out {
+                Some(psbt_path) => SendOutput::Psbt(psbt_path),
+                None if check => SendOutput::Check,
+                None => SendOutput::Broadcast,
+            };
+            handle_send_command(
+                source_wallet_path,
+                destination,
+                requested_satoshis,
+                fee_rate,
+                conf_target,
+                output,
+                SendOptions {
+                    op_return: op_return.as_deref().map(parse_op_return_data).transpose()?,
+                    inputs: InputSelection {
+                        coin_selection,
+                        utxos,
+                    },
+                    change_to,
+                },
+            )
+            .await?;
+        }
+        Commands::MaxSend {
+            wallet: wallet_config_path,
+            to: destination,
+            fee_rate,
+        } => {
+            handle_max_send_command(wallet_config_path, destination, resolve_fee_rate(fee_rate))
+                .await?;
+        }
+        Commands::SignPsbt {
+            wallet: wallet_config_path,
+            psbt_in,
+            psbt_out,
+        } => {
+            handle_sign_psbt_command(wallet_config_path, psbt_in, psbt_out).await?;
+        }
+        Commands::InspectPsbt { psbt, wallets } => {
+            handle_inspect_psbt_command(psbt, wallets).await?;
+        }
+        Commands::Broadcast { psbt, network } => {
+            handle_broadcast_command(psbt, network).await?;
+        }
+        Commands::SendBatch {
+            from: source_wallet_path,
+            recipients_file,
+            fee_rate,
         } => {
-            handle_send_command(source_wallet_path, destination_wallet_path, btc_amount).await?;
+            handle_send_batch_command(source_wallet_path, recipients_file, resolve_fee_rate(fee_rate))
+                .await?;
+        }
+        Commands::History {
+            wallet: wallet_config_path,
+        } => {
+            handle_history_command(wallet_config_path).await?;
+        }
+        Commands::BumpFee {
+            wallet: wallet_config_path,
+            txid,
+            fee_rate,
+        } => {
+            handle_bump_fee_command(wallet_config_path, txid, fee_rate).await?;
+        }
+        Commands::Utxos {
+            wallet: wallet_config_path,
+        } => {
+            handle_utxos_command(wallet_config_path).await?;
+        }
+        Commands::Generate {
+            output,
+            word_count,
+            network,
+            descriptor_type,
+            force,
+        } => {
+            handle_generate_command(output, word_count, network, descriptor_type, force).await?;
+        }
+        Commands::ExportDescriptor {
+            wallet: wallet_config_path,
+            public,
+        } => {
+            handle_export_descriptor_command(wallet_config_path, public).await?;
         }
         Commands::Address {
             wallet: wallet_config_path,
+            count,
+        } => match count {
+            Some(count) => handle_reveal_addresses_command(wallet_config_path, count).await?,
+            None => handle_address_command(wallet_config_path).await?,
+        },
+        Commands::Ping { network } => {
+            handle_ping_command(network)?;
+        }
+        Commands::ValidateAddress { address, network } => {
+            handle_validate_address_command(address, network)?;
+        }
+        Commands::SignMessage { wallet, message } => {
+            handle_sign_message_command(wallet, message).await?;
+        }
+        Commands::VerifyMessage {
+            address,
+            message,
+            signature,
+        } => {
+            handle_verify_message_command(address, message, signature)?;
+        }
+        Commands::DecodeTx { hex } => {
+            handle_decode_tx_command(hex)?;
+        }
+        Commands::Swaps { dir } => {
+            handle_swaps_command(dir)?;
+        }
+        Commands::ExportOffer { record, out } => {
+            handle_export_offer_command(record, out)?;
+        }
+        Commands::SwapStatus { record } => {
+            handle_swap_status_command(record)?;
+        }
+        Commands::SwapRefund {
+            record,
+            wallet: sender_wallet_path,
+            fee_rate,
         } => {
-            handle_address_command(wallet_config_path).await?;
+            handle_swap_refund_command(record, sender_wallet_path, resolve_fee_rate(fee_rate)).await?;
+        }
+        Commands::RecoverSwap {
+            wallet: sender_wallet_path,
+            recipient: recipient_wallet_path,
+            recipient_pubkey,
+            index,
+            contract_address,
+            timelock,
+            fee_rate,
+            refund: _,
+        } => {
+            // clap takes --recipient-pubkey only with --refund and --recipient only without it
+            let spend = match (recipient_wallet_path, recipient_pubkey) {
+                (Some(recipient_wallet_path), _) => RecoverSpend::Withdraw(recipient_wallet_path),
+                (None, Some(recipient_public_key)) => RecoverSpend::Refund(recipient_public_key),
+                (None, None) => unreachable!("clap requires --recipient or --recipient-pubkey"),
+            };
+            handle_recover_swap_command(
+                sender_wallet_path,
+                spend,
+                index,
+                contract_address,
+                timelock,
+                resolve_fee_rate(fee_rate),
+            )
+            .await?;
         }
         Commands::Swap {
             from: source_wallet_path,
             to: destination_wallet_path,
-            amount: btc_amount,
+            amount: amount_satoshis,
             timelock,
+            fee_rate,
+            force,
+            deterministic,
+            yes,
+            confirmations,
         } => {
             handle_swap_command(
                 source_wallet_path,
                 destination_wallet_path,
-                btc_amount,
+                amount_satoshis,
                 timelock,
+                resolve_fee_rate(fee_rate),
+                SwapFlags {
+                    force,
+                    deterministic,
+                    yes,
+                },
+                confirmations,
+            )
+            .await?;
+        }
+        Commands::GenSecret { bytes } => {
+            handle_gen_secret_command(bytes)?;
+        }
+        Commands::HtlcCreate {
+            from: sender_wallet_path,
+            to: recipient_wallet_path,
+            amount: btc_amount,
+            secret,
+            timeout,
+            relative,
+            hash_type,
+            fee_rate,
+        } => {
+            handle_htlc_create_command(
+                sender_wallet_path,
+                recipient_wallet_path,
+                btc_amount,
+                secret,
+                timelock_from_args(timeout, relative),
+                hash_type,
+                resolve_fee_rate(fee_rate),
             )
             .await?;
         }
+        Commands::Claim {
+            htlc,
+            recipient_wallet: recipient_wallet_path,
+            sender_wallet: sender_wallet_path,
+        } => {
+            handle_claim_command(htlc, recipient_wallet_path, sender_wallet_path).await?;
+        }
+        Commands::Refund {
+            htlc,
+            sender_wallet: sender_wallet_path,
+            recipient_wallet: recipient_wallet_path,
+        } => {
+            handle_refund_command(htlc, sender_wallet_path, recipient_wallet_path).await?;
+        }
         Commands::Withdraw {
             wallet: recipient_wallet_path,
             sender: sender_wallet_path,
-            amount: btc_amount,
+            amount: amount_satoshis,
             timelock,
             swap_secret,
+            fee_rate,
+            wait_secs,
+            poll_interval_secs,
+            include_unconfirmed,
+            key_aggregation,
+            confirmations,
         } => {
+            let options = WithdrawalOptions {
+                fee_rate: resolve_fee_rate(fee_rate),
+                spend_path: SpendPath::KeyPath,
+                poll: wait_secs.map(|wait_secs| FundingPoll {
+                    interval: Duration::from_secs(poll_interval_secs),
+                    timeout: Duration::from_secs(wait_secs),
+                }),
+                include_unconfirmed,
+                key_aggregation,
+            };
             handle_withdraw_command(
                 recipient_wallet_path,
                 sender_wallet_path,
-                btc_amount,
+                amount_satoshis,
                 timelock,
                 swap_secret,
+                options,
+                confirmations,
             )
             .await?;
         }
@@ -81,43 +381,608 @@ async fn main() -> eyre::Result<()> {
     Ok(())
 }
 
+/// Build the contract timelock from the `--timeout` and `--relative` flags
+fn timelock_from_args(timeout: u32, relative: bool) -> TimelockKind {
+    if relative {
+        TimelockKind::Relative(timeout)
+    } else {
+        TimelockKind::Absolute(timeout)
+    }
+}
+
+/// Print a command's success line, or a reminder that a dry run broadcast nothing
+fn say_done(message: &str) {
+    if is_dry_run() {
+        say!("🧪 Dry run finished, nothing was broadcast");
+    } else {
+        say!("✅ {}", message);
+    }
+}
+
+/// Use the requested fee rate, falling back to the default when none was given
+fn resolve_fee_rate(fee_rate_sat_per_vb: Option<f32>) -> FeeRate {
+    FeeRate::from_sat_per_vb(fee_rate_sat_per_vb.unwrap_or(DEFAULT_FEE_RATE_SAT_PER_VB))
+}
+
 /// Handle the balance command - display wallet balance in both BTC and satoshis
 async fn handle_balance_command(
     wallet_config_path: std::path::PathBuf,
+    verbose: bool,
+    min_conf: Option<u32>,
 ) -> eyre::Result<()> {
-    let balance_satoshis = WalletFactory::get_balance_satoshis(&wallet_config_path).await?;
+    let wallet = BitcoinWallet::from_config_file(&wallet_config_path).await?;
+    let balance = wallet.get_detailed_balance().await?;
+    let balance_satoshis = balance.get_total();
     let balance_btc_formatted = format_satoshis_to_btc(balance_satoshis);
 
-    println!(
+    say!(
         "Balance: {} BTC ({} sats)",
         balance_btc_formatted, balance_satoshis
     );
+
+    if verbose {
+        let buckets = [
+            ("Confirmed", balance.confirmed),
+            ("Trusted pending", balance.trusted_pending),
+            ("Untrusted pending", balance.untrusted_pending),
+            ("Immature", balance.immature),
+        ];
+        for (label, satoshis) in buckets {
+            say!(
+                "  {}: {} BTC ({} sats)",
+                label,
+                format_satoshis_to_btc(satoshis),
+                satoshis
+            );
+        }
+    }
+
+    let mut result = json!({
+        "total_sat": balance_satoshis,
+        "confirmed_sat": balance.confirmed,
+        "trusted_pending_sat": balance.trusted_pending,
+        "untrusted_pending_sat": balance.untrusted_pending,
+        "immature_sat": balance.immature,
+    });
+
+    if let Some(min_conf) = min_conf {
+        let split = wallet.get_balance_with_min_conf(min_conf).await?;
+        say!(
+            "  Spendable (>= {} confirmations): {} BTC ({} sats)",
+            min_conf,
+            format_satoshis_to_btc(split.spendable),
+            split.spendable
+        );
+        say!(
+            "  Maturing (< {} confirmations): {} BTC ({} sats)",
+            min_conf,
+            format_satoshis_to_btc(split.maturing),
+            split.maturing
+        );
+        result["min_conf"] = json!(min_conf);
+        result["spendable_sat"] = json!(split.spendable);
+        result["maturing_sat"] = json!(split.maturing);
+    }
+
+    output::emit(&result);
+    Ok(())
+}
+
+/// Handle the balances command - show several wallets' balances in one table
+///
+/// A wallet that fails is shown with its error while the rest are still
+/// reported; the command then fails once the table is printed.
+async fn handle_balances_command(wallet_config_paths: Vec<std::path::PathBuf>) -> eyre::Result<()> {
+    let balances = WalletFactory::get_balances_satoshis(&wallet_config_paths).await;
+
+    say!("{:<40}  {:>20}  {:>18}", "WALLET", "BALANCE (BTC)", "SATS");
+    let mut total_satoshis = 0;
+    let mut failures = 0;
+    let mut rows = Vec::with_capacity(balances.len());
+    for (path, balance) in wallet_config_paths.iter().zip(&balances) {
+        match balance {
+            Ok(satoshis) => {
+                total_satoshis += satoshis;
+                say!(
+                    "{:<40}  {:>20}  {:>18}",
+                    path.display(),
+                    format_satoshis_to_btc(*satoshis),
+                    satoshis
+                );
+                rows.push(json!({ "wallet": path, "total_sat": satoshis }));
+            }
+            Err(e) => {
+                failures += 1;
+                let message = format!("{:#}", e);
+                // Keep the table one row per wallet; parse errors span several lines
+                say!("{:<40}  ❌ {}", path.display(), message.lines().next().unwrap_or_default());
+                rows.push(json!({ "wallet": path, "error": message }));
+            }
+        }
+    }
+    say!(
+        "Total: {} BTC ({} sats) across {} wallet(s)",
+        format_satoshis_to_btc(total_satoshis),
+        total_satoshis,
+        balances.len() - failures
+    );
+    output::emit(&json!({ "wallets": rows, "total_sat": total_satoshis }));
+
+    if failures > 0 {
+        return Err(eyre::eyre!(
+            "{} of {} wallet balance(s) could not be fetched",
+            failures,
+            balances.len()
+        ));
+    }
     Ok(())
 }
 
-/// Handle the send command - transfer Bitcoin between wallets
+/// What the send command does with the transaction it builds
+enum SendOutput {
+    /// Sign and broadcast it
+    Broadcast,
+    /// Sign it and run the node's mempool acceptance check only
+    Check,
+    /// Write it unsigned to a PSBT file
+    Psbt(std::path::PathBuf),
+}
+
+/// Handle the send command - transfer Bitcoin to another wallet or a raw address
+///
+/// Without an amount the whole wallet balance is sent, less the fee. With
+/// `SendOutput::Psbt` the transaction is written unsigned instead of being
+/// sent, and with `SendOutput::Check` it is only signed and test-accepted.
 async fn handle_send_command(
     source_wallet_path: std::path::PathBuf,
-    destination_wallet_path: std::path::PathBuf,
-    btc_amount: f64,
+    destination: String,
+    requested_satoshis: Option<u64>,
+    fee_rate_sat_per_vb: Option<f32>,
+    conf_target: Option<u16>,
+    output: SendOutput,
+    options: SendOptions,
 ) -> eyre::Result<()> {
     let source_wallet = BitcoinWallet::from_config_file(&source_wallet_path).await?;
-    let destination_address = WalletFactory::get_address(&destination_wallet_path).await?;
-    let amount_satoshis = btc_to_satoshis(btc_amount);
-    let blockchain_client = create_bitcoin_rpc_client()?;
+    let destination_address = WalletFactory::resolve_address(&destination).await?;
+    ensure_address_network(&destination_address, source_wallet.network())?;
+    if let Some(change_address) = &options.change_to {
+        ensure_address_network(change_address, source_wallet.network())?;
+    }
+    let destination_script = destination_address.script_pubkey();
+    let blockchain_client = create_blockchain_backend(source_wallet.network())?;
+
+    let fee_rate = match (fee_rate_sat_per_vb, conf_target) {
+        (None, Some(target_blocks)) => estimate_fee_rate(&blockchain_client, target_blocks)?,
+        _ => resolve_fee_rate(fee_rate_sat_per_vb),
+    };
+    ensure_relayable_fee_rate(&blockchain_client, fee_rate)?;
 
-    let transaction_id = send_bitcoin_to_address(
+    let (psbt, transaction_details) = TransactionUtils::build_unsigned_psbt(
         &blockchain_client,
         &source_wallet.wallet,
         destination_address,
-        amount_satoshis,
+        requested_satoshis,
+        fee_rate,
+        &options,
+    )?;
+
+    if let SendOutput::Psbt(psbt_path) = output {
+        let amount_satoshis = amount_paid_to(&psbt.unsigned_tx, &destination_script);
+        write_psbt(&psbt_path, &psbt)?;
+
+        say!("📝 Unsigned PSBT written to {}", psbt_path.display());
+        say!(
+            "📊 Amount: {} BTC ({} sats)",
+            format_satoshis_to_btc(amount_satoshis),
+            amount_satoshis
+        );
+        if let Some(fee_sat) = transaction_details.fee {
+            say!("💸 Fee: {} sats", fee_sat);
+        }
+        output::emit(&json!({
+            "psbt_path": psbt_path,
+            "amount_sat": amount_satoshis,
+            "fee_sat": transaction_details.fee,
+        }));
+        return Ok(());
+    }
+
+    if let SendOutput::Check = output {
+        let checked = TransactionUtils::sign_and_check(
+            &blockchain_client,
+            &source_wallet.wallet,
+            psbt,
+            transaction_details,
+        )?;
+        let amount_satoshis = checked.amount_to(&destination_script);
+
+        say!("✅ Transaction would be accepted by the mempool (not broadcast)");
+        say!(
+            "📊 Amount: {} BTC ({} sats)",
+            format_satoshis_to_btc(amount_satoshis),
+            amount_satoshis
+        );
+        say!(
+            "💸 Fee: {} sats ({:.2} sat/vB effective)",
+            checked.fee_sat,
+            checked.effective_fee_rate()
+        );
+        say!("🔗 Transaction ID: {}", checked.txid);
+        output::emit(&json!({
+            "txid": checked.txid,
+            "amount_sat": amount_satoshis,
+            "fee_sat": checked.fee_sat,
+            "fee_rate_sat_per_vb": checked.effective_fee_rate(),
+            "broadcast": false,
+        }));
+        return Ok(());
+    }
+
+    let broadcast_result = TransactionUtils::sign_and_broadcast(
+        &blockchain_client,
+        &source_wallet.wallet,
+        psbt,
+        transaction_details,
+    )?;
+    let amount_satoshis = broadcast_result.amount_to(&destination_script);
+    let change_outpoint = TransactionUtils::find_change_outpoint(
+        &source_wallet.wallet,
+        &broadcast_result.transaction,
+        &destination_script,
+        options.change_to.as_ref(),
+    )?;
+
+    say_done("Transaction sent successfully!");
+    say!(
+        "📊 Amount: {} BTC ({} sats)",
+        format_satoshis_to_btc(amount_satoshis),
+        amount_satoshis
+    );
+    say!(
+        "💸 Fee: {} sats ({:.2} sat/vB effective)",
+        broadcast_result.fee_sat,
+        broadcast_result.effective_fee_rate()
+    );
+    if let Some(data) = &options.op_return {
+        say!("📎 OP_RETURN: {} bytes", data.len());
+    }
+    if let Some(change_outpoint) = change_outpoint {
+        say!("↩️  Change: {}", change_outpoint);
+    }
+    say!("🔗 Transaction ID: {}", broadcast_result.txid);
+    output::emit(&json!({
+        "txid": broadcast_result.txid,
+        "amount_sat": amount_satoshis,
+        "fee_sat": broadcast_result.fee_sat,
+        "fee_rate_sat_per_vb": broadcast_result.effective_fee_rate(),
+        "change_outpoint": change_outpoint.map(|outpoint| outpoint.to_string()),
+        "broadcast": true,
+    }));
+
+    Ok(())
+}
+
+/// Handle the max-send command - show what sending the whole balance would deliver
+async fn handle_max_send_command(
+    wallet_config_path: std::path::PathBuf,
+    destination: String,
+    fee_rate: FeeRate,
+) -> eyre::Result<()> {
+    let wallet = BitcoinWallet::from_config_file(&wallet_config_path).await?;
+    let destination_address = WalletFactory::resolve_address(&destination).await?;
+    ensure_address_network(&destination_address, wallet.network())?;
+
+    let max_satoshis = wallet.max_sendable(fee_rate, &destination_address)?;
+
+    say!(
+        "📤 Max sendable: {} BTC ({} sats) at {} sat/vB",
+        format_satoshis_to_btc(max_satoshis),
+        max_satoshis,
+        fee_rate.as_sat_per_vb()
+    );
+    say!("📍 To: {}", destination_address);
+    output::emit(&json!({
+        "amount_sat": max_satoshis,
+        "to": destination_address.to_string(),
+        "fee_rate_sat_per_vb": fee_rate.as_sat_per_vb(),
+    }));
+
+    Ok(())
+}
+
+/// Handle the sign-psbt command - add the wallet's signatures to a PSBT file
+async fn handle_sign_psbt_command(
+    wallet_config_path: std::path::PathBuf,
+    psbt_in: std::path::PathBuf,
+    psbt_out: std::path::PathBuf,
+) -> eyre::Result<()> {
+    let wallet = BitcoinWallet::from_config_file(&wallet_config_path).await?;
+    let mut psbt = read_psbt(&psbt_in)?;
+
+    let is_finalized = TransactionUtils::sign_psbt(&wallet.wallet, &mut psbt)?;
+    write_psbt(&psbt_out, &psbt)?;
+
+    say!("✍️  Signed PSBT written to {}", psbt_out.display());
+    if is_finalized {
+        say!("✅ PSBT is finalized and ready to broadcast");
+    } else {
+        say!("⚠️  PSBT still needs signatures for some inputs");
+    }
+    output::emit(&json!({
+        "psbt_path": psbt_out,
+        "finalized": is_finalized,
+    }));
+
+    Ok(())
+}
+
+/// Handle the inspect-psbt command - show who still has to sign a PSBT
+async fn handle_inspect_psbt_command(
+    psbt_path: std::path::PathBuf,
+    wallet_config_paths: Vec<std::path::PathBuf>,
+) -> eyre::Result<()> {
+    let psbt = read_psbt(&psbt_path)?;
+    let input_count = psbt.inputs.len();
+    say!("📄 {} has {} input(s)", psbt_path.display(), input_count);
+
+    let mut claimed = vec![false; input_count];
+    let mut rows = Vec::with_capacity(wallet_config_paths.len());
+    for path in &wallet_config_paths {
+        let wallet = BitcoinWallet::from_config_file(path).await?;
+        let inputs = TransactionUtils::signable_inputs(&wallet.wallet, &psbt)?;
+        let indices_where = |wanted: InputSignature| -> Vec<usize> {
+            inputs
+                .iter()
+                .filter(|(_, state)| *state == wanted)
+                .map(|(index, _)| *index)
+                .collect()
+        };
+        let (signed, unsigned) = (indices_where(InputSignature::Present), indices_where(InputSignature::Missing));
+        for (index, _) in &inputs {
+            claimed[*index] = true;
+        }
+
+        if inputs.is_empty() {
+            say!("{:<40}  cannot sign any input", path.display());
+        } else {
+            say!("{:<40}  signed: {:?}  needs to sign: {:?}", path.display(), signed, unsigned);
+        }
+        rows.push(json!({ "wallet": path, "signed": signed, "unsigned": unsigned }));
+    }
+
+    let unclaimed: Vec<usize> = (0..input_count).filter(|index| !claimed[*index]).collect();
+    if !unclaimed.is_empty() {
+        say!("⚠️  No listed wallet can sign input(s) {:?}", unclaimed);
+    }
+    output::emit(&json!({
+        "inputs": input_count,
+        "wallets": rows,
+        "unclaimed": unclaimed,
+    }));
+
+    Ok(())
+}
+
+/// Handle the broadcast command - send a finalized PSBT to the network
+async fn handle_broadcast_command(
+    psbt_path: std::path::PathBuf,
+    network: Network,
+) -> eyre::Result<()> {
+    let psbt = read_psbt(&psbt_path)?;
+    let transaction = extract_finalized_tx(psbt)?;
+    let blockchain_client = create_blockchain_backend(network)?;
+
+    publish_transaction(&blockchain_client, &transaction, None)?;
+
+    say_done("Transaction broadcast successfully!");
+    say!("🔗 Transaction ID: {}", transaction.txid());
+    output::emit(&json!({ "txid": transaction.txid() }));
+
+    Ok(())
+}
+
+/// Handle the send-batch command - pay every recipient in a file with one transaction
+async fn handle_send_batch_command(
+    source_wallet_path: std::path::PathBuf,
+    recipients_file: std::path::PathBuf,
+    fee_rate: FeeRate,
+) -> eyre::Result<()> {
+    let source_wallet = BitcoinWallet::from_config_file(&source_wallet_path).await?;
+    let recipients = load_recipients(&recipients_file)?;
+    if recipients.is_empty() {
+        return Err(eyre::eyre!(
+            "No recipients found in {}",
+            recipients_file.display()
+        ));
+    }
+    for (address, _) in &recipients {
+        ensure_address_network(address, source_wallet.network())?;
+    }
+    let total_satoshis = recipients
+        .iter()
+        .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
+        .ok_or_else(|| eyre::eyre!("Total batch amount overflows"))?;
+    let blockchain_client = create_blockchain_backend(source_wallet.network())?;
+    ensure_relayable_fee_rate(&blockchain_client, fee_rate)?;
+
+    let txid = TransactionUtils::create_and_broadcast_batch(
+        &blockchain_client,
+        &source_wallet.wallet,
+        &recipients,
+        fee_rate,
+    )
+    .await?;
+
+    say_done("Batch sent successfully!");
+    say!("👥 Recipients: {}", recipients.len());
+    say!(
+        "📊 Total: {} BTC ({} sats)",
+        format_satoshis_to_btc(total_satoshis),
+        total_satoshis
+    );
+    say!("🔗 Transaction ID: {}", txid);
+    output::emit(&json!({
+        "txid": txid,
+        "recipients": recipients.len(),
+        "total_sat": total_satoshis,
+    }));
+
+    Ok(())
+}
+
+/// Handle the bump-fee command - replace an unconfirmed transaction at a higher fee rate
+async fn handle_bump_fee_command(
+    wallet_config_path: std::path::PathBuf,
+    txid: Txid,
+    fee_rate_sat_per_vb: f32,
+) -> eyre::Result<()> {
+    let wallet = BitcoinWallet::from_config_file(&wallet_config_path).await?;
+    let blockchain_client = create_blockchain_backend(wallet.network())?;
+    let fee_rate = FeeRate::from_sat_per_vb(fee_rate_sat_per_vb);
+    ensure_relayable_fee_rate(&blockchain_client, fee_rate)?;
+
+    let replacement_txid = TransactionUtils::bump_fee(
+        &blockchain_client,
+        &wallet.wallet,
+        txid,
+        fee_rate,
     )
     .await?;
 
-    println!("✅ Transaction sent successfully!");
-    println!("📊 Amount: {} BTC ({} sats)", btc_amount, amount_satoshis);
-    println!("🔗 Transaction ID: {}", transaction_id);
+    say_done(&format!("Fee bumped to {} sat/vB", fee_rate_sat_per_vb));
+    say!("🔁 Replaced: {}", txid);
+    say!("🔗 Transaction ID: {}", replacement_txid);
+    output::emit(&json!({
+        "txid": replacement_txid,
+        "replaced_txid": txid,
+        "fee_rate_sat_per_vb": fee_rate_sat_per_vb,
+    }));
+
+    Ok(())
+}
+
+/// Handle the list-utxos command - display each unspent output controlled by the wallet
+async fn handle_utxos_command(
+    wallet_config_path: std::path::PathBuf,
+) -> eyre::Result<()> {
+    let wallet = BitcoinWallet::from_config_file(&wallet_config_path).await?;
+    let unspent_outputs = wallet.list_unspent().await?;
+
+    let mut utxos = Vec::with_capacity(unspent_outputs.len());
+    for utxo in &unspent_outputs {
+        let confirmations = wallet.get_confirmations(&utxo.outpoint.txid)?;
+        utxos.push(json!({
+            "outpoint": utxo.outpoint.to_string(),
+            "amount_sat": utxo.txout.value,
+            "confirmations": confirmations,
+            "spendable": confirmations > 0,
+        }));
+        say!(
+            "UTXO: {} | {} sats | {} confirmations | spendable: {}",
+            utxo.outpoint,
+            utxo.txout.value,
+            confirmations,
+            confirmations > 0
+        );
+    }
+
+    let total_satoshis: u64 = unspent_outputs.iter().map(|utxo| utxo.txout.value).sum();
+    say!(
+        "Total: {} UTXOs, {} BTC ({} sats)",
+        unspent_outputs.len(),
+        format_satoshis_to_btc(total_satoshis),
+        total_satoshis
+    );
+    output::emit(&json!({
+        "utxos": utxos,
+        "total_sat": total_satoshis,
+    }));
+    Ok(())
+}
+
+/// Handle the history command - list the wallet's transactions with their net effect
+async fn handle_history_command(
+    wallet_config_path: std::path::PathBuf,
+) -> eyre::Result<()> {
+    let wallet = BitcoinWallet::from_config_file(&wallet_config_path).await?;
+    let transactions = wallet.list_transactions(false).await?;
+
+    for details in &transactions {
+        let fee = details
+            .fee
+            .map_or_else(|| "unknown".to_string(), |fee| format!("{} sats", fee));
+        let height = details
+            .confirmation_time
+            .as_ref()
+            .map_or_else(|| "unconfirmed".to_string(), |block_time| {
+                format!("height {}", block_time.height)
+            });
+        say!(
+            "TX: {} | {:+} sats | fee: {} | {}",
+            details.txid,
+            net_amount(details),
+            fee,
+            height
+        );
+    }
 
+    say!("Total: {} transactions", transactions.len());
+    output::emit(&json!({
+        "transactions": transactions
+            .iter()
+            .map(|details| json!({
+                "txid": details.txid,
+                "net_sat": net_amount(details),
+                "fee_sat": details.fee,
+                "height": details.confirmation_time.as_ref().map(|block_time| block_time.height),
+            }))
+            .collect::<Vec<_>>(),
+    }));
+    Ok(())
+}
+
+/// Handle the export-descriptor command - print the wallet's descriptor for backup or import
+async fn handle_export_descriptor_command(
+    wallet_config_path: std::path::PathBuf,
+    public: bool,
+) -> eyre::Result<()> {
+    let wallet = BitcoinWallet::from_config_file(&wallet_config_path).await?;
+
+    let descriptor = if public {
+        wallet.export_descriptor(KeychainKind::External)?
+    } else {
+        let descriptor = wallet.export_private_descriptor(KeychainKind::External)?;
+        eprintln!("⚠️  This descriptor contains the wallet's private key; anyone who sees it can spend the funds");
+        descriptor
+    };
+    say!("{}", descriptor);
+    output::emit(&json!({
+        "descriptor": descriptor,
+        "private": !public,
+    }));
+
+    Ok(())
+}
+
+/// Handle the generate-wallet command - write a new wallet config and show its first address
+async fn handle_generate_command(
+    output_path: std::path::PathBuf,
+    word_count: usize,
+    network: bdk::bitcoin::Network,
+    descriptor_type: DescriptorType,
+    force: bool,
+) -> eyre::Result<()> {
+    let config = WalletConfig::generate(network, descriptor_type, word_count)?;
+    config.save(&output_path, force)?;
+    let address = WalletFactory::get_address(&output_path).await?;
+
+    say!("✅ Wallet config written to {}", output_path.display());
+    say!("⚠️  Back up the mnemonic in this file; it is the only way to recover the funds");
+    say!("📬 First receiving address: {}", address);
+    output::emit(&json!({
+        "config_path": output_path,
+        "address": address,
+    }));
     Ok(())
 }
 
@@ -126,21 +991,448 @@ async fn handle_address_command(
     wallet_config_path: std::path::PathBuf,
 ) -> eyre::Result<()> {
     let address = WalletFactory::get_address(&wallet_config_path).await?;
-    println!("{}", address);
+    say!("{}", address);
+    output::emit(&json!({ "address": address }));
+    Ok(())
+}
+
+/// Handle the address command with --count - hand out fresh addresses
+async fn handle_reveal_addresses_command(
+    wallet_config_path: std::path::PathBuf,
+    count: u32,
+) -> eyre::Result<()> {
+    let addresses = WalletFactory::reveal_addresses(&wallet_config_path, count).await?;
+    for address_info in &addresses {
+        say!("{:<6} {}", address_info.index, address_info.address);
+    }
+    output::emit(&json!({
+        "addresses": addresses
+            .iter()
+            .map(|address_info| json!({
+                "index": address_info.index,
+                "address": address_info.address.to_string(),
+            }))
+            .collect::<Vec<_>>(),
+    }));
     Ok(())
 }
 
+/// Handle the ping command - check the node before starting anything that needs it
+fn handle_ping_command(network: Network) -> eyre::Result<()> {
+    let status = check_node(network)?;
+
+    say!("✅ Node is reachable");
+    say!("🌐 Chain: {}", status.chain.as_deref().unwrap_or("unknown (not reported by this backend)"));
+    say!("📦 Block height: {}", status.height);
+    if let Some(progress) = status.verification_progress {
+        say!("🔍 Verification progress: {:.2}%", progress * 100.0);
+    }
+    if !status.is_synced() {
+        say!("⚠️  Node is still in initial block download");
+    }
+    output::emit(&json!({
+        "network": network.to_string(),
+        "chain": status.chain,
+        "height": status.height,
+        "verification_progress": status.verification_progress,
+        "initial_block_download": status.initial_block_download,
+        "synced": status.is_synced(),
+    }));
+    Ok(())
+}
+
+/// Handle the validate-address command - check an address before sending to it
+fn handle_validate_address_command(address: String, network: Network) -> eyre::Result<()> {
+    let parsed = parse_address_for_network(&address, network)?;
+    let address_type = address_type_name(&parsed);
+
+    say!("✅ {} is a valid {} address", parsed, network);
+    say!("🏷️  Type: {}", address_type);
+    output::emit(&json!({
+        "address": parsed,
+        "network": network.to_string(),
+        "type": address_type,
+    }));
+    Ok(())
+}
+
+/// Handle the sign-message command - prove control of the wallet's address
+async fn handle_sign_message_command(
+    wallet_config_path: std::path::PathBuf,
+    message: String,
+) -> eyre::Result<()> {
+    let address = WalletFactory::get_address(&wallet_config_path).await?;
+    let secret_key = WalletFactory::extract_receiving_key(&wallet_config_path)?;
+    let signature = sign_message(&address, &secret_key, &message)?;
+
+    say!("📍 Address: {}", address);
+    say!("✍️  Signature: {}", signature);
+    output::emit(&json!({
+        "address": address,
+        "message": message,
+        "signature": signature,
+    }));
+    Ok(())
+}
+
+/// Handle the verify-message command - fails unless the signature matches
+fn handle_verify_message_command(
+    address: String,
+    message: String,
+    signature: String,
+) -> eyre::Result<()> {
+    let parsed = Address::from_str(address.trim())
+        .map_err(|e| TransactionError::InvalidAddress(format!("{}: {}", address.trim(), e)))?;
+    if !verify_message(&parsed, &message, &signature)? {
+        return Err(eyre::eyre!(
+            "Signature is not valid for {} and this message",
+            parsed
+        ));
+    }
+
+    say!("✅ Signature is valid for {}", parsed);
+    output::emit(&json!({
+        "address": parsed,
+        "message": message,
+        "valid": true,
+    }));
+    Ok(())
+}
+
+/// Handle the decode-tx command - show the fields of a raw transaction
+fn handle_decode_tx_command(hex: String) -> eyre::Result<()> {
+    let transaction = decode_raw_transaction(&hex)?;
+
+    say!("🔗 Transaction ID: {}", transaction.txid());
+    say!("📋 Version: {}", transaction.version);
+    say!("🔒 Locktime: {}", transaction.lock_time);
+    say!("📏 Size: {} vB", transaction.vsize());
+    say!("📥 Inputs: {}", transaction.input.len());
+    for (index, input) in transaction.input.iter().enumerate() {
+        say!(
+            "  #{} {} sequence {:#010x}, {} witness items",
+            index,
+            input.previous_output,
+            input.sequence.to_consensus_u32(),
+            input.witness.len()
+        );
+    }
+    say!("📤 Outputs: {}", transaction.output.len());
+    for (index, output) in transaction.output.iter().enumerate() {
+        say!(
+            "  #{} {} BTC ({} sats), {}",
+            index,
+            format_satoshis_to_btc(output.value),
+            output.value,
+            script_type_name(&output.script_pubkey)
+        );
+    }
+
+    output::emit(&json!({
+        "txid": transaction.txid(),
+        "version": transaction.version,
+        "locktime": transaction.lock_time.0,
+        "vsize": transaction.vsize(),
+        "inputs": transaction
+            .input
+            .iter()
+            .map(|input| json!({
+                "outpoint": input.previous_output.to_string(),
+                "sequence": input.sequence.to_consensus_u32(),
+                "witness_items": input.witness.len(),
+            }))
+            .collect::<Vec<_>>(),
+        "outputs": transaction
+            .output
+            .iter()
+            .map(|output| json!({
+                "value_sat": output.value,
+                "script_type": script_type_name(&output.script_pubkey),
+            }))
+            .collect::<Vec<_>>(),
+    }));
+    Ok(())
+}
+
+/// Handle the swaps command - list every swap record saved in a directory
+fn handle_swaps_command(swaps_dir: std::path::PathBuf) -> eyre::Result<()> {
+    let records = SwapRecord::load_all(&swaps_dir)
+        .map_err(|e| eyre::eyre!("Failed to read swaps directory {}: {}", swaps_dir.display(), e))?;
+
+    say!(
+        "{:<64}  {:>14}  {:>8}  {:<64}  CREATED (UNIX)",
+        "CONTRACT ADDRESS", "AMOUNT (BTC)", "TIMELOCK", "FUNDING TXID"
+    );
+    for (_, record) in &records {
+        say!(
+            "{:<64}  {:>14}  {:>8}  {:<64}  {}",
+            record.contract_address,
+            format_satoshis_to_btc(record.swap_info.amount_satoshis),
+            record.swap_info.timelock_duration_blocks,
+            record.funding_txid,
+            record.creation_timestamp
+        );
+    }
+    say!("Total: {} swaps in {}", records.len(), swaps_dir.display());
+    output::emit(&json!({
+        "swaps": records
+            .iter()
+            .map(|(path, record)| json!({ "path": path, "record": record }))
+            .collect::<Vec<_>>(),
+    }));
+    Ok(())
+}
+
+/// Handle the export-offer command - write a swap's public details for the counterparty
+fn handle_export_offer_command(record_path: std::path::PathBuf, out: std::path::PathBuf) -> eyre::Result<()> {
+    let record = SwapRecord::load_from_json(&record_path)
+        .map_err(|e| eyre::eyre!("Failed to load swap record {}: {}", record_path.display(), e))?;
+    let offer = record.to_offer().map_err(|e| eyre::eyre!(e))?;
+    offer
+        .save_to_json(&out)
+        .map_err(|e| eyre::eyre!("Failed to write offer to {}: {}", out.display(), e))?;
+
+    match &offer.swap_id {
+        Some(swap_id) => say!("📤 Offer for swap {} written to {}", swap_id, out.display()),
+        None => say!("📤 Offer written to {}", out.display()),
+    }
+    say!("📍 Contract address: {}", offer.contract_address);
+    say!("🔒 SHA256: {}", offer.commitment.sha256);
+    say!("🔒 Keccak256: {}", offer.commitment.keccak256);
+    say!("💡 The offer holds no secrets and is safe to share");
+    output::emit(&json!({
+        "offer_path": out,
+        "offer": offer,
+    }));
+    Ok(())
+}
+
+/// Handle the swap-status command - report the on-chain state of a saved swap
+fn handle_swap_status_command(record_path: std::path::PathBuf) -> eyre::Result<()> {
+    let record = SwapRecord::load_from_json(&record_path)
+        .map_err(|e| eyre::eyre!("Failed to load swap record {}: {}", record_path.display(), e))?;
+    let network = Address::from_str(&record.contract_address)?.network;
+    let blockchain_client = create_blockchain_backend(network)?;
+    let status = check_swap_status(&blockchain_client, &record)?;
+
+    say!("📄 Contract: {}", record.contract_address);
+    let state = match status.funding_confirmations {
+        None => {
+            say!("❓ Funding transaction {} not found", record.funding_txid);
+            "funding_not_found"
+        }
+        Some(confirmations) => {
+            say!(
+                "💰 Funding: {} ({} confirmations)",
+                record.funding_txid, confirmations
+            );
+            match (status.contract_outpoint, status.spend) {
+                (None, _) => {
+                    say!("❓ Funding transaction does not pay the contract address");
+                    "contract_not_paid"
+                }
+                (Some(outpoint), None) => {
+                    say!("🔒 Unspent: {}", outpoint);
+                    "unspent"
+                }
+                (Some(_), Some((txid, SwapSpendPath::Claimed))) => {
+                    say!("✅ Claimed by the recipient in {}", txid);
+                    "claimed"
+                }
+                (Some(_), Some((txid, SwapSpendPath::Refunded))) => {
+                    say!("↩️  Refunded to the sender in {}", txid);
+                    "refunded"
+                }
+            }
+        }
+    };
+    output::emit(&json!({
+        "contract_address": record.contract_address,
+        "funding_txid": record.funding_txid,
+        "funding_confirmations": status.funding_confirmations,
+        "contract_outpoint": status.contract_outpoint.map(|outpoint| outpoint.to_string()),
+        "spend_txid": status.spend.map(|(txid, _)| txid),
+        "state": state,
+    }));
+    Ok(())
+}
+
+/// Handle the swap-refund command - reclaim a matured taproot swap for the sender
+async fn handle_swap_refund_command(
+    record_path: std::path::PathBuf,
+    sender_wallet_path: std::path::PathBuf,
+    fee_rate: FeeRate,
+) -> eyre::Result<()> {
+    let record = SwapRecord::load_from_json(&record_path)
+        .map_err(|e| eyre::eyre!("Failed to load swap record {}: {}", record_path.display(), e))?;
+    let sender_wallet = BitcoinWallet::from_config_file(&sender_wallet_path).await?;
+    let (revocation_public_key, revocation_secret_key) =
+        WalletFactory::extract_keypair(&sender_wallet_path)?;
+    if revocation_public_key != record.swap_info.revocation_public_key {
+        return Err(eyre::eyre!(
+            "{} does not hold the swap's revocation key",
+            sender_wallet_path.display()
+        ));
+    }
+    let blockchain_client = create_blockchain_backend(sender_wallet.network())?;
+    ensure_relayable_fee_rate(&blockchain_client, fee_rate)?;
+
+    say!("↩️  Refunding {}", record.swap_info);
+    let refund_txid = refund_taproot_htlc(
+        &blockchain_client,
+        &sender_wallet.wallet,
+        &record.swap_info,
+        &revocation_secret_key,
+        fee_rate,
+    )
+    .await?;
+
+    say_done("Swap refunded to the sender");
+    say!("🔗 Transaction ID: {}", refund_txid);
+    output::emit(&json!({ "txid": refund_txid }));
+    Ok(())
+}
+
+/// How the recover-swap command spends the rebuilt contract
+enum RecoverSpend {
+    /// Withdraw to the recipient, signing with the key of their wallet config
+    Withdraw(std::path::PathBuf),
+    /// Refund the sender, for which the recipient's public key is enough
+    Refund(bdk::bitcoin::secp256k1::PublicKey),
+}
+
+/// Handle the recover-swap command - spend a deterministic swap from the seed alone
+///
+/// Re-derives the swap secret at `index`, rebuilds the contract from the two
+/// parties' keys and the timelock, and only spends once it pays `contract_address`.
+async fn handle_recover_swap_command(
+    sender_wallet_path: std::path::PathBuf,
+    spend: RecoverSpend,
+    index: u32,
+    contract_address: Address,
+    timelock_blocks: u32,
+    fee_rate: FeeRate,
+) -> eyre::Result<()> {
+    let sender_wallet = BitcoinWallet::from_config_file(&sender_wallet_path).await?;
+    ensure_address_network(&contract_address, sender_wallet.network())?;
+    let master_key = WalletFactory::extract_master_key(&sender_wallet_path)?;
+    let swap_secret_key = derive_swap_secret(&master_key, index)?;
+    // Only the withdrawal signs for the recipient, so only it reads their secret key
+    let (recipient_public_key, recipient_signer) = match spend {
+        RecoverSpend::Withdraw(recipient_wallet_path) => {
+            let (recipient_public_key, recipient_secret_key) =
+                WalletFactory::extract_keypair(&recipient_wallet_path)?;
+            (recipient_public_key, Some((recipient_wallet_path, recipient_secret_key)))
+        }
+        RecoverSpend::Refund(recipient_public_key) => (recipient_public_key, None),
+    };
+    let (revocation_public_key, revocation_secret_key) =
+        WalletFactory::extract_keypair(&sender_wallet_path)?;
+
+    let secp = Secp256k1::new();
+    let mut swap_info = rebuild_swap_info(
+        &swap_secret_key.public_key(&secp),
+        &recipient_public_key,
+        &revocation_public_key,
+        timelock_blocks,
+        &contract_address,
+    )?;
+    say!("✅ Secret index {} rebuilds contract {}", index, contract_address);
+
+    let blockchain_client = create_blockchain_backend(sender_wallet.network())?;
+    ensure_relayable_fee_rate(&blockchain_client, fee_rate)?;
+    swap_info.amount_satoshis = contract_balance(&blockchain_client, &swap_info, sender_wallet.network())?;
+    if swap_info.amount_satoshis == 0 {
+        return Err(FundingError::NeverFunded(format!(
+            "{} holds nothing to recover",
+            contract_address
+        ))
+        .into());
+    }
+
+    let Some((recipient_wallet_path, recipient_secret_key)) = recipient_signer else {
+        say!("↩️  Refunding {}", swap_info);
+        let refund_txid = refund_taproot_htlc(
+            &blockchain_client,
+            &sender_wallet.wallet,
+            &swap_info,
+            &revocation_secret_key,
+            fee_rate,
+        )
+        .await?;
+
+        say_done("Swap refunded to the sender");
+        say!("🔗 Transaction ID: {}", refund_txid);
+        output::emit(&json!({ "txid": refund_txid, "refunded": true }));
+        return Ok(());
+    };
+
+    // withdraw_from_taproot_htlc aggregates the escrow key itself from the recipient's
+    let withdrawal_swap_info = SwapInfo {
+        recipient_public_key,
+        ..swap_info
+    };
+    let destination_address = WalletFactory::get_address(&recipient_wallet_path).await?;
+    ensure_address_network(&destination_address, sender_wallet.network())?;
+    let withdrawal = withdraw_from_taproot_htlc(
+        &blockchain_client,
+        &sender_wallet.wallet,
+        destination_address,
+        &withdrawal_swap_info,
+        &recipient_secret_key,
+        &swap_secret_key,
+        WithdrawalOptions {
+            fee_rate,
+            spend_path: SpendPath::KeyPath,
+            poll: None,
+            include_unconfirmed: false,
+            // rebuild_swap_info aggregates like new_atomic_swap does
+            key_aggregation: KeyAggregation::Musig,
+        },
+    )
+    .await?;
+
+    say_done("Swap recovered to the recipient");
+    say!(
+        "📬 Withdrew {} BTC to {}",
+        format_satoshis_to_btc(withdrawal.amount_sat),
+        withdrawal.destination_address
+    );
+    say!("💸 Fee: {} sats", withdrawal.fee_sat);
+    say!("🔗 Transaction ID: {}", withdrawal.txid);
+    output::emit(&withdrawal);
+
+    Ok(())
+}
+
+/// Switches of the swap command
+struct SwapFlags {
+    /// Fund even if a record shows the swap was already funded
+    force: bool,
+    /// Derive the swap secret from the source wallet's seed
+    deterministic: bool,
+    /// Skip the confirmation prompt
+    yes: bool,
+}
+
 /// Handle the swap command - create atomic swap HTLC
 async fn handle_swap_command(
     source_wallet_path: std::path::PathBuf,
     destination_wallet_path: std::path::PathBuf,
-    btc_amount: f64,
+    amount_satoshis: u64,
     timelock_blocks: u32,
+    fee_rate: FeeRate,
+    flags: SwapFlags,
+    confirmations: ConfirmationArgs,
 ) -> eyre::Result<()> {
+    let SwapFlags {
+        force,
+        deterministic,
+        yes,
+    } = flags;
     let source_wallet = BitcoinWallet::from_config_file(&source_wallet_path).await?;
     let (recipient_public_key, _) = WalletFactory::extract_keypair(&destination_wallet_path)?;
     let (revocation_public_key, _) = WalletFactory::extract_keypair(&source_wallet_path)?;
-    let amount_satoshis = btc_to_satoshis(btc_amount);
 
     let mut swap_info = SwapInfo::new(
         recipient_public_key,
@@ -148,44 +1440,136 @@ async fn handle_swap_command(
         timelock_blocks,
         amount_satoshis,
     );
+    let swap_id = swap_info.swap_id();
+    ensure_swap_not_funded(&swap_id, force)?;
+
+    // Derived secrets are indexed by the swap's parameters, skipping any index a forced re-run used
+    let (swap_secret, secret_index) = if deterministic {
+        let secret_index = swap_info
+            .unused_secret_index(std::path::Path::new(SWAPS_DIR))
+            .map_err(|e| eyre::eyre!("Failed to read swaps directory {}: {}", SWAPS_DIR, e))?;
+        let master_key = WalletFactory::extract_master_key(&source_wallet_path)?;
+        (derive_swap_secret(&master_key, secret_index)?, Some(secret_index))
+    } else {
+        (SecretKey::new(&mut rand::thread_rng()), None)
+    };
 
-    let blockchain_client = create_bitcoin_rpc_client()?;
-    let mut rng = rand::thread_rng();
+    let blockchain_client = create_blockchain_backend(source_wallet.network())?;
+    ensure_relayable_fee_rate(&blockchain_client, fee_rate)?;
 
-    println!("🔄 Creating atomic swap...");
-    println!("📊 Amount: {} BTC ({} sats)", btc_amount, amount_satoshis);
-    println!("⏰ Timelock: {} blocks", timelock_blocks);
+    log::info!("Creating atomic swap locking {}", swap_info);
 
-    let transaction_id = new_atomic_swap(
+    let (creation, record_path) = new_atomic_swap(
         &blockchain_client,
         &source_wallet.wallet,
         &mut swap_info,
-        &mut rng,
+        &swap_secret,
+        secret_index,
+        fee_rate,
+        |quote| {
+            say!(
+                "💰 You will lock {} sats + {} sats fee = {} sats total",
+                quote.amount_sat,
+                quote.fee_sat,
+                quote.total_sat()
+            );
+            if yes || is_dry_run() || confirm("Fund the swap?")? {
+                Ok(())
+            } else {
+                Err(eyre::eyre!("Swap cancelled; nothing was broadcast"))
+            }
+        },
     )
     .await?;
 
-    println!("✅ Atomic swap created successfully!");
-    println!("🔗 Transaction ID: {}", transaction_id);
+    say_done("Atomic swap created successfully!");
+    say!("🆔 Swap ID: {}", swap_id);
+    if let Some(secret_index) = secret_index {
+        say!("🔑 Swap secret derived from the wallet seed at index {}", secret_index);
+    }
+    say!("🔗 Transaction ID: {}", creation.txid);
+    say!("📍 Contract address: {}", creation.contract_address);
+    say!("📜 Descriptor: {}", creation.descriptor);
+    if let Some(record_path) = &record_path {
+        say!("💾 Recovery data saved to {}", record_path.display());
+    }
+    let confirmation_count = await_confirmations(&blockchain_client, &creation.txid, confirmations).await?;
+    output::emit(&json!({
+        "swap_id": swap_id,
+        "secret_index": secret_index,
+        "txid": creation.txid,
+        "contract_address": creation.contract_address,
+        "descriptor": creation.descriptor,
+        "fee_sat": creation.fee_sat,
+        "record_path": record_path,
+        "confirmations": confirmation_count,
+    }));
 
     Ok(())
 }
 
+/// Ask on the terminal whether to go ahead, defaulting to no
+///
+/// Only asks when stdin is a terminal, so piped and CI runs go ahead without
+/// blocking. The question goes to stderr to keep `--json` output clean.
+fn confirm(question: &str) -> eyre::Result<bool> {
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        return Ok(true);
+    }
+    eprint!("{} [y/N] ", question);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    stdin.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Refuse to fund a swap whose record is already in the swaps directory, unless `force` is set
+///
+/// Retrying `swap` after a funding that looked like it failed would otherwise
+/// lock the same amount a second time.
+fn ensure_swap_not_funded(swap_id: &str, force: bool) -> eyre::Result<()> {
+    let existing = SwapRecord::find_by_swap_id(std::path::Path::new(SWAPS_DIR), swap_id)
+        .map_err(|e| eyre::eyre!("Failed to read swaps directory {}: {}", SWAPS_DIR, e))?;
+    let Some((record_path, record)) = existing else {
+        return Ok(());
+    };
+
+    if force {
+        log::warn!(
+            "Swap {} was already funded in {} ({}); funding it again because of --force",
+            swap_id,
+            record.funding_txid,
+            record_path.display()
+        );
+        return Ok(());
+    }
+    Err(FundingError::AlreadyFunded(format!(
+        "swap {} was funded in {} (record {}); pass --force to fund it again",
+        swap_id,
+        record.funding_txid,
+        record_path.display()
+    ))
+    .into())
+}
+
 /// Handle the withdraw command - withdraw funds from atomic swap HTLC
 async fn handle_withdraw_command(
     recipient_wallet_path: std::path::PathBuf,
     sender_wallet_path: std::path::PathBuf,
-    btc_amount: f64,
+    amount_satoshis: u64,
     timelock_blocks: u32,
     swap_secret_hex: String,
+    options: WithdrawalOptions,
+    confirmations: ConfirmationArgs,
 ) -> eyre::Result<()> {
     let sender_wallet = BitcoinWallet::from_config_file(&sender_wallet_path).await?;
     let (recipient_public_key, recipient_secret_key) = WalletFactory::extract_keypair(&recipient_wallet_path)?;
     let (revocation_public_key, _) = WalletFactory::extract_keypair(&sender_wallet_path)?;
-    
+
     let swap_secret_key = SecretKey::from_str(&swap_secret_hex)
         .map_err(|e| eyre::eyre!("Invalid swap secret key format: {}", e))?;
     
-    let amount_satoshis = btc_to_satoshis(btc_amount);
     let swap_info = SwapInfo::new(
         recipient_public_key,
         revocation_public_key,
@@ -193,26 +1577,331 @@ async fn handle_withdraw_command(
         amount_satoshis,
     );
 
-    let blockchain_client = create_bitcoin_rpc_client()?;
+    let blockchain_client = create_blockchain_backend(sender_wallet.network())?;
+    ensure_relayable_fee_rate(&blockchain_client, options.fee_rate)?;
     let destination_address = WalletFactory::get_address(&recipient_wallet_path).await?;
+    ensure_address_network(&destination_address, sender_wallet.network())?;
 
-    println!("💰 Withdrawing from atomic swap...");
-    println!("📊 Amount: {} BTC ({} sats)", btc_amount, amount_satoshis);
-    println!("⏰ Original timelock: {} blocks", timelock_blocks);
-    println!("🔑 Using swap secret: {}...", &swap_secret_hex[..16]);
+    log::info!(
+        "Withdrawing {} BTC ({} sats) from atomic swap with a {} block timelock",
+        format_satoshis_to_btc(amount_satoshis),
+        amount_satoshis,
+        timelock_blocks
+    );
 
-    let transaction_id = withdraw_from_taproot_htlc(
+    let withdrawal = withdraw_from_taproot_htlc(
         &blockchain_client,
         &sender_wallet.wallet,
         destination_address,
         &swap_info,
         &recipient_secret_key,
         &swap_secret_key,
+        options,
+    )
+    .await
+    .inspect_err(|e| match e.downcast_ref::<FundingError>() {
+        Some(FundingError::NeverFunded(_)) => {
+            eprintln!("💡 Check that the swap was created with the same wallets, amount and timelock")
+        }
+        Some(FundingError::NotYetConfirmed(_)) => eprintln!(
+            "💡 Wait for the next block, retry with --wait-secs, or pass --include-unconfirmed"
+        ),
+        Some(FundingError::AlreadyFunded(_) | FundingError::TimelockNotMatured(_)) | None => {}
+    })?;
+
+    say_done("Atomic swap withdrawal successful!");
+    say!(
+        "📬 Withdrew {} BTC to {}",
+        format_satoshis_to_btc(withdrawal.amount_sat),
+        withdrawal.destination_address
+    );
+    say!("💸 Fee: {} sats", withdrawal.fee_sat);
+    say!("🔗 Transaction ID: {}", withdrawal.txid);
+    let confirmation_count = await_confirmations(&blockchain_client, &withdrawal.txid, confirmations).await?;
+    let mut result = serde_json::to_value(&withdrawal)?;
+    result["confirmations"] = json!(confirmation_count);
+    output::emit(&result);
+
+    Ok(())
+}
+
+/// Wait for `txid` to reach `--wait-confirmations`, if given, returning the count it reached
+///
+/// Nothing is broadcast under `--dry-run`, so there is nothing to wait for.
+async fn await_confirmations(
+    blockchain_client: &Backend,
+    txid: &Txid,
+    confirmations: ConfirmationArgs,
+) -> eyre::Result<Option<u32>> {
+    let Some(wanted) = confirmations.wait_confirmations else {
+        return Ok(None);
+    };
+    if is_dry_run() {
+        return Ok(None);
+    }
+
+    say!("⏳ Waiting for {} confirmation(s) of {}", wanted, txid);
+    let timeout = Duration::from_secs(confirmations.confirmation_timeout_secs);
+    let reached = wait_for_confirmations(blockchain_client, txid, wanted, timeout).await?;
+    say!("✅ Confirmed with {} confirmation(s)", reached);
+    Ok(Some(reached))
+}
+
+/// Handle the gen-secret command - create a high-entropy preimage for an HTLC
+fn handle_gen_secret_command(len: usize) -> eyre::Result<()> {
+    let secret = generate_secret(len, &mut rand::thread_rng()).map_err(|e| eyre::eyre!(e))?;
+    let secret_hex = format!("0x{}", hex::encode(&secret));
+    let sha256_hex = hex::encode(HashType::Sha256.hash(&secret));
+    let keccak256_hex = format!("0x{}", hex::encode(keccak256(&secret)));
+
+    say!("🔑 Secret: {}", secret_hex);
+    say!("🔒 SHA256: {}", sha256_hex);
+    say!("🔒 Keccak256: {}", keccak256_hex);
+    say!("💡 Pass the secret to htlc-create --secret as printed and share only a hash");
+    output::emit(&json!({
+        "secret": secret_hex,
+        "sha256": sha256_hex,
+        "keccak256": keccak256_hex,
+    }));
+    Ok(())
+}
+
+/// Handle the htlc-create command - fund a P2WSH HTLC from the sender's wallet
+async fn handle_htlc_create_command(
+    sender_wallet_path: std::path::PathBuf,
+    recipient_wallet_path: std::path::PathBuf,
+    btc_amount: f64,
+    secret: String,
+    timelock: TimelockKind,
+    hash_type: HashType,
+    fee_rate: FeeRate,
+) -> eyre::Result<()> {
+    let sender_wallet = BitcoinWallet::from_config_file(&sender_wallet_path).await?;
+    let (recipient_public_key, _) = WalletFactory::extract_keypair(&recipient_wallet_path)?;
+    let (sender_public_key, _) = WalletFactory::extract_keypair(&sender_wallet_path)?;
+    let amount_satoshis = btc_to_satoshis(btc_amount).map_err(TransactionError::InvalidAmount)?;
+
+    let secret = parse_secret(&secret).map_err(|e| eyre::eyre!(e))?;
+    let contract = create_htlc_contract_with_hash_type(
+        &secret,
+        hash_type,
+        PublicKey::new(recipient_public_key),
+        PublicKey::new(sender_public_key),
+        timelock,
+    )
+    .map_err(|e| eyre::eyre!(e))?;
+    let blockchain_client = create_blockchain_backend(sender_wallet.network())?;
+    ensure_relayable_fee_rate(&blockchain_client, fee_rate)?;
+
+    log::info!(
+        "Creating HTLC for {} BTC ({} sats), refundable after {}",
+        btc_amount,
+        amount_satoshis,
+        timelock
+    );
+
+    let deployment = deploy_htlc(
+        &blockchain_client,
+        &sender_wallet.wallet,
+        &contract,
+        Amount::from_sat(amount_satoshis),
+        fee_rate,
     )
     .await?;
 
-    println!("✅ Atomic swap withdrawal successful!");
-    println!("🔗 Transaction ID: {}", transaction_id);
+    say_done("HTLC created successfully!");
+    say!("📬 Contract address: {}", deployment.address);
+    say!("Contract ID: {}", deployment.txid);
+    say!("Contract vout: {}", deployment.vout);
+    say!("Contract outpoint: {}", deployment.outpoint());
+    say!("💸 Fee: {} sats", deployment.fee_sat);
+    output::emit(&json!({
+        "contract_txid": deployment.txid,
+        "vout": deployment.vout,
+        "contract_address": deployment.address,
+        "outpoint": deployment.outpoint().to_string(),
+        "fee_sat": deployment.fee_sat,
+    }));
+
+    Ok(())
+}
+
+/// Handle the claim command - spend a P2WSH HTLC through the secret path
+async fn handle_claim_command(
+    htlc: HtlcSpendArgs,
+    recipient_wallet_path: std::path::PathBuf,
+    sender_wallet_path: std::path::PathBuf,
+) -> eyre::Result<()> {
+    let HtlcSpendArgs {
+        contract_txid,
+        vout,
+        amount: btc_amount,
+        secret,
+        timeout,
+        relative,
+        hash_type,
+        fee_rate,
+    } = htlc;
+    let timelock = timelock_from_args(timeout, relative);
+    let fee_rate = resolve_fee_rate(fee_rate);
+    let recipient_wallet = BitcoinWallet::from_config_file(&recipient_wallet_path).await?;
+    let network = recipient_wallet.network();
+    let (recipient_public_key, recipient_secret_key) =
+        WalletFactory::extract_keypair(&recipient_wallet_path)?;
+    let (sender_public_key, _) = WalletFactory::extract_keypair(&sender_wallet_path)?;
+    let amount_satoshis = btc_to_satoshis(btc_amount).map_err(TransactionError::InvalidAmount)?;
+
+    let secret = parse_secret(&secret).map_err(|e| eyre::eyre!(e))?;
+    let contract = create_htlc_contract_with_hash_type(
+        &secret,
+        hash_type,
+        PublicKey::new(recipient_public_key),
+        PublicKey::new(sender_public_key),
+        timelock,
+    )
+    .map_err(|e| eyre::eyre!(e))?;
+    let blockchain_client = create_blockchain_backend(network)?;
+    ensure_relayable_fee_rate(&blockchain_client, fee_rate)?;
+
+    let contract_address = contract.address(network);
+    // Check the funding on chain before revealing the secret
+    let contract_outpoint = verify_htlc_funding(
+        &blockchain_client,
+        &contract_txid,
+        &contract,
+        Amount::from_sat(amount_satoshis),
+    )?;
+    if let Some(vout) = vout
+        && vout != contract_outpoint.vout
+    {
+        return Err(FundingError::NeverFunded(format!(
+            "output {} of {} does not pay the contract; output {} does",
+            vout, contract_txid, contract_outpoint.vout
+        ))
+        .into());
+    }
+    let destination_address = WalletFactory::get_address(&recipient_wallet_path).await?;
+
+    log::info!(
+        "Claiming HTLC output {} holding {} BTC ({} sats)",
+        contract_outpoint,
+        btc_amount,
+        amount_satoshis
+    );
+
+    let claim_transaction = contract
+        .create_claim_transaction(
+            contract_outpoint,
+            Amount::from_sat(amount_satoshis),
+            &destination_address,
+            &secret,
+            &recipient_secret_key,
+            fee_rate,
+        )
+        .map_err(|e| eyre::eyre!("Failed to build claim transaction: {}", e))?;
+    let output_satoshis: u64 = claim_transaction.output.iter().map(|output| output.value).sum();
+    let fee_sat = amount_satoshis.saturating_sub(output_satoshis);
+    publish_transaction(&blockchain_client, &claim_transaction, Some(fee_sat))?;
+
+    say_done("HTLC claimed successfully!");
+    say!("Claim ID: {}", claim_transaction.txid());
+    output::emit(&json!({
+        "txid": claim_transaction.txid(),
+        "contract_outpoint": contract_outpoint.to_string(),
+        "contract_address": contract_address,
+    }));
+
+    Ok(())
+}
+
+/// Handle the refund command - spend a P2WSH HTLC through the timeout path
+async fn handle_refund_command(
+    htlc: HtlcSpendArgs,
+    sender_wallet_path: std::path::PathBuf,
+    recipient_wallet_path: std::path::PathBuf,
+) -> eyre::Result<()> {
+    let HtlcSpendArgs {
+        contract_txid,
+        vout,
+        amount: btc_amount,
+        secret,
+        timeout,
+        relative,
+        hash_type,
+        fee_rate,
+    } = htlc;
+    let timelock = timelock_from_args(timeout, relative);
+    let fee_rate = resolve_fee_rate(fee_rate);
+    let sender_wallet = BitcoinWallet::from_config_file(&sender_wallet_path).await?;
+    let network = sender_wallet.network();
+    let (sender_public_key, sender_secret_key) =
+        WalletFactory::extract_keypair(&sender_wallet_path)?;
+    let (recipient_public_key, _) = WalletFactory::extract_keypair(&recipient_wallet_path)?;
+    let amount_satoshis = btc_to_satoshis(btc_amount).map_err(TransactionError::InvalidAmount)?;
+
+    let secret = parse_secret(&secret).map_err(|e| eyre::eyre!(e))?;
+    let contract = create_htlc_contract_with_hash_type(
+        &secret,
+        hash_type,
+        PublicKey::new(recipient_public_key),
+        PublicKey::new(sender_public_key),
+        timelock,
+    )
+    .map_err(|e| eyre::eyre!(e))?;
+    let blockchain_client = create_blockchain_backend(network)?;
+    ensure_relayable_fee_rate(&blockchain_client, fee_rate)?;
+
+    // A transaction with lock time T can be mined in block T, so the refund
+    // is valid once the next block reaches the timeout. Relative timelocks
+    // depend on the funding depth and are left to the node to enforce.
+    let current_height = blockchain_client.get_height()?;
+    if let TimelockKind::Absolute(timeout) = timelock
+        && current_height + 1 < timeout
+    {
+        return Err(eyre::eyre!(
+            "HTLC timelock has not matured: refund is possible from block {}, current height is {} ({} blocks to go)",
+            timeout,
+            current_height,
+            timeout - current_height - 1
+        ));
+    }
+
+    let contract_address = contract.address(network);
+    let contract_outpoint = match vout {
+        Some(vout) => OutPoint::new(contract_txid, vout),
+        None => find_htlc_outpoint(&blockchain_client, &contract_txid, &contract_address)?,
+    };
+    let destination_address = WalletFactory::get_address(&sender_wallet_path).await?;
+
+    log::info!(
+        "Refunding HTLC output {} holding {} BTC ({} sats), timeout {} at height {}",
+        contract_outpoint,
+        btc_amount,
+        amount_satoshis,
+        timelock,
+        current_height
+    );
+
+    let refund_transaction = contract
+        .create_refund_transaction(
+            contract_outpoint,
+            Amount::from_sat(amount_satoshis),
+            &destination_address,
+            &sender_secret_key,
+            fee_rate,
+        )
+        .map_err(|e| eyre::eyre!("Failed to build refund transaction: {}", e))?;
+    let output_satoshis: u64 = refund_transaction.output.iter().map(|output| output.value).sum();
+    let fee_sat = amount_satoshis.saturating_sub(output_satoshis);
+    publish_transaction(&blockchain_client, &refund_transaction, Some(fee_sat))?;
+
+    say_done("HTLC refunded successfully!");
+    say!("Refund ID: {}", refund_transaction.txid());
+    output::emit(&json!({
+        "txid": refund_transaction.txid(),
+        "contract_outpoint": contract_outpoint.to_string(),
+        "contract_address": contract_address,
+    }));
 
     Ok(())
 }
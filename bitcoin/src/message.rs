@@ -0,0 +1,118 @@
+//! Signing and verifying messages to prove control of an address
+//!
+//! Uses the legacy scheme from Bitcoin Core's `signmessage`: the message is
+//! prefixed with `\x18Bitcoin Signed Message:\n` and its length, double
+//! SHA-256 hashed and signed with a recoverable ECDSA signature, encoded as 65
+//! bytes of base64. The verifier recovers the public key from the signature
+//! and checks that it hashes to the address.
+//!
+//! Core itself only verifies p2pkh addresses this way; like Electrum and most
+//! hardware wallets we apply the same scheme to p2wpkh. Taproot addresses
+//! commit to a tweaked x-only key that cannot be recovered from an ECDSA
+//! signature, so they need BIP322 and are not yet supported.
+
+use bdk::bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+use bdk::bitcoin::util::misc::{MessageSignature, signed_msg_hash};
+use bdk::bitcoin::{Address, AddressType, PublicKey};
+use eyre::{Result, eyre};
+
+use crate::error::TransactionError;
+use crate::wallet::address_type_name;
+
+/// Sign `message` with the key behind `address`, returning a base64 signature
+///
+/// Fails if `address` is not a p2wpkh address or does not belong to `secret_key`.
+pub fn sign_message(address: &Address, secret_key: &SecretKey, message: &str) -> Result<String> {
+    ensure_supported(address)?;
+    let secp = Secp256k1::new();
+    let public_key = PublicKey::new(secret_key.public_key(&secp));
+    if Address::p2wpkh(&public_key, address.network)? != *address {
+        return Err(eyre!("{} does not belong to the signing key", address));
+    }
+
+    let message_hash = Message::from_slice(&signed_msg_hash(message)[..])?;
+    let signature = secp.sign_ecdsa_recoverable(&message_hash, secret_key);
+    Ok(MessageSignature::new(signature, true).to_base64())
+}
+
+/// Check that a base64 `signature` over `message` was made by the key behind `address`
+///
+/// Returns `Ok(false)` for a well-formed signature by some other key or over
+/// another message, and an error if the signature cannot be decoded or the
+/// address type is not supported.
+pub fn verify_message(address: &Address, message: &str, signature: &str) -> Result<bool> {
+    ensure_supported(address)?;
+    let signature = MessageSignature::from_base64(signature.trim())
+        .map_err(|e| eyre!("Invalid message signature: {}", e))?;
+
+    let secp = Secp256k1::verification_only();
+    let Ok(public_key) = signature.recover_pubkey(&secp, signed_msg_hash(message)) else {
+        return Ok(false);
+    };
+    // An uncompressed key has no p2wpkh address
+    if !public_key.compressed {
+        return Ok(false);
+    }
+    Ok(Address::p2wpkh(&public_key, address.network)? == *address)
+}
+
+fn ensure_supported(address: &Address) -> Result<()> {
+    match address.address_type() {
+        Some(AddressType::P2wpkh) => Ok(()),
+        Some(AddressType::P2tr) => Err(TransactionError::InvalidAddress(format!(
+            "{}: message signing for taproot addresses (BIP322) is not yet supported",
+            address
+        ))
+        .into()),
+        _ => Err(TransactionError::InvalidAddress(format!(
+            "{}: message signing only supports p2wpkh addresses, not {}",
+            address,
+            address_type_name(address)
+        ))
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk::bitcoin::Network;
+    use std::str::FromStr;
+
+    fn key_and_address(byte: u8) -> (SecretKey, Address) {
+        let secret_key = SecretKey::from_slice(&[byte; 32]).unwrap();
+        let public_key = PublicKey::new(secret_key.public_key(&Secp256k1::new()));
+        (secret_key, Address::p2wpkh(&public_key, Network::Regtest).unwrap())
+    }
+
+    #[test]
+    fn signatures_verify_only_for_the_signed_message_and_address() {
+        let (secret_key, address) = key_and_address(1);
+        let (_, other_address) = key_and_address(2);
+
+        let signature = sign_message(&address, &secret_key, "swap offer #42").unwrap();
+        assert!(verify_message(&address, "swap offer #42", &signature).unwrap());
+        assert!(!verify_message(&address, "swap offer #43", &signature).unwrap());
+        assert!(!verify_message(&other_address, "swap offer #42", &signature).unwrap());
+
+        assert!(sign_message(&other_address, &secret_key, "swap offer #42").is_err());
+        assert!(verify_message(&address, "swap offer #42", "not base64!").is_err());
+    }
+
+    #[test]
+    fn taproot_and_legacy_addresses_are_rejected() {
+        let (secret_key, address) = key_and_address(1);
+        let signature = sign_message(&address, &secret_key, "hello").unwrap();
+
+        let taproot = Address::from_str(
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+        )
+        .unwrap();
+        let err = verify_message(&taproot, "hello", &signature).unwrap_err();
+        assert!(err.to_string().contains("taproot"), "{}", err);
+
+        let p2pkh = Address::from_str("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2").unwrap();
+        let err = verify_message(&p2pkh, "hello", &signature).unwrap_err();
+        assert!(err.to_string().contains("p2pkh"), "{}", err);
+    }
+}
@@ -0,0 +1,44 @@
+//! Command output for humans or scripts
+//!
+//! By default commands print human-readable progress and results to stdout.
+//! With `--json` those lines move to stderr and each command instead prints
+//! exactly one JSON object to stdout, so callers can parse the result without
+//! depending on the wording of the prose.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Selects JSON output for the rest of the run
+pub fn init(json: bool) {
+    JSON_OUTPUT.store(json, Ordering::Relaxed);
+}
+
+/// Whether `--json` was given
+pub fn is_json() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Print the machine-readable result of a command; does nothing without `--json`
+pub fn emit<T: Serialize>(result: &T) {
+    if is_json() {
+        match serde_json::to_string(result) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Failed to serialize command output: {}", e),
+        }
+    }
+}
+
+/// Print a human-readable line: to stdout normally, to stderr with `--json`
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::output::is_json() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use say;
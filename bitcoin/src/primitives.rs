@@ -4,11 +4,25 @@
 //! This module contains core data structures used throughout the Bitcoin CLI,
 //! including swap information and contract parameters.
 
+use bdk::bitcoin::hashes::{Hash, HashEngine, sha256};
 use bdk::bitcoin::secp256k1::{PublicKey, SecretKey};
 use bdk::bitcoin::{Address as BitcoinAddress, Txid};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::constants::LATEST_SWAP_RECORD_FILE;
+use crate::escrow::KeyAggregation;
+use crate::keccak::keccak256;
+use crate::wallet::format_satoshis_to_btc;
+
+/// Dust limit of a P2TR output at the default 3 sat/vB dust relay fee
+pub const TAPROOT_DUST_LIMIT_SAT: u64 = 330;
+
+/// Largest timelock accepted by default, the most a BIP68 relative height lock can encode
+pub const DEFAULT_MAX_TIMELOCK_BLOCKS: u32 = 65_535;
+
 /// Information required for atomic swap operations
 /// 
 /// This struct encapsulates the public parameters needed to create and manage
@@ -52,14 +66,34 @@ impl SwapInfo {
     }
     
     /// Validates that the swap info contains valid parameters
+    ///
+    /// Uses [`DEFAULT_MAX_TIMELOCK_BLOCKS`] as the timelock limit.
     pub fn validate(&self) -> Result<(), &'static str> {
+        self.validate_with_max_timelock(DEFAULT_MAX_TIMELOCK_BLOCKS)
+    }
+
+    /// Validates the swap info, allowing timelocks of up to `max_timelock_blocks`
+    pub fn validate_with_max_timelock(&self, max_timelock_blocks: u32) -> Result<(), &'static str> {
         if self.amount_satoshis == 0 {
             return Err("Amount must be greater than zero");
         }
+
+        // The swap locks funds in a P2TR output
+        if self.amount_satoshis < TAPROOT_DUST_LIMIT_SAT {
+            return Err("Amount is below the 330 sat dust limit of a taproot output");
+        }
         
         if self.timelock_duration_blocks == 0 {
             return Err("Timelock duration must be greater than zero");
         }
+
+        if self.timelock_duration_blocks > max_timelock_blocks {
+            return Err("Timelock duration exceeds the maximum allowed number of blocks");
+        }
+
+        if self.recipient_public_key == self.revocation_public_key {
+            return Err("Recipient and revocation public keys must differ");
+        }
         
         Ok(())
     }
@@ -68,6 +102,119 @@ impl SwapInfo {
     pub fn amount_btc(&self) -> f64 {
         self.amount_satoshis as f64 / 100_000_000.0
     }
+
+    /// Deterministic ID of the swap, the hex SHA256 of the recipient key, amount and timelock
+    ///
+    /// Running the same swap twice gives the same ID, so a saved record with
+    /// this ID means the swap was already funded.
+    pub fn swap_id(&self) -> String {
+        let mut engine = sha256::Hash::engine();
+        engine.input(&self.recipient_public_key.serialize());
+        engine.input(&self.amount_satoshis.to_le_bytes());
+        engine.input(&self.timelock_duration_blocks.to_le_bytes());
+        sha256::Hash::from_engine(engine).to_string()
+    }
+
+    /// Index of the deterministic swap secret for this swap, taken from its ID
+    ///
+    /// Depends only on the swap's parameters, so the secret can be re-derived
+    /// from the wallet seed without any saved record. Always below 2^31.
+    pub fn secret_index(&self) -> u32 {
+        let swap_id = self.swap_id();
+        u32::from_str_radix(&swap_id[..8], 16).expect("swap ID is hex") & 0x7fff_ffff
+    }
+
+    /// The first index from [`Self::secret_index`] on that no record in `swaps_dir` uses
+    ///
+    /// Funding the same swap again with `--force` must not reuse its secret,
+    /// or both fundings would lock to one contract. A missing directory holds
+    /// no records.
+    pub fn unused_secret_index(&self, swaps_dir: &Path) -> std::io::Result<u32> {
+        let used: Vec<u32> = if swaps_dir.exists() {
+            SwapRecord::load_all(swaps_dir)?
+                .into_iter()
+                .filter_map(|(_, record)| record.secret_index)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let mut secret_index = self.secret_index();
+        while used.contains(&secret_index) {
+            secret_index = (secret_index + 1) & 0x7fff_ffff;
+        }
+        Ok(secret_index)
+    }
+
+    /// One-line human readable description of the swap, see the `Display` impl
+    pub fn summary(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Leading hex digits shown when abbreviating a public key
+const PUBKEY_PREFIX_LEN: usize = 10;
+
+fn abbreviate_public_key(public_key: &PublicKey) -> String {
+    let hex = public_key.to_string();
+    format!("{}…", &hex[..PUBKEY_PREFIX_LEN])
+}
+
+impl fmt::Display for SwapInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} BTC to {}, timelock {} blocks, revocable by {}",
+            format_satoshis_to_btc(self.amount_satoshis),
+            abbreviate_public_key(&self.recipient_public_key),
+            self.timelock_duration_blocks,
+            abbreviate_public_key(&self.revocation_public_key)
+        )
+    }
+}
+
+/// Which hop of a multi-hop swap a secret belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretRole {
+    /// Secret generated by the party that starts the chain
+    Initiator,
+    /// Secret forwarded by a party in the middle of the chain
+    Intermediary,
+    /// Secret revealed to the party at the end of the chain
+    Responder,
+}
+
+/// A secret and the SHA256 commitment its hash lock is built on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretEntry {
+    /// Preimage of the hash lock (hex encoded)
+    pub secret_hex: String,
+
+    /// SHA256 of the secret bytes (hex encoded)
+    pub sha256_hash: String,
+
+    /// Hop of the swap this secret locks
+    pub role: SecretRole,
+}
+
+impl SecretEntry {
+    /// Creates an entry for `secret`, computing its commitment
+    pub fn new(secret: &[u8], role: SecretRole) -> Self {
+        Self {
+            secret_hex: hex::encode(secret),
+            sha256_hash: sha256::Hash::hash(secret).to_string(),
+            role,
+        }
+    }
+
+    /// Validates that the secret is hex and hashes to the recorded commitment
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let secret = hex::decode(&self.secret_hex).map_err(|_| "Secret entry is not valid hex")?;
+        if sha256::Hash::hash(&secret).to_string() != self.sha256_hash.to_ascii_lowercase() {
+            return Err("Secret entry hash does not match its secret");
+        }
+        Ok(())
+    }
 }
 
 /// Complete record of an atomic swap including all persistent data
@@ -81,6 +228,13 @@ pub struct SwapRecord {
     
     /// Secret key used in the atomic swap mechanism (hex encoded)
     pub swap_secret: String,
+
+    /// keccak256 of the swap secret bytes (hex encoded), the commitment the
+    /// prover checks the secret against
+    ///
+    /// Records written before it was stored lack it; loading fills it in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_hash: Option<String>,
     
     /// Taproot descriptor string for the contract
     pub descriptor_string: String,
@@ -93,6 +247,27 @@ pub struct SwapRecord {
     
     /// Unix timestamp when the swap was created
     pub creation_timestamp: u64,
+
+    /// Derivation index of `swap_secret` when it was derived from the wallet seed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_index: Option<u32>,
+
+    /// ID of the swap as printed by `swap`, from [`SwapInfo::swap_id`] before
+    /// the recipient key in `swap_info` was replaced by the escrow key
+    ///
+    /// Records written before it was stored lack it and match no swap ID.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub swap_id: Option<String>,
+
+    /// How the escrow key in `swap_info` was aggregated; legacy addition when missing
+    #[serde(default)]
+    pub key_aggregation: KeyAggregation,
+
+    /// Further secrets of a multi-hop swap, one per hash lock in the chain
+    ///
+    /// Empty for single-hop swaps, which only use `swap_secret`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<SecretEntry>,
 }
 
 impl SwapRecord {
@@ -112,13 +287,23 @@ impl SwapRecord {
             
         Self {
             swap_info,
+            secret_hash: Some(hex::encode(keccak256(&swap_secret.secret_bytes()))),
             swap_secret: swap_secret_hex,
             descriptor_string,
             contract_address: contract_address.to_string(),
             funding_txid: funding_txid.to_string(),
             creation_timestamp,
+            secret_index: None,
+            swap_id: None,
+            key_aggregation: KeyAggregation::Musig,
+            secrets: Vec::new(),
         }
     }
+
+    /// Adds a secret for another hop of a multi-hop swap
+    pub fn add_secret(&mut self, entry: SecretEntry) {
+        self.secrets.push(entry);
+    }
     
     /// Validates the swap record
     pub fn validate(&self) -> Result<(), &'static str> {
@@ -127,6 +312,13 @@ impl SwapRecord {
         if self.swap_secret.is_empty() {
             return Err("Swap secret cannot be empty");
         }
+
+        if let Some(secret_hash) = &self.secret_hash {
+            let swap_secret = hex::decode(&self.swap_secret).map_err(|_| "Swap secret is not valid hex")?;
+            if hex::encode(keccak256(&swap_secret)) != secret_hash.trim_start_matches("0x").to_ascii_lowercase() {
+                return Err("Secret hash does not match the swap secret");
+            }
+        }
         
         if self.descriptor_string.is_empty() {
             return Err("Descriptor string cannot be empty");
@@ -139,27 +331,484 @@ impl SwapRecord {
         if self.funding_txid.is_empty() {
             return Err("Funding transaction ID cannot be empty");
         }
+
+        for entry in &self.secrets {
+            entry.validate()?;
+        }
         
         Ok(())
     }
     
-    /// Saves the swap record to JSON file
-    pub fn save_to_json(&self, file_path: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+    /// Path of the record for this swap inside `swaps_dir`, keyed by funding txid
+    pub fn file_path(&self, swaps_dir: &Path) -> PathBuf {
+        swaps_dir.join(format!("swap_{}.json", self.funding_txid))
+    }
+
+    /// Saves the swap record to JSON file, creating the parent directory if needed
+    pub fn save_to_json(&self, file_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
         self.validate().map_err(|e| format!("Validation failed: {}", e))?;
-        
+
+        if let Some(parent) = file_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
         let json_string = serde_json::to_string_pretty(self)?;
-        std::fs::write(file_path, json_string)?;
+        write_private_file(file_path, json_string.as_bytes())?;
         
         Ok(())
     }
     
     /// Loads a swap record from JSON file
-    pub fn load_from_json(file_path: &std::path::Path) -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn load_from_json(file_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
         let json_string = std::fs::read_to_string(file_path)?;
-        let swap_record: SwapRecord = serde_json::from_str(&json_string)?;
+        let mut swap_record: SwapRecord = serde_json::from_str(&json_string)?;
         
         swap_record.validate().map_err(|e| format!("Validation failed: {}", e))?;
+        if swap_record.secret_hash.is_none() {
+            let swap_secret = hex::decode(&swap_record.swap_secret).map_err(|_| "Swap secret is not valid hex")?;
+            swap_record.secret_hash = Some(hex::encode(keccak256(&swap_secret)));
+        }
         
         Ok(swap_record)
     }
-}
\ No newline at end of file
+
+    /// Loads every swap record in `swaps_dir`, oldest first
+    ///
+    /// Files that are not valid swap records are skipped with a warning. The
+    /// copy of the latest record is skipped too, as its swap is listed under
+    /// its own txid-keyed file.
+    pub fn load_all(swaps_dir: &Path) -> std::io::Result<Vec<(PathBuf, SwapRecord)>> {
+        let mut records = Vec::new();
+        for entry in std::fs::read_dir(swaps_dir)? {
+            let path = entry?.path();
+            let is_json = path.extension().is_some_and(|extension| extension == "json");
+            let is_latest_copy = path
+                .file_name()
+                .is_some_and(|name| name == LATEST_SWAP_RECORD_FILE);
+            if !is_json || is_latest_copy {
+                continue;
+            }
+
+            match Self::load_from_json(&path) {
+                Ok(record) => records.push((path, record)),
+                Err(e) => log::warn!("Skipping {}: {}", path.display(), e),
+            }
+        }
+        records.sort_by_key(|(_, record)| record.creation_timestamp);
+        Ok(records)
+    }
+
+    /// Finds the most recent record in `swaps_dir` for the swap with ID `swap_id`
+    ///
+    /// Matches the ID stored in the record, not one hashed from its escrow key.
+    /// A missing directory holds no records.
+    pub fn find_by_swap_id(
+        swaps_dir: &Path,
+        swap_id: &str,
+    ) -> std::io::Result<Option<(PathBuf, SwapRecord)>> {
+        if !swaps_dir.exists() {
+            return Ok(None);
+        }
+        Ok(Self::load_all(swaps_dir)?
+            .into_iter()
+            .rev()
+            .find(|(_, record)| record.swap_id.as_deref() == Some(swap_id)))
+    }
+
+    /// The public half of this record, to hand to the counterparty
+    ///
+    /// Carries the hash commitments to the swap secret and to every multi-hop
+    /// secret, never the secrets themselves.
+    pub fn to_offer(&self) -> Result<SwapOffer, &'static str> {
+        self.validate()?;
+        let swap_secret = hex::decode(&self.swap_secret).map_err(|_| "Swap secret is not valid hex")?;
+        let hop_commitments = self
+            .secrets
+            .iter()
+            .map(|entry| {
+                let secret = hex::decode(&entry.secret_hex).map_err(|_| "Secret entry is not valid hex")?;
+                Ok(SecretCommitment::new(&secret))
+            })
+            .collect::<Result<_, &'static str>>()?;
+
+        Ok(SwapOffer {
+            swap_id: self.swap_id.clone(),
+            contract_address: self.contract_address.clone(),
+            descriptor: self.descriptor_string.clone(),
+            funding_txid: self.funding_txid.clone(),
+            amount_satoshis: self.swap_info.amount_satoshis,
+            timelock_duration_blocks: self.swap_info.timelock_duration_blocks,
+            recipient_public_key: self.swap_info.recipient_public_key,
+            revocation_public_key: self.swap_info.revocation_public_key,
+            key_aggregation: self.key_aggregation,
+            commitment: SecretCommitment::new(&swap_secret),
+            hop_commitments,
+        })
+    }
+}
+
+/// Hash commitments to a secret, in the forms each chain's hash lock uses
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretCommitment {
+    /// SHA256 of the secret bytes (hex encoded), for Bitcoin hash locks
+    pub sha256: String,
+
+    /// Keccak-256 of the secret bytes (`0x`-prefixed hex), for Ethereum hash locks
+    pub keccak256: String,
+}
+
+impl SecretCommitment {
+    /// Commitments to `secret`
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            sha256: sha256::Hash::hash(secret).to_string(),
+            keccak256: format!("0x{}", hex::encode(keccak256(secret))),
+        }
+    }
+}
+
+/// A swap as offered to the counterparty: everything needed to verify and
+/// monitor its contract, without the secret
+///
+/// Built with [`SwapRecord::to_offer`]. Unlike the record it is safe to share.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwapOffer {
+    /// ID of the swap, as printed by `swap`; missing for records saved before it was stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub swap_id: Option<String>,
+
+    /// HTLC contract address
+    pub contract_address: String,
+
+    /// Public taproot descriptor of the contract
+    pub descriptor: String,
+
+    /// Transaction ID of the funding transaction
+    pub funding_txid: String,
+
+    /// Amount locked in the contract (in satoshis)
+    pub amount_satoshis: u64,
+
+    /// Number of blocks before the sender can refund
+    pub timelock_duration_blocks: u32,
+
+    /// Key that spends the contract through the key path; for a funded swap
+    /// this is the escrow key aggregated from the swap key and the recipient's
+    pub recipient_public_key: PublicKey,
+
+    /// Key that can refund the contract after the timelock
+    pub revocation_public_key: PublicKey,
+
+    /// How the escrow key was aggregated, for the recipient's `withdraw --key-aggregation`
+    #[serde(default)]
+    pub key_aggregation: KeyAggregation,
+
+    /// Commitments to the swap secret
+    pub commitment: SecretCommitment,
+
+    /// Commitments to the further secrets of a multi-hop swap, in record order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hop_commitments: Vec<SecretCommitment>,
+}
+
+impl SwapOffer {
+    /// Saves the offer to a JSON file, creating the parent directory if needed
+    pub fn save_to_json(&self, file_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = file_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(file_path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Loads an offer received from the counterparty
+    pub fn load_from_json(file_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let json_string = std::fs::read_to_string(file_path)?;
+        Ok(serde_json::from_str(&json_string)?)
+    }
+}
+/// Writes a file readable only by its owner, since swap records hold the swap secret
+fn write_private_file(file_path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // `mode` only applies on creation, so tighten records written by older versions too
+        if file_path.exists() {
+            std::fs::set_permissions(file_path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    options.open(file_path)?.write_all(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk::bitcoin::Network;
+    use bdk::bitcoin::secp256k1::Secp256k1;
+
+    fn test_record(funding_byte: u8) -> SwapRecord {
+        let secp = Secp256k1::new();
+        let recipient_secret = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let revocation_secret = SecretKey::from_slice(&[2u8; 32]).unwrap();
+        let swap_secret = SecretKey::from_slice(&[3u8; 32]).unwrap();
+        let swap_info = SwapInfo::new(
+            recipient_secret.public_key(&secp),
+            revocation_secret.public_key(&secp),
+            144,
+            50_000,
+        );
+        let contract_address = BitcoinAddress::p2wpkh(
+            &bdk::bitcoin::PublicKey::new(recipient_secret.public_key(&secp)),
+            Network::Regtest,
+        )
+        .unwrap();
+        SwapRecord::new(
+            swap_info,
+            &swap_secret,
+            "tr(descriptor)".to_string(),
+            contract_address,
+            Txid::from_inner([funding_byte; 32]),
+        )
+    }
+
+    #[test]
+    fn swap_info_validate_enforces_bounds() {
+        let valid = test_record(7).swap_info;
+        assert!(valid.validate().is_ok());
+
+        let dust = SwapInfo {
+            amount_satoshis: TAPROOT_DUST_LIMIT_SAT - 1,
+            ..valid.clone()
+        };
+        assert_eq!(
+            dust.validate(),
+            Err("Amount is below the 330 sat dust limit of a taproot output")
+        );
+        let at_dust_limit = SwapInfo {
+            amount_satoshis: TAPROOT_DUST_LIMIT_SAT,
+            ..valid.clone()
+        };
+        assert!(at_dust_limit.validate().is_ok());
+
+        let long_timelock = SwapInfo {
+            timelock_duration_blocks: 500_000,
+            ..valid.clone()
+        };
+        assert_eq!(
+            long_timelock.validate(),
+            Err("Timelock duration exceeds the maximum allowed number of blocks")
+        );
+        assert!(long_timelock.validate_with_max_timelock(500_000).is_ok());
+        let max_timelock = SwapInfo {
+            timelock_duration_blocks: DEFAULT_MAX_TIMELOCK_BLOCKS,
+            ..valid.clone()
+        };
+        assert!(max_timelock.validate().is_ok());
+
+        let same_keys = SwapInfo {
+            revocation_public_key: valid.recipient_public_key,
+            ..valid
+        };
+        assert_eq!(
+            same_keys.validate(),
+            Err("Recipient and revocation public keys must differ")
+        );
+    }
+
+    #[test]
+    fn swap_info_summary_abbreviates_keys() {
+        let swap_info = test_record(7).swap_info;
+        let recipient_hex = swap_info.recipient_public_key.to_string();
+        let revocation_hex = swap_info.revocation_public_key.to_string();
+        assert_eq!(
+            swap_info.summary(),
+            format!(
+                "0.0005 BTC to {}…, timelock 144 blocks, revocable by {}…",
+                &recipient_hex[..10],
+                &revocation_hex[..10]
+            )
+        );
+        assert_eq!(swap_info.summary(), swap_info.to_string());
+    }
+
+    #[test]
+    fn load_all_skips_invalid_files_and_the_latest_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut older = test_record(7);
+        older.creation_timestamp -= 60;
+        let newer = test_record(8);
+        newer.save_to_json(&newer.file_path(dir.path())).unwrap();
+        older.save_to_json(&older.file_path(dir.path())).unwrap();
+        newer
+            .save_to_json(&dir.path().join(LATEST_SWAP_RECORD_FILE))
+            .unwrap();
+        std::fs::write(dir.path().join("corrupt.json"), "{ not json").unwrap();
+        std::fs::write(dir.path().join("other.json"), r#"{"hello": "world"}"#).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let records = SwapRecord::load_all(dir.path()).unwrap();
+        let txids: Vec<_> = records
+            .iter()
+            .map(|(_, record)| record.funding_txid.clone())
+            .collect();
+        assert_eq!(txids, vec![older.funding_txid, newer.funding_txid]);
+    }
+
+    #[test]
+    fn swap_id_identifies_repeated_swaps() {
+        let swap_info = test_record(7).swap_info;
+        assert_eq!(swap_info.swap_id(), test_record(8).swap_info.swap_id());
+        let larger = SwapInfo {
+            amount_satoshis: swap_info.amount_satoshis + 1,
+            ..swap_info.clone()
+        };
+        assert_ne!(swap_info.swap_id(), larger.swap_id());
+        let longer = SwapInfo {
+            timelock_duration_blocks: swap_info.timelock_duration_blocks + 1,
+            ..swap_info.clone()
+        };
+        assert_ne!(swap_info.swap_id(), longer.swap_id());
+        assert_eq!(swap_info.secret_index(), test_record(8).swap_info.secret_index());
+        assert_ne!(swap_info.secret_index(), larger.secret_index());
+        assert!(swap_info.secret_index() < 1 << 31);
+    }
+
+    #[test]
+    fn swap_record_round_trips_through_its_txid_keyed_path() {
+        let secp = Secp256k1::new();
+        let recipient_secret = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let revocation_secret = SecretKey::from_slice(&[2u8; 32]).unwrap();
+        let swap_secret = SecretKey::from_slice(&[3u8; 32]).unwrap();
+        let swap_info = SwapInfo::new(
+            recipient_secret.public_key(&secp),
+            revocation_secret.public_key(&secp),
+            144,
+            50_000,
+        );
+        let contract_address = BitcoinAddress::p2wpkh(
+            &bdk::bitcoin::PublicKey::new(recipient_secret.public_key(&secp)),
+            Network::Regtest,
+        )
+        .unwrap();
+        let funding_txid = Txid::from_inner([7u8; 32]);
+        let record = SwapRecord::new(
+            swap_info,
+            &swap_secret,
+            "tr(descriptor)".to_string(),
+            contract_address,
+            funding_txid,
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let swaps_dir = dir.path().join("swaps");
+        let path = record.file_path(&swaps_dir);
+        assert_eq!(path, swaps_dir.join(format!("swap_{}.json", funding_txid)));
+
+        record.save_to_json(&path).unwrap();
+        let loaded = SwapRecord::load_from_json(&path).unwrap();
+        assert_eq!(loaded.swap_secret, hex::encode(swap_secret.secret_bytes()));
+        assert_eq!(loaded.funding_txid, funding_txid.to_string());
+        assert_eq!(loaded.swap_info.amount_satoshis, 50_000);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn multi_hop_secrets_round_trip_and_must_match_their_hashes() {
+        let mut record = test_record(7);
+        record.add_secret(SecretEntry::new(b"first hop", SecretRole::Initiator));
+        record.add_secret(SecretEntry::new(&[0xab; 32], SecretRole::Responder));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = record.file_path(dir.path());
+        record.save_to_json(&path).unwrap();
+        let loaded = SwapRecord::load_from_json(&path).unwrap();
+        assert_eq!(loaded.secrets, record.secrets);
+        assert_eq!(loaded.secrets[0].secret_hex, hex::encode(b"first hop"));
+
+        let mut tampered = record.clone();
+        tampered.secrets[1].secret_hex = hex::encode([0xcd; 32]);
+        assert_eq!(tampered.validate(), Err("Secret entry hash does not match its secret"));
+        assert!(tampered.save_to_json(&path).is_err());
+
+        // Records written before multi-hop support have no `secrets` key
+        let legacy = serde_json::to_value(test_record(7)).unwrap();
+        assert!(legacy.get("secrets").is_none());
+        let legacy: SwapRecord = serde_json::from_value(legacy).unwrap();
+        assert!(legacy.secrets.is_empty());
+    }
+
+    #[test]
+    fn offer_commits_to_the_secrets_without_revealing_them() {
+        let mut record = test_record(7);
+        record.add_secret(SecretEntry::new(b"first hop", SecretRole::Initiator));
+        // As `new_atomic_swap` saves it: the ID `swap` printed, then the escrow key
+        let printed_swap_id = record.swap_info.swap_id();
+        let swap_public_key = SecretKey::from_slice(&[3u8; 32]).unwrap().public_key(&Secp256k1::new());
+        record.swap_info.recipient_public_key = KeyAggregation::Musig
+            .public_key(&swap_public_key, &record.swap_info.recipient_public_key)
+            .unwrap();
+        record.swap_id = Some(printed_swap_id.clone());
+
+        let offer = record.to_offer().unwrap();
+        assert_eq!(offer.swap_id, Some(printed_swap_id));
+        assert_ne!(offer.swap_id, Some(record.swap_info.swap_id()));
+        assert_eq!(offer.contract_address, record.contract_address);
+        assert_eq!(offer.amount_satoshis, 50_000);
+        assert_eq!(offer.timelock_duration_blocks, 144);
+        assert_eq!(offer.commitment, SecretCommitment::new(&[3u8; 32]));
+        assert_eq!(offer.hop_commitments[0].sha256, record.secrets[0].sha256_hash);
+        assert_eq!(
+            offer.hop_commitments[0].keccak256,
+            format!("0x{}", hex::encode(keccak256(b"first hop")))
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("offer.json");
+        offer.save_to_json(&path).unwrap();
+        let shared = std::fs::read_to_string(&path).unwrap();
+        assert!(!shared.contains(&record.swap_secret));
+        assert!(!shared.contains(&record.secrets[0].secret_hex));
+        assert_eq!(SwapOffer::load_from_json(&path).unwrap(), offer);
+
+        let mut broken = record.clone();
+        broken.swap_secret = "not hex".to_string();
+        assert_eq!(broken.to_offer(), Err("Swap secret is not valid hex"));
+    }
+
+    #[test]
+    fn records_commit_to_the_keccak256_of_their_secret() {
+        let record = test_record(7);
+        assert_eq!(
+            record.secret_hash.as_deref(),
+            Some(hex::encode(keccak256(&[3u8; 32])).as_str())
+        );
+
+        // The checked-in record is what the prover reads
+        let checked_in = SwapRecord::load_from_json(Path::new("swaps/swap_bitcoin.json")).unwrap();
+        let secret = hex::decode(&checked_in.swap_secret).unwrap();
+        assert_eq!(checked_in.secret_hash, Some(hex::encode(keccak256(&secret))));
+
+        // Records written before `secret_hash` was stored get it on load
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("legacy.json");
+        let mut legacy = serde_json::to_value(&record).unwrap();
+        legacy.as_object_mut().unwrap().remove("secret_hash");
+        std::fs::write(&path, legacy.to_string()).unwrap();
+        assert_eq!(SwapRecord::load_from_json(&path).unwrap().secret_hash, record.secret_hash);
+
+        let mut tampered = record.clone();
+        tampered.secret_hash = Some("00".repeat(32));
+        assert_eq!(tampered.validate(), Err("Secret hash does not match the swap secret"));
+    }
+}
@@ -4,24 +4,36 @@
 //! specifically Hash Time Locked Contracts for atomic swaps and payment channels.
 
 use bdk::bitcoin::Address as BitcoinAddress;
-use bdk::bitcoin::secp256k1::{self, Scalar, Secp256k1, SecretKey};
-use bdk::bitcoin::{Network, PrivateKey, Txid};
-use bdk::blockchain::{Blockchain, RpcBlockchain};
-use bdk::database::MemoryDatabase;
+use bdk::bitcoin::secp256k1::{self, PublicKey, Secp256k1, SecretKey};
+use bdk::bitcoin::util::psbt::PartiallySignedTransaction;
+use bdk::bitcoin::{
+    Network, OutPoint, PackedLockTime, PrivateKey, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+};
+use bdk::blockchain::GetHeight;
+use bdk::database::{BatchDatabase, Database, MemoryDatabase};
 use bdk::descriptor::IntoWalletDescriptor;
 use bdk::miniscript::Descriptor;
 use bdk::miniscript::descriptor::TapTree;
 use bdk::miniscript::policy::Concrete;
+use bdk::signer::{SignerContext, SignerOrdering, SignerWrapper};
 use bdk::wallet::AddressIndex;
-use bdk::{FeeRate, KeychainKind, SignOptions, SyncOptions, Wallet, bitcoin};
+use bdk::{FeeRate, KeychainKind, LocalUtxo, SignOptions, SyncOptions, Wallet, bitcoin};
 use eyre::{Context, Result, eyre};
-use rand::rngs::ThreadRng;
+use serde::Serialize;
 use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use std::{str::FromStr, sync::Arc};
 
-use crate::constants::DEFAULT_FEE_RATE_SAT_PER_VB;
+use crate::blockchain::Backend;
+use crate::constants::{LATEST_SWAP_RECORD_FILE, SWAPS_DIR};
+use crate::contract::SCHNORR_SIGNATURE_LEN;
+use crate::error::FundingError;
+use crate::escrow::KeyAggregation;
 use crate::primitives::{SwapInfo, SwapRecord};
-use crate::transaction::TransactionUtils;
+use crate::transaction::{
+    SendOptions, TransactionUtils, amount_paid_to, ensure_inputs_cover, is_dry_run, publish_transaction,
+};
 
 /// Creates a secp256k1 context for cryptographic operations
 fn create_secp_context() -> Secp256k1<secp256k1::All> {
@@ -41,7 +53,7 @@ fn create_contract_wallet(descriptor: &str, network: Network) -> Result<Wallet<M
 /// Syncs a wallet with the blockchain and returns the wallet reference
 fn sync_wallet_with_blockchain(
     wallet: &Wallet<MemoryDatabase>,
-    blockchain_client: &RpcBlockchain,
+    blockchain_client: &Backend,
 ) -> Result<()> {
     wallet
         .sync(blockchain_client, SyncOptions::default())
@@ -52,9 +64,10 @@ fn sync_wallet_with_blockchain(
 /// Signs and finalizes a PSBT, returning an error if signing fails
 fn sign_and_finalize_transaction(
     wallet: &Wallet<MemoryDatabase>,
-    psbt: &mut bdk::bitcoin::util::psbt::PartiallySignedTransaction,
+    psbt: &mut PartiallySignedTransaction,
+    sign_options: SignOptions,
 ) -> Result<()> {
-    let is_finalized = wallet.sign(psbt, SignOptions::default())?;
+    let is_finalized = wallet.sign(psbt, sign_options)?;
 
     if !is_finalized {
         return Err(eyre!("Failed to sign and finalize transaction"));
@@ -63,15 +76,70 @@ fn sign_and_finalize_transaction(
     Ok(())
 }
 
-/// Broadcasts a transaction and returns the transaction ID
+/// Broadcasts a transaction (or prints it under `--dry-run`) and returns the transaction ID
 fn broadcast_transaction(
-    blockchain_client: &RpcBlockchain,
+    blockchain_client: &Backend,
     signed_transaction: bdk::bitcoin::Transaction,
+    fee_sat: Option<u64>,
 ) -> Result<Txid> {
-    blockchain_client.broadcast(&signed_transaction)?;
+    publish_transaction(blockchain_client, &signed_transaction, fee_sat)?;
     Ok(signed_transaction.txid())
 }
 
+/// A funded taproot HTLC: everything needed to later withdraw from or monitor it
+#[derive(Debug, Clone, Serialize)]
+pub struct HtlcCreation {
+    /// Funding transaction ID
+    pub txid: Txid,
+    pub contract_address: BitcoinAddress,
+    /// Public taproot descriptor of the contract
+    pub descriptor: String,
+    /// Fee of the funding transaction in satoshis
+    pub fee_sat: u64,
+}
+
+/// What funding a contract costs the sender, known before anything is broadcast
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FundingQuote {
+    /// Satoshis locked in the contract
+    pub amount_sat: u64,
+    /// Fee of the funding transaction
+    pub fee_sat: u64,
+}
+
+impl FundingQuote {
+    /// Everything that leaves the sender's wallet
+    pub fn total_sat(&self) -> u64 {
+        self.amount_sat + self.fee_sat
+    }
+}
+
+/// The public taproot descriptor of the HTLC for `swap_info`, with checksum
+///
+/// The internal key is `swap_info.recipient_public_key` (the aggregated escrow
+/// key for swaps) and the single leaf lets the revocation key spend after the
+/// relative timelock. Creation, withdrawal, refund and watchers all derive the
+/// contract from this one string.
+pub fn build_htlc_descriptor(swap_info: &SwapInfo, network: Network) -> Result<String> {
+    // Build taproot policy: revocation key can spend after the timelock
+    let policy_script = format!(
+        "and(older({}),pk({}))",
+        swap_info.timelock_duration_blocks, swap_info.revocation_public_key
+    );
+
+    let compiled_policy = Concrete::<String>::from_str(&policy_script)?
+        .compile()
+        .wrap_err("Failed to compile taproot policy")?;
+
+    let tap_tree = TapTree::Leaf(Arc::new(compiled_policy));
+
+    let descriptor = Descriptor::new_tr(swap_info.recipient_public_key.to_string(), Some(tap_tree))?
+        .to_string()
+        .into_wallet_descriptor(&create_secp_context(), network)?
+        .0;
+    Ok(descriptor.to_string())
+}
+
 /// Creates a taproot-based Hash Time Locked Contract (HTLC)
 ///
 /// This function creates a taproot contract with two spending conditions:
@@ -82,62 +150,196 @@ fn broadcast_transaction(
 /// * `blockchain_client` - RPC client for blockchain operations
 /// * `sender_wallet` - Wallet that will fund the contract
 /// * `swap_info` - Complete swap information including keys, timelock, and amount
+/// * `fee_rate` - Fee rate for the funding transaction
+/// * `approve` - Sees the cost of the built funding transaction and can cancel
+///   it with an error before it is signed and broadcast
 ///
 /// # Returns
-/// Tuple containing (Transaction ID, descriptor string, contract address)
-pub async fn create_taproot_htlc_contract(
-    blockchain_client: &RpcBlockchain,
-    sender_wallet: &Wallet<MemoryDatabase>,
+/// Funding transaction ID, contract address, descriptor and fee
+pub async fn create_taproot_htlc_contract<D: BatchDatabase>(
+    blockchain_client: &Backend,
+    sender_wallet: &Wallet<D>,
     swap_info: &SwapInfo,
-) -> Result<(Txid, String, BitcoinAddress)> {
+    fee_rate: FeeRate,
+    approve: impl FnOnce(&FundingQuote) -> Result<()>,
+) -> Result<HtlcCreation> {
     // Validate swap info before proceeding
     swap_info
         .validate()
         .map_err(|e| eyre!("Invalid swap info: {}", e))?;
 
-    let secp_context = create_secp_context();
-
-    // Build taproot policy: recipient can spend after timelock OR revocation key can spend
-    let policy_script = format!(
-        "and(older({}),pk({}))",
-        swap_info.timelock_duration_blocks, swap_info.revocation_public_key
-    );
-
-    let compiled_policy = Concrete::<String>::from_str(&policy_script)?
-        .compile()
-        .wrap_err("Failed to compile taproot policy")?;
-
-    let tap_tree = TapTree::Leaf(Arc::new(compiled_policy));
-
-    // Generate taproot descriptor
-    let taproot_descriptor_string =
-        Descriptor::new_tr(swap_info.recipient_public_key.to_string(), Some(tap_tree))?
-            .to_string()
-            .into_wallet_descriptor(&secp_context, sender_wallet.network())?
-            .0;
+    let taproot_descriptor_string = build_htlc_descriptor(swap_info, sender_wallet.network())?;
 
     // Create contract wallet
-    let contract_wallet = create_contract_wallet(
-        &taproot_descriptor_string.to_string(),
-        sender_wallet.network(),
-    )?;
+    let contract_wallet = create_contract_wallet(&taproot_descriptor_string, sender_wallet.network())?;
 
     let contract_address = contract_wallet.get_address(AddressIndex::New)?.address;
 
+    let funding_error = || {
+        format!(
+            "Failed to send {} satoshis to HTLC contract address {}",
+            swap_info.amount_satoshis, contract_address
+        )
+    };
+
     // Fund the contract
-    let funding_transaction_id = TransactionUtils::create_and_broadcast(
+    let (funding_psbt, funding_details) = TransactionUtils::build_unsigned_psbt(
         blockchain_client,
         sender_wallet,
         contract_address.clone(),
-        swap_info.amount_satoshis,
+        Some(swap_info.amount_satoshis),
+        fee_rate,
+        &SendOptions::default(),
     )
-    .await
-    .wrap_err(format!(
-        "Failed to send {} satoshis to HTLC contract address {}",
-        swap_info.amount_satoshis, contract_address
-    ))?;
+    .wrap_err_with(funding_error)?;
+    approve(&FundingQuote {
+        amount_sat: swap_info.amount_satoshis,
+        fee_sat: funding_details.fee.unwrap_or_default(),
+    })?;
+    let funding = TransactionUtils::sign_and_broadcast(blockchain_client, sender_wallet, funding_psbt, funding_details)
+        .wrap_err_with(funding_error)?;
+
+    Ok(HtlcCreation {
+        txid: funding.txid,
+        contract_address,
+        descriptor: taproot_descriptor_string,
+        fee_sat: funding.fee_sat,
+    })
+}
+
+/// Syncs the contract wallet until it holds a spendable output
+///
+/// Returns the unconfirmed outpoints the withdrawal must leave alone, which is
+/// empty when `include_unconfirmed` is set.
+async fn wait_for_contract_funding(
+    contract_wallet: &Wallet<MemoryDatabase>,
+    blockchain_client: &Backend,
+    options: WithdrawalOptions,
+) -> Result<Vec<OutPoint>> {
+    let WithdrawalOptions {
+        poll: funding_poll,
+        include_unconfirmed,
+        ..
+    } = options;
+    let deadline = funding_poll.map(|poll| Instant::now() + poll.timeout);
+
+    loop {
+        sync_wallet_with_blockchain(contract_wallet, blockchain_client)?;
+
+        let balance = contract_wallet.get_balance()?;
+        let pending_sat = balance.trusted_pending + balance.untrusted_pending;
+        log::debug!(
+            "Withdrawal wallet balance: {} sats confirmed, {} sats pending",
+            balance.confirmed,
+            pending_sat
+        );
+
+        if balance.confirmed > 0 || (include_unconfirmed && pending_sat > 0) {
+            return unconfirmed_contract_outpoints(contract_wallet, include_unconfirmed);
+        }
+
+        let delay = funding_poll
+            .zip(deadline)
+            .and_then(|(poll, deadline)| next_poll_delay(poll.interval, deadline, Instant::now()));
+        match delay {
+            Some(delay) => {
+                log::debug!("Contract not spendable yet, re-syncing in {:?}", delay);
+                tokio::time::sleep(delay).await;
+            }
+            _ if pending_sat > 0 => {
+                return Err(FundingError::NotYetConfirmed(format!(
+                    "{} sats are waiting for confirmation",
+                    pending_sat
+                ))
+                .into());
+            }
+            _ => {
+                return Err(FundingError::NeverFunded(
+                    "no transaction paying the contract address was found".to_string(),
+                )
+                .into());
+            }
+        }
+    }
+}
+
+/// How long to sleep before the next funding check, or `None` once `deadline` has passed
+///
+/// The last sleep is cut short to end at `deadline`, so a timeout shorter than
+/// `interval` still gets a final check instead of giving up after the first.
+fn next_poll_delay(interval: Duration, deadline: Instant, now: Instant) -> Option<Duration> {
+    let remaining = deadline.checked_duration_since(now).filter(|remaining| !remaining.is_zero())?;
+    Some(interval.min(remaining))
+}
+
+/// Outpoints of unspent contract outputs whose transaction is still unconfirmed
+fn unconfirmed_contract_outpoints(
+    contract_wallet: &Wallet<MemoryDatabase>,
+    include_unconfirmed: bool,
+) -> Result<Vec<OutPoint>> {
+    if include_unconfirmed {
+        return Ok(Vec::new());
+    }
+
+    let unconfirmed_txids: Vec<Txid> = contract_wallet
+        .list_transactions(false)?
+        .into_iter()
+        .filter(|details| details.confirmation_time.is_none())
+        .map(|details| details.txid)
+        .collect();
+
+    Ok(contract_wallet
+        .list_unspent()?
+        .into_iter()
+        .map(|utxo| utxo.outpoint)
+        .filter(|outpoint| unconfirmed_txids.contains(&outpoint.txid))
+        .collect())
+}
+
+/// Outcome of a successful taproot HTLC withdrawal
+#[derive(Debug, Clone, Serialize)]
+pub struct WithdrawalResult {
+    pub txid: Txid,
+    /// Amount paid to the destination in satoshis
+    pub amount_sat: u64,
+    /// Fee paid in satoshis
+    pub fee_sat: u64,
+    pub destination_address: BitcoinAddress,
+    /// Contract outputs spent by the withdrawal
+    pub input_outpoints: Vec<OutPoint>,
+}
+
+/// How long to keep re-syncing while the contract output is missing
+#[derive(Debug, Clone, Copy)]
+pub struct FundingPoll {
+    /// Delay between re-syncs
+    pub interval: Duration,
+    /// Total time to wait before giving up
+    pub timeout: Duration,
+}
+
+/// Which taproot path a withdrawal spends the contract through
+#[derive(Debug, Clone, Copy)]
+pub enum SpendPath {
+    /// Sign for the escrow internal key: the smallest spend, indistinguishable from a plain payment
+    KeyPath,
+    /// Satisfy the revocation leaf, revealing its script; needs the revocation key and a matured timelock
+    ScriptPath { revocation_secret_key: SecretKey },
+}
 
-    Ok((funding_transaction_id, taproot_descriptor_string.to_string(), contract_address))
+/// Fee and funding-wait settings for a taproot HTLC withdrawal
+#[derive(Debug, Clone, Copy)]
+pub struct WithdrawalOptions {
+    /// Fee rate for the withdrawal transaction
+    pub fee_rate: FeeRate,
+    /// Taproot path the contract output is spent through
+    pub spend_path: SpendPath,
+    /// Keep re-syncing until the contract is spendable instead of failing at once
+    pub poll: Option<FundingPoll>,
+    /// Also spend contract outputs that are not yet confirmed
+    pub include_unconfirmed: bool,
+    /// How the escrow key was aggregated, as recorded in the swap's `SwapRecord`
+    pub key_aggregation: KeyAggregation,
 }
 
 /// Withdraws funds from a taproot-based Hash Time Locked Contract (HTLC)
@@ -151,94 +353,337 @@ pub async fn create_taproot_htlc_contract(
 /// * `swap_info` - Swap information including public keys, timelock, and amount
 /// * `recipient_secret_key` - Secret key of the recipient
 /// * `swap_secret_key` - Secret key for the atomic swap mechanism
+/// * `options` - Fee rate, spend path, and whether to wait for and accept unconfirmed contract funding
+///
+/// # Errors
+/// A `FundingError` when the contract has nothing spendable: `NeverFunded` if no
+/// funding transaction was seen at all, `NotYetConfirmed` if it is still in the mempool.
 ///
 /// # Returns
-/// Transaction ID of the withdrawal transaction
-pub async fn withdraw_from_taproot_htlc(
-    blockchain_client: &RpcBlockchain,
-    sender_wallet: &Wallet<MemoryDatabase>,
+/// Transaction ID, amounts, destination and spent outpoints of the withdrawal
+pub async fn withdraw_from_taproot_htlc<D: BatchDatabase>(
+    blockchain_client: &Backend,
+    sender_wallet: &Wallet<D>,
     destination_address: BitcoinAddress,
     swap_info: &SwapInfo,
     recipient_secret_key: &SecretKey,
     swap_secret_key: &SecretKey,
-) -> Result<Txid> {
+    options: WithdrawalOptions,
+) -> Result<WithdrawalResult> {
     // Validate swap info before proceeding
     swap_info
         .validate()
         .map_err(|e| eyre!("Invalid swap info: {}", e))?;
 
-    // Combine secret keys for escrow - this gives us the private key for the taproot internal key
-    let combined_secret_key = swap_secret_key
-        .add_tweak(&Scalar::from_be_bytes(recipient_secret_key.secret_bytes())?)
-        .map_err(|_| eyre!("Failed to combine secret keys for escrow"))?;
+    let (combined_secret_key, escrow_swap_info) =
+        escrow_key(swap_info, recipient_secret_key, swap_secret_key, options.key_aggregation)?;
+    let (signing_key, is_internal_key) = match options.spend_path {
+        SpendPath::KeyPath => (combined_secret_key, true),
+        SpendPath::ScriptPath { revocation_secret_key } => (revocation_secret_key, false),
+    };
 
-    let escrow_private_key = PrivateKey::new(combined_secret_key, sender_wallet.network());
+    // Create and sync withdrawal wallet
+    let withdrawal_wallet = contract_spending_wallet(
+        &escrow_swap_info,
+        signing_key,
+        is_internal_key,
+        sender_wallet.network(),
+    )?;
 
+    let unconfirmed_outpoints =
+        wait_for_contract_funding(&withdrawal_wallet, blockchain_client, options).await?;
 
-    // Generate the same taproot descriptor as in create function
-     let revocation_pubkey = bitcoin::PublicKey::new(swap_info.revocation_public_key);
-     let taproot_descriptor = bdk::descriptor!(tr(
-            escrow_private_key,
-            and_v(v:pk(revocation_pubkey), older(swap_info.timelock_duration_blocks))
-        ))?;
-    
-    // Create and sync withdrawal wallet
-    let withdrawal_wallet = Wallet::new(
-        taproot_descriptor,
+    let (withdrawal_psbt, transaction_details) = build_signed_withdrawal(
+        &withdrawal_wallet,
+        &destination_address,
+        options,
+        unconfirmed_outpoints,
+    )?;
+
+    // Extract and broadcast the signed transaction
+    let signed_withdrawal_transaction = withdrawal_psbt.extract_tx();
+    let input_outpoints = signed_withdrawal_transaction
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .collect();
+    let amount_sat = amount_paid_to(&signed_withdrawal_transaction, &destination_address.script_pubkey());
+    let withdrawal_transaction_id =
+        broadcast_transaction(blockchain_client, signed_withdrawal_transaction, transaction_details.fee)?;
+
+    let withdrawal = WithdrawalResult {
+        txid: withdrawal_transaction_id,
+        amount_sat,
+        fee_sat: transaction_details.fee.unwrap_or_default(),
+        destination_address,
+        input_outpoints,
+    };
+    log::debug!("Withdrawal result: {:?}", withdrawal);
+
+    Ok(withdrawal)
+}
+
+/// The escrow secret key of a swap and its `SwapInfo` with the escrow key as internal key
+///
+/// `swap_info` carries the recipient's own public key; the contract's internal
+/// key is the escrow key aggregated from it and the swap key with `key_aggregation`.
+fn escrow_key(
+    swap_info: &SwapInfo,
+    recipient_secret_key: &SecretKey,
+    swap_secret_key: &SecretKey,
+    key_aggregation: KeyAggregation,
+) -> Result<(SecretKey, SwapInfo)> {
+    let escrow_secret_key = key_aggregation.secret_key(swap_secret_key, recipient_secret_key)?;
+    let escrow_swap_info = SwapInfo {
+        recipient_public_key: escrow_secret_key.public_key(&create_secp_context()),
+        ..swap_info.clone()
+    };
+    Ok((escrow_secret_key, escrow_swap_info))
+}
+
+/// A wallet over the contract descriptor that signs with `signing_key`
+///
+/// The key is either the escrow internal key, for a key path spend, or the
+/// revocation key of the script leaf.
+fn contract_spending_wallet(
+    swap_info: &SwapInfo,
+    signing_key: SecretKey,
+    is_internal_key: bool,
+    network: Network,
+) -> Result<Wallet<MemoryDatabase>> {
+    let descriptor = build_htlc_descriptor(swap_info, network)?;
+    let mut wallet = create_contract_wallet(&descriptor, network)?;
+    add_contract_signer(&mut wallet, signing_key, is_internal_key);
+    Ok(wallet)
+}
+
+fn add_contract_signer(wallet: &mut Wallet<MemoryDatabase>, signing_key: SecretKey, is_internal_key: bool) {
+    wallet.add_signer(
+        KeychainKind::External,
+        SignerOrdering::default(),
+        Arc::new(SignerWrapper::new(
+            PrivateKey::new(signing_key, wallet.network()),
+            SignerContext::Tap { is_internal_key },
+        )),
+    );
+}
+
+/// Build and sign a transaction draining the contract wallet to `destination_address`
+///
+/// The script path selects the revocation leaf through bdk's policy path,
+/// which also sets the input's relative locktime, and signs without the
+/// internal key so the leaf is used. The key path needs no policy choice, so
+/// it is built by [`build_key_path_withdrawal`] instead of bdk's builder,
+/// which demands one for any descriptor with a timelocked leaf.
+fn build_signed_withdrawal(
+    withdrawal_wallet: &Wallet<MemoryDatabase>,
+    destination_address: &BitcoinAddress,
+    options: WithdrawalOptions,
+    unspendable: Vec<OutPoint>,
+) -> Result<(PartiallySignedTransaction, bdk::TransactionDetails)> {
+    let spendable_utxos: Vec<_> = withdrawal_wallet
+        .list_unspent()?
+        .into_iter()
+        .filter(|utxo| !unspendable.contains(&utxo.outpoint))
+        .collect();
+    ensure_inputs_cover(
+        withdrawal_wallet,
+        &spendable_utxos,
+        &destination_address.script_pubkey(),
         None,
-        sender_wallet.network(),
-        MemoryDatabase::new(),
+        &[],
+        options.fee_rate,
     )?;
 
-    sync_wallet_with_blockchain(&withdrawal_wallet, blockchain_client)?;
+    let (mut withdrawal_psbt, transaction_details) = match options.spend_path {
+        SpendPath::KeyPath => build_key_path_withdrawal(
+            withdrawal_wallet,
+            spendable_utxos,
+            destination_address,
+            options.fee_rate,
+        )?,
+        SpendPath::ScriptPath { .. } => {
+            let wallet_policy = withdrawal_wallet
+                .policies(KeychainKind::External)?
+                .ok_or_else(|| eyre!("No spending policy found for withdrawal wallet"))?;
+            log::debug!("Available wallet policy: {:?}", wallet_policy);
 
-    // Debug: Check if withdrawal wallet has any UTXOs
-    let withdrawal_balance = withdrawal_wallet.get_balance()?;
-    println!("🔍 Withdrawal wallet balance: {} sats", withdrawal_balance.confirmed);
-    
-    if withdrawal_balance.confirmed == 0 {
-        return Err(eyre!("Withdrawal wallet has no confirmed balance. Expected UTXO might not be found."));
-    }
+            // The taproot policy lists the internal key first, then the script leaf
+            let spending_policy_path = BTreeMap::from([(wallet_policy.id, vec![1])]);
+            log::debug!("Using spending policy path: {:?}", spending_policy_path);
 
-    // Set up policy path for script spending
-    let wallet_policy = withdrawal_wallet
-        .policies(KeychainKind::External)?
-        .ok_or_else(|| eyre!("No spending policy found for withdrawal wallet"))?;
+            let mut transaction_builder = withdrawal_wallet.build_tx();
+            transaction_builder
+                .fee_rate(options.fee_rate)
+                // A relative timelock's CSV sequence still takes precedence
+                .enable_rbf()
+                .drain_wallet()
+                .drain_to(destination_address.script_pubkey())
+                .unspendable(unspendable)
+                .policy_path(spending_policy_path, KeychainKind::External);
 
-    println!("🔍 Available wallet policy: {:?}", wallet_policy);
+            transaction_builder
+                .finish()
+                .wrap_err("Failed to build withdrawal transaction")?
+        }
+    };
 
-    let mut spending_policy_path = BTreeMap::new();
-    // Use the first item (key path spend) - recipient can spend with combined secret
-    // This allows immediate claiming by the recipient who has both secrets
-    spending_policy_path.insert(wallet_policy.id, vec![0]);
-    
-    println!("🔍 Using spending policy path: {:?}", spending_policy_path);
+    // A relative timelock counts the block the spend is mined in, one past the synced tip
+    let next_block_height = withdrawal_wallet
+        .database()
+        .get_sync_time()?
+        .map(|sync_time| sync_time.block_time.height + 1);
+    let sign_options = SignOptions {
+        sign_with_tap_internal_key: matches!(options.spend_path, SpendPath::KeyPath),
+        assume_height: next_block_height,
+        ..SignOptions::default()
+    };
+    sign_and_finalize_transaction(withdrawal_wallet, &mut withdrawal_psbt, sign_options)?;
 
-  
+    Ok((withdrawal_psbt, transaction_details))
+}
 
-    let (mut withdrawal_psbt, _transaction_details) = {
-        let mut transaction_builder = withdrawal_wallet.build_tx();
+/// Build an unsigned key path spend of `utxos` to `destination_address`
+///
+/// Each input is signalled for RBF and later satisfied by a lone Schnorr
+/// signature, so the fee is sized for that witness at `fee_rate`.
+fn build_key_path_withdrawal(
+    withdrawal_wallet: &Wallet<MemoryDatabase>,
+    utxos: Vec<LocalUtxo>,
+    destination_address: &BitcoinAddress,
+    fee_rate: FeeRate,
+) -> Result<(PartiallySignedTransaction, bdk::TransactionDetails)> {
+    let sent: u64 = utxos.iter().map(|utxo| utxo.txout.value).sum();
+    let mut withdrawal_transaction = Transaction {
+        version: 2,
+        lock_time: PackedLockTime::ZERO,
+        input: utxos
+            .iter()
+            .map(|utxo| TxIn {
+                previous_output: utxo.outpoint,
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::from_vec(vec![vec![0; SCHNORR_SIGNATURE_LEN]]),
+                ..TxIn::default()
+            })
+            .collect(),
+        output: vec![TxOut {
+            value: sent,
+            script_pubkey: destination_address.script_pubkey(),
+        }],
+    };
+    let fee = fee_rate.fee_wu(withdrawal_transaction.weight());
+    withdrawal_transaction.output[0].value = sent - fee;
+    for input in &mut withdrawal_transaction.input {
+        input.witness = Witness::new();
+    }
 
-        transaction_builder
-            .fee_rate(FeeRate::from_sat_per_vb(DEFAULT_FEE_RATE_SAT_PER_VB))
-            .drain_wallet()
-            .drain_to(destination_address.script_pubkey())
-            .policy_path(spending_policy_path, KeychainKind::External);
+    let mut withdrawal_psbt = PartiallySignedTransaction::from_unsigned_tx(withdrawal_transaction.clone())
+        .wrap_err("Failed to build withdrawal transaction")?;
+    for (psbt_input, utxo) in withdrawal_psbt.inputs.iter_mut().zip(utxos) {
+        let txout = utxo.txout.clone();
+        *psbt_input = withdrawal_wallet.get_psbt_input(utxo, None, true)?;
+        psbt_input.witness_utxo.get_or_insert(txout);
+    }
 
-        transaction_builder
-            .finish()
-            .wrap_err("Failed to build withdrawal transaction")?
+    let transaction_details = bdk::TransactionDetails {
+        txid: withdrawal_transaction.txid(),
+        transaction: Some(withdrawal_transaction),
+        received: 0,
+        sent,
+        fee: Some(fee),
+        confirmation_time: None,
     };
+    Ok((withdrawal_psbt, transaction_details))
+}
 
-    // Sign and finalize the withdrawal transaction
-    sign_and_finalize_transaction(&withdrawal_wallet, &mut withdrawal_psbt)?;
+/// Blocks still to be mined before an output confirmed at `confirmation_height`
+/// can be spent through a `timelock_blocks` relative timelock
+///
+/// Zero once the next block, at `tip_height + 1`, may include the spend.
+pub fn blocks_until_refundable(confirmation_height: u32, timelock_blocks: u32, tip_height: u32) -> u32 {
+    confirmation_height
+        .saturating_add(timelock_blocks)
+        .saturating_sub(tip_height + 1)
+}
 
-    // Extract and broadcast the signed transaction
-    let signed_withdrawal_transaction = withdrawal_psbt.extract_tx();
-    let withdrawal_transaction_id =
-        broadcast_transaction(blockchain_client, signed_withdrawal_transaction)?;
+/// Reclaims a taproot HTLC through its revocation timelock leaf
+///
+/// Rebuilds the contract descriptor from `swap_info` and signs its leaf with
+/// the revocation key, so the sender never needs the escrow key, and sends every
+/// matured contract output back to a fresh `sender_wallet` address at
+/// `fee_rate`.
+///
+/// # Errors
+/// A `FundingError`: `NeverFunded` or `NotYetConfirmed` as for withdrawals, and
+/// `TimelockNotMatured` with the number of blocks left when no contract output
+/// is old enough to spend yet.
+pub async fn refund_taproot_htlc<D: BatchDatabase>(
+    blockchain_client: &Backend,
+    sender_wallet: &Wallet<D>,
+    swap_info: &SwapInfo,
+    revocation_secret_key: &SecretKey,
+    fee_rate: FeeRate,
+) -> Result<Txid> {
+    swap_info
+        .validate()
+        .map_err(|e| eyre!("Invalid swap info: {}", e))?;
+
+    let refund_wallet = contract_spending_wallet(
+        swap_info,
+        *revocation_secret_key,
+        false,
+        sender_wallet.network(),
+    )?;
+
+    let options = WithdrawalOptions {
+        fee_rate,
+        spend_path: SpendPath::ScriptPath {
+            revocation_secret_key: *revocation_secret_key,
+        },
+        poll: None,
+        include_unconfirmed: false,
+        // Unused: the refund leaf is signed with the revocation key, not the escrow key
+        key_aggregation: KeyAggregation::Musig,
+    };
+    let mut unspendable = wait_for_contract_funding(&refund_wallet, blockchain_client, options).await?;
+
+    let tip_height = blockchain_client.get_height()?;
+    let confirmation_heights: BTreeMap<Txid, u32> = refund_wallet
+        .list_transactions(false)?
+        .into_iter()
+        .filter_map(|details| Some((details.txid, details.confirmation_time?.height)))
+        .collect();
+    let contract_utxos = refund_wallet.list_unspent()?;
+    let mut blocks_left = Vec::new();
+    for utxo in &contract_utxos {
+        let Some(&confirmation_height) = confirmation_heights.get(&utxo.outpoint.txid) else {
+            continue;
+        };
+        let remaining = blocks_until_refundable(
+            confirmation_height,
+            swap_info.timelock_duration_blocks,
+            tip_height,
+        );
+        if remaining > 0 {
+            unspendable.push(utxo.outpoint);
+            blocks_left.push(remaining);
+        }
+    }
+    if let Some(fewest_blocks_left) = blocks_left.iter().min()
+        && blocks_left.len() == contract_utxos.len()
+    {
+        return Err(FundingError::TimelockNotMatured(format!(
+            "{} more block(s) needed after tip {} for the {}-block timelock",
+            fewest_blocks_left, tip_height, swap_info.timelock_duration_blocks
+        ))
+        .into());
+    }
+
+    let refund_address = sender_wallet.get_address(AddressIndex::New)?.address;
+    let (refund_psbt, transaction_details) =
+        build_signed_withdrawal(&refund_wallet, &refund_address, options, unspendable)?;
 
-    Ok(withdrawal_transaction_id)
+    broadcast_transaction(blockchain_client, refund_psbt.extract_tx(), transaction_details.fee)
 }
 
 /// Creates a new atomic swap using taproot-based Hash Time Locked Contract (HTLC)
@@ -250,50 +695,602 @@ pub async fn withdraw_from_taproot_htlc(
 /// * `blockchain_client` - RPC client for blockchain operations
 /// * `sender_wallet` - Wallet that will fund the atomic swap
 /// * `swap_info` - Swap information including public keys, timelock, and amount
+/// * `swap_secret` - Swap secret, random or derived with `derive_swap_secret`
+/// * `secret_index` - Derivation index of a derived `swap_secret`, kept in the record
+/// * `fee_rate` - Fee rate for the funding transaction
+/// * `approve` - Passed to `create_taproot_htlc_contract` to confirm the funding cost
 ///
 /// # Returns
-/// Transaction ID of the funding transaction for the atomic swap
-pub async fn new_atomic_swap(
-    blockchain_client: &RpcBlockchain,
-    sender_wallet: &Wallet<MemoryDatabase>,
+/// The funded contract and the path of the `SwapRecord` saved from it. Under
+/// `--dry-run` nothing is funded, so no record is saved and the path is `None`.
+pub async fn new_atomic_swap<D: BatchDatabase>(
+    blockchain_client: &Backend,
+    sender_wallet: &Wallet<D>,
     swap_info: &mut SwapInfo,
-    rng: &mut ThreadRng,
-) -> Result<Txid> {
-    let swap_secret = secp256k1::SecretKey::new(rng);
-    // Display the swap secret in a masked, beautiful format
-    let secret_bytes = swap_secret.secret_bytes();
-    let masked = format!(
-        "{:02x}e8*******86{:02x}",
-        secret_bytes[0], secret_bytes[31]
-    );
-    println!("| Swap k secret: [{}]", masked);
+    swap_secret: &SecretKey,
+    secret_index: Option<u32>,
+    fee_rate: FeeRate,
+    approve: impl FnOnce(&FundingQuote) -> Result<()>,
+) -> Result<(HtlcCreation, Option<PathBuf>)> {
+    let swap_id = lock_to_escrow_key(swap_info, swap_secret)?;
+
+    let creation = create_taproot_htlc_contract(blockchain_client, sender_wallet, swap_info, fee_rate, approve).await?;
+    if is_dry_run() {
+        return Ok((creation, None));
+    }
+
+    // A random swap secret exists nowhere else, so failing to persist it is an error
+    let swap_record = funded_swap_record(swap_id, swap_info, swap_secret, secret_index, &creation);
 
-    let secp_ctx = create_secp_context();
-    let swap_pubkey = swap_secret.public_key(&secp_ctx);
+    let swaps_dir = Path::new(SWAPS_DIR);
+    let record_path = swap_record.file_path(swaps_dir);
+    swap_record
+        .save_to_json(&record_path)
+        .map_err(|e| eyre!("Swap funded in {} but saving its record to {} failed: {}", creation.txid, record_path.display(), e))?;
 
-    let escrow_pubkey = swap_pubkey
-        .combine(&swap_info.recipient_public_key)
-        .expect("It's impossible to fail for 2 different public keys");
+    let latest_path = swaps_dir.join(LATEST_SWAP_RECORD_FILE);
+    if let Err(e) = swap_record.save_to_json(&latest_path) {
+        log::warn!("Failed to update {}: {}", latest_path.display(), e);
+    }
 
-    swap_info.recipient_public_key = escrow_pubkey;
+    Ok((creation, Some(record_path)))
+}
 
-    let (txid, descriptor_string, contract_address) = create_taproot_htlc_contract(blockchain_client, sender_wallet, swap_info).await?;
-    
-    // Create and save SwapRecord to JSON
-    let swap_record = SwapRecord::new(
+/// Replace the recipient key of `swap_info` with its MuSig escrow key with `swap_secret`
+///
+/// Returns the swap's ID, taken before the substitution: `swap` prints and
+/// looks up the ID hashed from the recipient's own key.
+fn lock_to_escrow_key(swap_info: &mut SwapInfo, swap_secret: &SecretKey) -> Result<String> {
+    let swap_id = swap_info.swap_id();
+    let swap_pubkey = swap_secret.public_key(&create_secp_context());
+    swap_info.recipient_public_key = KeyAggregation::Musig.public_key(&swap_pubkey, &swap_info.recipient_public_key)?;
+    Ok(swap_id)
+}
+
+/// The record [`new_atomic_swap`] saves for a funded swap
+fn funded_swap_record(
+    swap_id: String,
+    swap_info: &SwapInfo,
+    swap_secret: &SecretKey,
+    secret_index: Option<u32>,
+    creation: &HtlcCreation,
+) -> SwapRecord {
+    let mut swap_record = SwapRecord::new(
         swap_info.clone(),
-        &swap_secret,
-        descriptor_string,
-        contract_address,
-        txid,
+        swap_secret,
+        creation.descriptor.clone(),
+        creation.contract_address.clone(),
+        creation.txid,
     );
-    
-    let json_path = std::path::Path::new("swaps/swap_bitcoin.json");
-    if let Err(e) = swap_record.save_to_json(json_path) {
-        eprintln!("⚠️  Warning: Failed to save swap record to JSON: {}", e);
+    swap_record.secret_index = secret_index;
+    swap_record.swap_id = Some(swap_id);
+    swap_record
+}
+
+/// Rebuild the contract of a swap from its keys, checking it pays `contract_address`
+///
+/// The escrow key is aggregated from `swap_public_key` and
+/// `recipient_public_key` as [`new_atomic_swap`] does, so the returned
+/// `SwapInfo` matches the one a `SwapRecord` would hold. The amount is not
+/// part of the contract and is left at zero; see [`contract_balance`].
+pub fn rebuild_swap_info(
+    swap_public_key: &PublicKey,
+    recipient_public_key: &PublicKey,
+    revocation_public_key: &PublicKey,
+    timelock_blocks: u32,
+    contract_address: &BitcoinAddress,
+) -> Result<SwapInfo> {
+    let escrow_pubkey = KeyAggregation::Musig.public_key(swap_public_key, recipient_public_key)?;
+    let swap_info = SwapInfo::new(escrow_pubkey, *revocation_public_key, timelock_blocks, 0);
+
+    let descriptor = build_htlc_descriptor(&swap_info, contract_address.network)?;
+    let rebuilt_address = create_contract_wallet(&descriptor, contract_address.network)?
+        .get_address(AddressIndex::Peek(0))?
+        .address;
+    if rebuilt_address != *contract_address {
+        return Err(eyre!(
+            "The rebuilt contract pays {}, not {}; check the wallets, secret index and timelock",
+            rebuilt_address,
+            contract_address
+        ));
+    }
+    Ok(swap_info)
+}
+
+/// Satoshis currently held by the contract of `swap_info`, including unconfirmed outputs
+pub fn contract_balance(blockchain_client: &Backend, swap_info: &SwapInfo, network: Network) -> Result<u64> {
+    let descriptor = build_htlc_descriptor(swap_info, network)?;
+    let contract_wallet = create_contract_wallet(&descriptor, network)?;
+    sync_wallet_with_blockchain(&contract_wallet, blockchain_client)?;
+    Ok(contract_wallet.get_balance()?.get_total())
+}
+
+/// Which path spent a taproot swap contract
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapSpendPath {
+    /// Key path spend with the aggregated escrow key, i.e. the recipient claimed
+    Claimed,
+    /// Script path spend through the revocation timelock leaf, i.e. the sender refunded
+    Refunded,
+}
+
+/// On-chain state of a persisted swap
+#[derive(Debug, Clone)]
+pub struct SwapStatus {
+    /// Confirmations of the funding transaction, `None` if the node has not seen it
+    pub funding_confirmations: Option<u32>,
+    /// Contract output created by the funding transaction
+    pub contract_outpoint: Option<OutPoint>,
+    /// Spending transaction and path, if the contract output has been spent
+    pub spend: Option<(Txid, SwapSpendPath)>,
+}
+
+/// Classify a contract input by its witness shape
+///
+/// A taproot key path spend carries only a signature (plus an optional annex
+/// starting with `0x50`); a script path spend adds the leaf script and its
+/// control block. The swap's claim path reveals no hash preimage, so unlike the
+/// P2WSH `HTLCContract::extract_preimage` this is all there is to go on.
+pub fn classify_spend(witness: &bitcoin::Witness) -> SwapSpendPath {
+    let has_annex = witness.len() >= 2
+        && witness
+            .last()
+            .is_some_and(|last| last.first() == Some(&0x50));
+    let stack_items = witness.len() - usize::from(has_annex);
+    if stack_items <= 1 {
+        SwapSpendPath::Claimed
     } else {
-        println!("💾 Swap record saved to {}", json_path.display());
+        SwapSpendPath::Refunded
+    }
+}
+
+/// Look up the funding and spending state of a saved swap
+///
+/// Syncs a watch-only wallet built from the record's public descriptor, so no
+/// keys are needed.
+pub fn check_swap_status(blockchain_client: &Backend, record: &SwapRecord) -> Result<SwapStatus> {
+    let contract_address = BitcoinAddress::from_str(&record.contract_address)
+        .wrap_err_with(|| format!("Invalid contract address {}", record.contract_address))?;
+    let funding_txid = Txid::from_str(&record.funding_txid)
+        .wrap_err_with(|| format!("Invalid funding txid {}", record.funding_txid))?;
+
+    let contract_wallet = create_contract_wallet(&record.descriptor_string, contract_address.network)?;
+    sync_wallet_with_blockchain(&contract_wallet, blockchain_client)?;
+    let transactions = contract_wallet.list_transactions(true)?;
+
+    let Some(funding) = transactions.iter().find(|details| details.txid == funding_txid) else {
+        return Ok(SwapStatus {
+            funding_confirmations: None,
+            contract_outpoint: None,
+            spend: None,
+        });
+    };
+    let tip_height = blockchain_client.get_height()?;
+    let funding_confirmations = Some(match &funding.confirmation_time {
+        Some(block_time) => tip_height.saturating_sub(block_time.height) + 1,
+        None => 0,
+    });
+
+    let contract_script = contract_address.script_pubkey();
+    let contract_outpoint = funding.transaction.as_ref().and_then(|transaction| {
+        transaction
+            .output
+            .iter()
+            .position(|output| output.script_pubkey == contract_script)
+            .map(|vout| OutPoint::new(funding_txid, vout as u32))
+    });
+
+    let spend = contract_outpoint.and_then(|outpoint| {
+        transactions.iter().find_map(|details| {
+            let input = details
+                .transaction
+                .as_ref()?
+                .input
+                .iter()
+                .find(|input| input.previous_output == outpoint)?;
+            Some((details.txid, classify_spend(&input.witness)))
+        })
+    });
+
+    Ok(SwapStatus {
+        funding_confirmations,
+        contract_outpoint,
+        spend,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk::bitcoin::hashes::Hash;
+    use bdk::bitcoin::{PackedLockTime, Transaction, TxIn, TxOut, Witness};
+    use bdk::database::{BatchOperations, SyncTime};
+    use bdk::bitcoin::util::bip32::ExtendedPrivKey;
+    use bdk::{BlockTime, LocalUtxo, TransactionDetails};
+    use crate::escrow::aggregate_public_keys;
+    use crate::wallet::derive_swap_secret;
+
+    const CONTRACT_SAT: u64 = 100_000;
+    const TIMELOCK_BLOCKS: u32 = 10;
+
+    fn escrow_secret() -> SecretKey {
+        SecretKey::from_slice(&[1u8; 32]).unwrap()
+    }
+
+    fn revocation_secret() -> SecretKey {
+        SecretKey::from_slice(&[2u8; 32]).unwrap()
+    }
+
+    /// Swap info whose internal key is already the escrow key, as saved in a `SwapRecord`
+    fn test_swap_info() -> SwapInfo {
+        let secp = Secp256k1::new();
+        SwapInfo::new(
+            escrow_secret().public_key(&secp),
+            revocation_secret().public_key(&secp),
+            TIMELOCK_BLOCKS,
+            CONTRACT_SAT,
+        )
+    }
+
+    /// A withdrawal wallet holding one confirmed contract output, synced past its timelock
+    fn funded_withdrawal_wallet(spend_path: SpendPath) -> Wallet<MemoryDatabase> {
+        match spend_path {
+            SpendPath::KeyPath => funded_contract_wallet(&test_swap_info(), escrow_secret(), true),
+            SpendPath::ScriptPath { revocation_secret_key } => {
+                funded_contract_wallet(&test_swap_info(), revocation_secret_key, false)
+            }
+        }
+    }
+
+    /// A wallet over `swap_info`'s contract holding one confirmed output, signing with `signing_key`
+    fn funded_contract_wallet(
+        swap_info: &SwapInfo,
+        signing_key: SecretKey,
+        is_internal_key: bool,
+    ) -> Wallet<MemoryDatabase> {
+        let descriptor = build_htlc_descriptor(swap_info, Network::Regtest).unwrap();
+        let contract_script = create_contract_wallet(&descriptor, Network::Regtest)
+            .unwrap()
+            .get_address(AddressIndex::Peek(0))
+            .unwrap()
+            .script_pubkey();
+        let funding = Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            // A non-null previous output keeps this from looking like an immature coinbase
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 1),
+                ..TxIn::default()
+            }],
+            output: vec![TxOut {
+                value: CONTRACT_SAT,
+                script_pubkey: contract_script.clone(),
+            }],
+        };
+
+        let mut database = MemoryDatabase::new();
+        database.set_script_pubkey(&contract_script, KeychainKind::External, 0).unwrap();
+        database.set_last_index(KeychainKind::External, 0).unwrap();
+        database.set_raw_tx(&funding).unwrap();
+        database
+            .set_tx(&TransactionDetails {
+                transaction: Some(funding.clone()),
+                txid: funding.txid(),
+                received: CONTRACT_SAT,
+                sent: 0,
+                fee: None,
+                confirmation_time: Some(BlockTime { height: 100, timestamp: 0 }),
+            })
+            .unwrap();
+        database
+            .set_utxo(&LocalUtxo {
+                outpoint: OutPoint::new(funding.txid(), 0),
+                txout: funding.output[0].clone(),
+                keychain: KeychainKind::External,
+                is_spent: false,
+            })
+            .unwrap();
+        database
+            .set_sync_time(SyncTime {
+                block_time: BlockTime { height: 100 + TIMELOCK_BLOCKS, timestamp: 0 },
+            })
+            .unwrap();
+
+        let mut wallet = Wallet::new(descriptor.as_str(), None, Network::Regtest, database).unwrap();
+        add_contract_signer(&mut wallet, signing_key, is_internal_key);
+        wallet
+    }
+
+    fn signed_withdrawal(spend_path: SpendPath, fee_rate: FeeRate) -> (Transaction, TransactionDetails) {
+        let wallet = funded_withdrawal_wallet(spend_path);
+        let destination = wallet.get_address(AddressIndex::Peek(1)).unwrap().address;
+        let options = WithdrawalOptions {
+            fee_rate,
+            spend_path,
+            poll: None,
+            include_unconfirmed: false,
+            key_aggregation: KeyAggregation::Musig,
+        };
+        let (psbt, details) = build_signed_withdrawal(&wallet, &destination, options, Vec::new()).unwrap();
+        (psbt.extract_tx(), details)
+    }
+
+    fn withdrawal_witness_size(spend_path: SpendPath) -> usize {
+        let (transaction, _) = signed_withdrawal(spend_path, FeeRate::from_sat_per_vb(1.0));
+        transaction.input[0].witness.iter().map(<[u8]>::len).sum()
+    }
+
+    #[test]
+    fn withdrawal_pays_the_contract_less_a_fee_at_the_requested_rate() {
+        let (cheap, cheap_details) = signed_withdrawal(SpendPath::KeyPath, FeeRate::from_sat_per_vb(1.0));
+        let (fast, fast_details) = signed_withdrawal(SpendPath::KeyPath, FeeRate::from_sat_per_vb(25.0));
+
+        for (transaction, details) in [(&cheap, &cheap_details), (&fast, &fast_details)] {
+            assert_eq!(transaction.output.len(), 1);
+            assert_eq!(transaction.output[0].value + details.fee.unwrap(), CONTRACT_SAT);
+        }
+        // Key path spends are sized for their lone signature, so the fee fits the signed weight exactly
+        assert_eq!(cheap_details.fee.unwrap(), FeeRate::from_sat_per_vb(1.0).fee_wu(cheap.weight()));
+        assert_eq!(fast_details.fee.unwrap(), FeeRate::from_sat_per_vb(25.0).fee_wu(fast.weight()));
+        assert!(fast.output[0].value < cheap.output[0].value);
+
+        // Refunds spend the revocation leaf at the caller's rate too; bdk sizes
+        // that fee before signing, so it may overpay slightly but never underpay
+        let script_path = SpendPath::ScriptPath {
+            revocation_secret_key: revocation_secret(),
+        };
+        let (refund, refund_details) = signed_withdrawal(script_path, FeeRate::from_sat_per_vb(25.0));
+        assert!(refund_details.fee.unwrap() >= 25 * refund.vsize() as u64);
+    }
+
+    #[test]
+    fn create_withdraw_and_watchers_share_one_descriptor() {
+        let swap_info = test_swap_info();
+        let descriptor = build_htlc_descriptor(&swap_info, Network::Regtest).unwrap();
+        assert!(descriptor.starts_with(&format!("tr({},", swap_info.recipient_public_key)));
+        assert!(descriptor.contains('#'), "descriptor should carry a checksum");
+        assert_eq!(build_htlc_descriptor(&swap_info, Network::Regtest).unwrap(), descriptor);
+
+        // What withdrawals and refunds spend from, whichever key they sign with
+        for (signing_key, is_internal_key) in [(escrow_secret(), true), (revocation_secret(), false)] {
+            let wallet =
+                contract_spending_wallet(&swap_info, signing_key, is_internal_key, Network::Regtest).unwrap();
+            let spent_descriptor = wallet.public_descriptor(KeychainKind::External).unwrap().unwrap();
+            assert_eq!(spent_descriptor.to_string(), descriptor);
+        }
+
+        // The compiled leaf matches the hand-written `and_v(v:pk(..),older(..))` miniscript
+        let revocation_pubkey = bitcoin::PublicKey::new(swap_info.revocation_public_key);
+        let escrow_pubkey = bitcoin::PublicKey::new(swap_info.recipient_public_key);
+        let handwritten = bdk::descriptor!(tr(
+            escrow_pubkey,
+            and_v(v:pk(revocation_pubkey), older(TIMELOCK_BLOCKS))
+        ))
+        .unwrap();
+        let handwritten_address = Wallet::new(handwritten, None, Network::Regtest, MemoryDatabase::new())
+            .unwrap()
+            .get_address(AddressIndex::Peek(0))
+            .unwrap()
+            .address;
+        let contract_address = create_contract_wallet(&descriptor, Network::Regtest)
+            .unwrap()
+            .get_address(AddressIndex::Peek(0))
+            .unwrap()
+            .address;
+        assert_eq!(handwritten_address, contract_address);
+    }
+
+    #[test]
+    fn rebuilt_swap_must_pay_the_given_contract_address() {
+        let secp = Secp256k1::new();
+        let swap_public_key = SecretKey::from_slice(&[3u8; 32]).unwrap().public_key(&secp);
+        let recipient_public_key = SecretKey::from_slice(&[4u8; 32]).unwrap().public_key(&secp);
+        let revocation_public_key = revocation_secret().public_key(&secp);
+        let escrow_pubkey = aggregate_public_keys(&swap_public_key, &recipient_public_key).unwrap();
+        let original = SwapInfo::new(escrow_pubkey, revocation_public_key, TIMELOCK_BLOCKS, CONTRACT_SAT);
+        let descriptor = build_htlc_descriptor(&original, Network::Regtest).unwrap();
+        let contract_address = create_contract_wallet(&descriptor, Network::Regtest)
+            .unwrap()
+            .get_address(AddressIndex::Peek(0))
+            .unwrap()
+            .address;
+
+        let rebuild = |timelock_blocks| {
+            rebuild_swap_info(
+                &swap_public_key,
+                &recipient_public_key,
+                &revocation_public_key,
+                timelock_blocks,
+                &contract_address,
+            )
+        };
+        let rebuilt = rebuild(TIMELOCK_BLOCKS).unwrap();
+        assert_eq!(rebuilt.recipient_public_key, escrow_pubkey);
+        assert_eq!(build_htlc_descriptor(&rebuilt, Network::Regtest).unwrap(), descriptor);
+
+        let err = rebuild(TIMELOCK_BLOCKS + 1).unwrap_err();
+        assert!(err.to_string().contains(&contract_address.to_string()), "{}", err);
+    }
+
+    /// What `create_taproot_htlc_contract` returns for the contract of `swap_info`
+    fn test_creation(swap_info: &SwapInfo) -> HtlcCreation {
+        let descriptor = build_htlc_descriptor(swap_info, Network::Regtest).unwrap();
+        let contract_address = create_contract_wallet(&descriptor, Network::Regtest)
+            .unwrap()
+            .get_address(AddressIndex::Peek(0))
+            .unwrap()
+            .address;
+        HtlcCreation {
+            txid: Txid::all_zeros(),
+            contract_address,
+            descriptor,
+            fee_sat: 0,
+        }
+    }
+
+    #[test]
+    fn repeated_swaps_find_the_record_saved_with_the_escrow_key() {
+        let secp = Secp256k1::new();
+        let recipient_public_key = SecretKey::from_slice(&[4u8; 32]).unwrap().public_key(&secp);
+        let swap_info = SwapInfo::new(
+            recipient_public_key,
+            revocation_secret().public_key(&secp),
+            TIMELOCK_BLOCKS,
+            CONTRACT_SAT,
+        );
+        let swap_secret = SecretKey::from_slice(&[3u8; 32]).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let swaps_dir = dir.path().join("swaps");
+        assert!(SwapRecord::find_by_swap_id(&swaps_dir, &swap_info.swap_id()).unwrap().is_none());
+
+        // As `new_atomic_swap` saves it, holding the escrow key rather than the recipient's
+        let mut funded = swap_info.clone();
+        let swap_id = lock_to_escrow_key(&mut funded, &swap_secret).unwrap();
+        assert_eq!(swap_id, swap_info.swap_id());
+        assert_ne!(funded.swap_id(), swap_id);
+        let newer = funded_swap_record(swap_id, &funded, &swap_secret, None, &test_creation(&funded));
+        let mut older = newer.clone();
+        older.funding_txid = Txid::from_inner([1u8; 32]).to_string();
+        older.creation_timestamp -= 60;
+        older.save_to_json(&older.file_path(&swaps_dir)).unwrap();
+        newer.save_to_json(&newer.file_path(&swaps_dir)).unwrap();
+
+        // Running `swap` again with the same parameters looks up the same ID
+        let (path, found) = SwapRecord::find_by_swap_id(&swaps_dir, &swap_info.swap_id())
+            .unwrap()
+            .unwrap();
+        assert_eq!(path, newer.file_path(&swaps_dir));
+        assert_eq!(found.swap_info.recipient_public_key, funded.recipient_public_key);
+        let larger = SwapInfo {
+            amount_satoshis: CONTRACT_SAT + 1,
+            ..swap_info
+        };
+        assert!(SwapRecord::find_by_swap_id(&swaps_dir, &larger.swap_id()).unwrap().is_none());
+    }
+
+    #[test]
+    fn forced_deterministic_swaps_lock_to_different_contracts() {
+        let secp = Secp256k1::new();
+        let master_key = ExtendedPrivKey::new_master(Network::Regtest, &[9u8; 32]).unwrap();
+        let recipient_public_key = SecretKey::from_slice(&[4u8; 32]).unwrap().public_key(&secp);
+        let swap_info = SwapInfo::new(
+            recipient_public_key,
+            revocation_secret().public_key(&secp),
+            TIMELOCK_BLOCKS,
+            CONTRACT_SAT,
+        );
+        // What `new_atomic_swap` funds and records for the secret at `secret_index`
+        let fund = |secret_index: u32| {
+            let swap_secret = derive_swap_secret(&master_key, secret_index).unwrap();
+            let mut funded = swap_info.clone();
+            let swap_id = lock_to_escrow_key(&mut funded, &swap_secret).unwrap();
+            let creation = test_creation(&funded);
+            let record = funded_swap_record(swap_id, &funded, &swap_secret, Some(secret_index), &creation);
+            (record, creation.contract_address)
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let first_index = swap_info.unused_secret_index(dir.path()).unwrap();
+        assert_eq!(first_index, swap_info.secret_index());
+        let (first_record, first_address) = fund(first_index);
+        first_record.save_to_json(&first_record.file_path(dir.path())).unwrap();
+
+        let second_index = swap_info.unused_secret_index(dir.path()).unwrap();
+        assert_ne!(second_index, first_index);
+        let (_, second_address) = fund(second_index);
+        assert_ne!(second_address, first_address);
+    }
+
+    #[test]
+    fn legacy_records_withdraw_with_plain_key_addition() {
+        let secp = Secp256k1::new();
+        let swap_secret = SecretKey::from_slice(&[3u8; 32]).unwrap();
+        let recipient_secret = SecretKey::from_slice(&[4u8; 32]).unwrap();
+        let recipient_public_key = recipient_secret.public_key(&secp);
+        let legacy_escrow = swap_secret.public_key(&secp).combine(&recipient_public_key).unwrap();
+        let legacy_info = SwapInfo::new(legacy_escrow, revocation_secret().public_key(&secp), TIMELOCK_BLOCKS, CONTRACT_SAT);
+        let descriptor = build_htlc_descriptor(&legacy_info, Network::Regtest).unwrap();
+        let contract_address = create_contract_wallet(&descriptor, Network::Regtest)
+            .unwrap()
+            .get_address(AddressIndex::Peek(0))
+            .unwrap()
+            .address;
+
+        // Saved before the scheme was recorded, so the field is missing
+        let record = SwapRecord::new(legacy_info.clone(), &swap_secret, descriptor, contract_address, Txid::all_zeros());
+        let mut record_json = serde_json::to_value(&record).unwrap();
+        record_json.as_object_mut().unwrap().remove("key_aggregation");
+        let record: SwapRecord = serde_json::from_value(record_json).unwrap();
+        assert_eq!(record.key_aggregation, KeyAggregation::Legacy);
+
+        // `withdraw` starts from the recipient's own key, as handle_withdraw_command does
+        let withdraw_info = SwapInfo {
+            recipient_public_key,
+            ..legacy_info
+        };
+        let (escrow_secret_key, escrow_info) =
+            escrow_key(&withdraw_info, &recipient_secret, &swap_secret, record.key_aggregation).unwrap();
+        assert_eq!(escrow_info.recipient_public_key, record.swap_info.recipient_public_key);
+        let (_, musig_info) = escrow_key(&withdraw_info, &recipient_secret, &swap_secret, KeyAggregation::Musig).unwrap();
+        assert_ne!(musig_info.recipient_public_key, legacy_escrow);
+
+        let wallet = funded_contract_wallet(&escrow_info, escrow_secret_key, true);
+        let destination = wallet.get_address(AddressIndex::Peek(1)).unwrap().address;
+        let options = WithdrawalOptions {
+            fee_rate: FeeRate::from_sat_per_vb(1.0),
+            spend_path: SpendPath::KeyPath,
+            poll: None,
+            include_unconfirmed: false,
+            key_aggregation: record.key_aggregation,
+        };
+        let (psbt, _) = build_signed_withdrawal(&wallet, &destination, options, Vec::new()).unwrap();
+        assert_eq!(psbt.extract_tx().input[0].witness.len(), 1);
+    }
+
+    #[test]
+    fn refund_matures_when_the_next_block_satisfies_the_timelock() {
+        // Confirmed at 100 with a 10-block lock: spendable in block 110
+        assert_eq!(blocks_until_refundable(100, 10, 100), 9);
+        assert_eq!(blocks_until_refundable(100, 10, 108), 1);
+        assert_eq!(blocks_until_refundable(100, 10, 109), 0);
+        assert_eq!(blocks_until_refundable(100, 10, 500), 0);
+    }
+
+    #[test]
+    fn funding_polls_sleep_at_most_until_the_deadline() {
+        let now = Instant::now();
+        let interval = Duration::from_secs(10);
+
+        // A timeout shorter than the interval still gets a second check
+        assert_eq!(
+            next_poll_delay(interval, now + Duration::from_secs(3), now),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(next_poll_delay(interval, now + Duration::from_secs(60), now), Some(interval));
+        assert_eq!(next_poll_delay(interval, now, now), None);
+        assert_eq!(next_poll_delay(interval, now, now + Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn key_path_withdrawal_has_smaller_witness_than_script_path() {
+        let revocation_secret_key = revocation_secret();
+        let key_path = withdrawal_witness_size(SpendPath::KeyPath);
+        let script_path = withdrawal_witness_size(SpendPath::ScriptPath { revocation_secret_key });
+
+        // A lone 64-byte Schnorr signature against signature, leaf script and control block
+        assert_eq!(key_path, 64);
+        assert!(script_path > key_path, "script path witness is {} bytes", script_path);
+    }
+
+    #[test]
+    fn spend_path_follows_witness_shape() {
+        let key_path = Witness::from_vec(vec![vec![1; 64]]);
+        assert_eq!(classify_spend(&key_path), SwapSpendPath::Claimed);
+
+        let key_path_with_annex = Witness::from_vec(vec![vec![1; 64], vec![0x50, 1]]);
+        assert_eq!(classify_spend(&key_path_with_annex), SwapSpendPath::Claimed);
+
+        let script_path = Witness::from_vec(vec![vec![1; 64], vec![2; 36], vec![0xc0; 33]]);
+        assert_eq!(classify_spend(&script_path), SwapSpendPath::Refunded);
     }
-    
-    Ok(txid)
 }
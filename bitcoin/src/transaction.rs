@@ -1,71 +1,1030 @@
-use bdk::bitcoin::{Address, Txid};
-use bdk::blockchain::{Blockchain, RpcBlockchain};
-use bdk::database::MemoryDatabase;
-use bdk::{FeeRate, SignOptions, SyncOptions, Wallet};
-use eyre::{Result, eyre};
+use bdk::bitcoin::consensus::Decodable;
+use bdk::bitcoin::consensus::encode::{self, serialize_hex};
+use bdk::bitcoin::hashes::hex::FromHex;
+use bdk::bitcoin::psbt::PartiallySignedTransaction;
+use bdk::bitcoin::{Address, Network, OutPoint, PackedLockTime, Script, Transaction, TxOut, Txid};
+use bdk::bitcoincore_rpc::RpcApi;
+use bdk::bitcoincore_rpc::bitcoincore_rpc_json::TestMempoolAcceptResult;
+use bdk::blockchain::Blockchain;
+use bdk::database::BatchDatabase;
+use bdk::wallet::coin_selection::{
+    BranchAndBoundCoinSelection, DefaultCoinSelectionAlgorithm, LargestFirstCoinSelection,
+    OldestFirstCoinSelection,
+};
+use bdk::wallet::tx_builder::{CreateTx, TxBuilder};
+use bdk::{FeeRate, LocalUtxo, SignOptions, SyncOptions, TransactionDetails, Wallet};
+use eyre::{Result, WrapErr, eyre};
+use serde::Deserialize;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::constants::DEFAULT_FEE_RATE_SAT_PER_VB;
+use crate::blockchain::Backend;
+use crate::constants::MAX_OP_RETURN_DATA_LEN;
+use crate::error::TransactionError;
+use crate::output::say;
+use crate::wallet::btc_to_satoshis;
+
+/// Set by the global `--dry-run` flag
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Make [`publish_transaction`] print transactions instead of broadcasting them
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+/// Whether `--dry-run` was given
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Broadcast a signed transaction, or under `--dry-run` print it instead
+///
+/// Every command that sends a transaction goes through here. A dry run
+/// prints the raw transaction hex with its fee (when known) and virtual size,
+/// and leaves the network untouched.
+pub fn publish_transaction(
+    blockchain_client: &Backend,
+    transaction: &Transaction,
+    fee_sat: Option<u64>,
+) -> Result<()> {
+    if !is_dry_run() {
+        blockchain_client.broadcast(transaction)?;
+        return Ok(());
+    }
+
+    let vsize = transaction.vsize();
+    say!("🧪 Dry run: transaction {} was not broadcast", transaction.txid());
+    match fee_sat {
+        Some(fee_sat) => say!(
+            "💸 Fee: {} sats ({:.2} sat/vB)",
+            fee_sat,
+            fee_sat as f64 / vsize as f64
+        ),
+        None => say!("💸 Fee: unknown"),
+    }
+    say!("📏 Size: {} vB", vsize);
+    say!("📦 Raw transaction: {}", serialize_hex(transaction));
+    Ok(())
+}
+
+/// Algorithm for choosing which wallet UTXOs fund a transaction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CoinSelection {
+    /// bdk's default algorithm (currently branch and bound)
+    #[default]
+    Default,
+    /// Spend the largest UTXOs first, using as few inputs as possible
+    LargestFirst,
+    /// Spend the oldest UTXOs first
+    OldestFirst,
+    /// Look for an input set that needs no change output, avoiding a change
+    /// address and its fee
+    BranchAndBound,
+}
+
+impl CoinSelection {
+    /// Finish `tx_builder` using this algorithm to pick its inputs
+    fn finish<D: BatchDatabase>(
+        self,
+        tx_builder: TxBuilder<'_, D, DefaultCoinSelectionAlgorithm, CreateTx>,
+    ) -> Result<(PartiallySignedTransaction, TransactionDetails)> {
+        Ok(match self {
+            CoinSelection::Default => tx_builder.finish()?,
+            CoinSelection::LargestFirst => tx_builder.coin_selection(LargestFirstCoinSelection).finish()?,
+            CoinSelection::OldestFirst => tx_builder.coin_selection(OldestFirstCoinSelection).finish()?,
+            CoinSelection::BranchAndBound => tx_builder
+                .coin_selection(BranchAndBoundCoinSelection::default())
+                .finish()?,
+        })
+    }
+}
+
+/// Which wallet UTXOs may fund a transaction
+#[derive(Debug, Clone, Default)]
+pub struct InputSelection {
+    /// Algorithm that picks among the wallet's UTXOs
+    pub coin_selection: CoinSelection,
+    /// Spend exactly these outpoints instead of letting the algorithm choose
+    pub utxos: Vec<OutPoint>,
+}
+
+impl InputSelection {
+    /// The UTXOs a transaction may spend: the chosen ones, or else every wallet UTXO
+    fn candidate_utxos<D: BatchDatabase>(&self, wallet: &Wallet<D>) -> Result<Vec<LocalUtxo>> {
+        if self.utxos.is_empty() {
+            return Ok(wallet.list_unspent()?);
+        }
+        let mut utxos = Vec::with_capacity(self.utxos.len());
+        for outpoint in &self.utxos {
+            // Unknown outpoints are reported by `finish`
+            if let Some(utxo) = wallet.get_utxo(*outpoint)? {
+                utxos.push(utxo);
+            }
+        }
+        Ok(utxos)
+    }
+
+    /// Restrict `tx_builder` to the chosen UTXOs, if any, and finish it
+    ///
+    /// Fails with [`TransactionError::InsufficientFunds`], naming the
+    /// shortfall, when the chosen UTXOs cannot pay the outputs and fee.
+    fn finish<D: BatchDatabase>(
+        &self,
+        mut tx_builder: TxBuilder<'_, D, DefaultCoinSelectionAlgorithm, CreateTx>,
+    ) -> Result<(PartiallySignedTransaction, TransactionDetails)> {
+        if self.utxos.is_empty() {
+            return self.coin_selection.finish(tx_builder);
+        }
+
+        tx_builder
+            .add_utxos(&self.utxos)
+            .map_err(|e| TransactionError::BuildFailed(format!("Cannot spend selected UTXO: {}", e)))?
+            .manually_selected_only();
+        self.coin_selection.finish(tx_builder).map_err(|e| {
+            match e.downcast_ref::<bdk::Error>() {
+                Some(bdk::Error::InsufficientFunds { needed, available }) => {
+                    TransactionError::InsufficientFunds(format!(
+                        "selected UTXOs hold {} sats but {} are needed including the fee ({} sats short)",
+                        available,
+                        needed,
+                        needed.saturating_sub(*available)
+                    ))
+                    .into()
+                }
+                _ => e,
+            }
+        })
+    }
+}
+
+/// Weight of an input before its witness or script sig: outpoint, sequence and script length
+const TXIN_BASE_WEIGHT: usize = (32 + 4 + 4) * 4;
+
+/// Fee bdk charges to send the whole value of `utxos` to one `drain_script` output
+///
+/// Mirrors bdk's own accounting, so the result is the fee a drain really
+/// pays: the transaction header and `extra_outputs`, the segwit marker, each
+/// input with the worst-case satisfaction of its descriptor, and the drain
+/// output.
+pub fn drain_fee<D: BatchDatabase>(
+    wallet: &Wallet<D>,
+    utxos: &[LocalUtxo],
+    drain_script: &Script,
+    extra_outputs: &[TxOut],
+    fee_rate: FeeRate,
+) -> Result<u64> {
+    let header = Transaction {
+        version: 1,
+        lock_time: PackedLockTime::ZERO,
+        input: Vec::new(),
+        output: extra_outputs.to_vec(),
+    };
+    let mut fee = fee_rate.fee_wu(header.weight()) + fee_rate.fee_wu(2);
+    for utxo in utxos {
+        let satisfaction_weight = wallet
+            .get_descriptor_for_keychain(utxo.keychain)
+            .max_satisfaction_weight()
+            .map_err(|e| TransactionError::BuildFailed(format!("Cannot size input {}: {}", utxo.outpoint, e)))?;
+        fee += fee_rate.fee_wu(TXIN_BASE_WEIGHT + satisfaction_weight);
+    }
+    let drain_output_len = encode::serialize(drain_script).len() + 8;
+    Ok(fee + fee_rate.fee_vb(drain_output_len))
+}
+
+/// Fail early when `utxos` cannot fund a payment to `script_pubkey` at `fee_rate`
+///
+/// Spending every UTXO is the most a transaction can pay, so it fails when
+/// their value less [`drain_fee`] is below `amount_satoshis`, or below the
+/// output's dust limit when draining (`None`). The
+/// [`TransactionError::InsufficientFunds`] names the exact shortfall.
+pub fn ensure_inputs_cover<D: BatchDatabase>(
+    wallet: &Wallet<D>,
+    utxos: &[LocalUtxo],
+    script_pubkey: &Script,
+    amount_satoshis: Option<u64>,
+    extra_outputs: &[TxOut],
+    fee_rate: FeeRate,
+) -> Result<()> {
+    let available: u64 = utxos.iter().map(|utxo| utxo.txout.value).sum();
+    let fee = drain_fee(wallet, utxos, script_pubkey, extra_outputs, fee_rate)?;
+    let (required, purpose) = match amount_satoshis {
+        Some(amount_satoshis) => (amount_satoshis, format!("pay {} sats", amount_satoshis)),
+        None => {
+            let dust_limit = script_pubkey.dust_value().to_sat();
+            (dust_limit, format!("leave an output above the {} sat dust limit", dust_limit))
+        }
+    };
+    let needed = fee + required;
+    if available >= needed {
+        return Ok(());
+    }
+    Err(TransactionError::InsufficientFunds(format!(
+        "{} sats in {} UTXO(s) cannot {} after {} sats in fees at {} sat/vB ({} sats short)",
+        available,
+        utxos.len(),
+        purpose,
+        fee,
+        fee_rate.as_sat_per_vb(),
+        needed - available
+    ))
+    .into())
+}
+
+/// Optional extras for a send built by [`TransactionUtils::build_unsigned_psbt`]
+#[derive(Debug, Clone, Default)]
+pub struct SendOptions {
+    /// Data for a zero-value `OP_RETURN` output
+    pub op_return: Option<Vec<u8>>,
+    /// Which UTXOs fund the transaction
+    pub inputs: InputSelection,
+    /// Where change goes instead of a fresh address of the sending wallet
+    pub change_to: Option<Address>,
+}
 
 /// Transaction utilities for Bitcoin operations
 pub struct TransactionUtils;
 
+/// Outcome of a successfully broadcast transaction
+#[derive(Debug, Clone)]
+pub struct BroadcastResult {
+    pub txid: Txid,
+    /// Fee paid in satoshis
+    pub fee_sat: u64,
+    /// Virtual size of the signed transaction in vbytes
+    pub vsize: usize,
+    /// The signed transaction as broadcast
+    pub transaction: Transaction,
+    /// Change output, when the builder created one and the caller located it
+    /// with [`TransactionUtils::find_change_outpoint`]
+    pub change_outpoint: Option<OutPoint>,
+}
+
+impl BroadcastResult {
+    /// Effective fee rate in sat/vB
+    pub fn effective_fee_rate(&self) -> f64 {
+        self.fee_sat as f64 / self.vsize as f64
+    }
+
+    /// Total satoshis the transaction pays to `script_pubkey`
+    pub fn amount_to(&self, script_pubkey: &Script) -> u64 {
+        amount_paid_to(&self.transaction, script_pubkey)
+    }
+}
+
+/// Total satoshis `transaction` pays to `script_pubkey`, signed or not
+pub fn amount_paid_to(transaction: &Transaction, script_pubkey: &Script) -> u64 {
+    transaction
+        .output
+        .iter()
+        .filter(|output| &output.script_pubkey == script_pubkey)
+        .map(|output| output.value)
+        .sum()
+}
+
 
 impl TransactionUtils {
-    /// Create and broadcast a transaction
-    pub async fn create_and_broadcast(
-        blockchain_client: &RpcBlockchain,
-        sender_wallet: &Wallet<MemoryDatabase>,
+    /// Pay `amount_satoshis` to `recipient_address`, with change back to the wallet
+    ///
+    /// The result carries the change outpoint if the transaction has one.
+    pub async fn create_and_broadcast_with_fee_rate<D: BatchDatabase>(
+        blockchain_client: &Backend,
+        sender_wallet: &Wallet<D>,
         recipient_address: Address,
         amount_satoshis: u64,
-    ) -> Result<Txid> {
-        Self::create_and_broadcast_with_fee_rate(
+        fee_rate: FeeRate,
+    ) -> Result<BroadcastResult> {
+        let recipient_script = recipient_address.script_pubkey();
+        let (partially_signed_tx, transaction_details) = Self::build_unsigned_psbt(
             blockchain_client,
             sender_wallet,
             recipient_address,
-            amount_satoshis,
-            FeeRate::from_sat_per_vb(DEFAULT_FEE_RATE_SAT_PER_VB),
-        ).await
+            Some(amount_satoshis),
+            fee_rate,
+            &SendOptions::default(),
+        )?;
+
+        let mut broadcast_result = Self::sign_and_broadcast(
+            blockchain_client,
+            sender_wallet,
+            partially_signed_tx,
+            transaction_details,
+        )?;
+        broadcast_result.change_outpoint = Self::find_change_outpoint(
+            sender_wallet,
+            &broadcast_result.transaction,
+            &recipient_script,
+            None,
+        )?;
+        Ok(broadcast_result)
     }
 
-    pub async fn create_and_broadcast_with_fee_rate(
-        blockchain_client: &RpcBlockchain,
-        sender_wallet: &Wallet<MemoryDatabase>,
-        recipient_address: Address,
-        amount_satoshis: u64,
+    /// Locate the change output of a transaction paying `recipient_script`
+    ///
+    /// With `change_to` the change is the output paying that address;
+    /// otherwise it is the output paying back to `wallet` that is not the
+    /// payment itself.
+    pub fn find_change_outpoint<D: BatchDatabase>(
+        wallet: &Wallet<D>,
+        transaction: &Transaction,
+        recipient_script: &Script,
+        change_to: Option<&Address>,
+    ) -> Result<Option<OutPoint>> {
+        let txid = transaction.txid();
+        for (vout, output) in transaction.output.iter().enumerate() {
+            if &output.script_pubkey == recipient_script {
+                continue;
+            }
+            let is_change = match change_to {
+                Some(change_address) => output.script_pubkey == change_address.script_pubkey(),
+                None => wallet.is_mine(&output.script_pubkey)?,
+            };
+            if is_change {
+                return Ok(Some(OutPoint::new(txid, vout as u32)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Pay several recipients in a single transaction
+    pub async fn create_and_broadcast_batch<D: BatchDatabase>(
+        blockchain_client: &Backend,
+        sender_wallet: &Wallet<D>,
+        recipients: &[(Address, u64)],
         fee_rate: FeeRate,
     ) -> Result<Txid> {
+        if recipients.is_empty() {
+            return Err(eyre!("Batch send needs at least one recipient"));
+        }
         sender_wallet.sync(blockchain_client, SyncOptions::default())?;
 
-        let (mut partially_signed_tx, _) = {
+        let (partially_signed_tx, transaction_details) = {
             let mut tx_builder = sender_wallet.build_tx();
-            tx_builder
-                .fee_rate(fee_rate)
-                .add_recipient(recipient_address.script_pubkey(), amount_satoshis);
+            tx_builder.fee_rate(fee_rate).enable_rbf();
+            for (address, amount_satoshis) in recipients {
+                tx_builder.add_recipient(address.script_pubkey(), *amount_satoshis);
+            }
             tx_builder.finish()?
         };
 
+        let broadcast_result = Self::sign_and_broadcast(
+            blockchain_client,
+            sender_wallet,
+            partially_signed_tx,
+            transaction_details,
+        )?;
+        Ok(broadcast_result.txid)
+    }
+
+    /// Build an unsigned send to `recipient_address` without broadcasting it
+    ///
+    /// With no amount the whole wallet balance is drained to the recipient, or
+    /// only the chosen UTXOs when `options.inputs` names any. Otherwise change
+    /// goes to `options.change_to`, or a fresh wallet address by default.
+    /// `options.op_return` data, if any, is added as a zero-value `OP_RETURN`
+    /// output.
+    /// The PSBT carries the previous outputs, so it can be signed by a copy of
+    /// the wallet that has never synced.
+    pub fn build_unsigned_psbt<D: BatchDatabase>(
+        blockchain_client: &Backend,
+        sender_wallet: &Wallet<D>,
+        recipient_address: Address,
+        amount_satoshis: Option<u64>,
+        fee_rate: FeeRate,
+        options: &SendOptions,
+    ) -> Result<(PartiallySignedTransaction, TransactionDetails)> {
+        sender_wallet.sync(blockchain_client, SyncOptions::default())?;
+        Self::build_psbt_from_synced_wallet(
+            sender_wallet,
+            recipient_address,
+            amount_satoshis,
+            fee_rate,
+            options,
+        )
+    }
+
+    /// [`Self::build_unsigned_psbt`] using the wallet's UTXOs as of its last sync
+    ///
+    /// Checks with [`ensure_inputs_cover`] that the candidate UTXOs can pay
+    /// the amount and fee before building anything.
+    pub fn build_psbt_from_synced_wallet<D: BatchDatabase>(
+        sender_wallet: &Wallet<D>,
+        recipient_address: Address,
+        amount_satoshis: Option<u64>,
+        fee_rate: FeeRate,
+        options: &SendOptions,
+    ) -> Result<(PartiallySignedTransaction, TransactionDetails)> {
+        let op_return_outputs: Vec<TxOut> = options
+            .op_return
+            .iter()
+            .map(|data| TxOut {
+                value: 0,
+                script_pubkey: Script::new_op_return(data),
+            })
+            .collect();
+        ensure_inputs_cover(
+            sender_wallet,
+            &options.inputs.candidate_utxos(sender_wallet)?,
+            &recipient_address.script_pubkey(),
+            amount_satoshis,
+            &op_return_outputs,
+            fee_rate,
+        )?;
+
+        let mut tx_builder = sender_wallet.build_tx();
+        tx_builder.fee_rate(fee_rate).enable_rbf();
+        if let Some(data) = &options.op_return {
+            tx_builder.add_data(data);
+        }
+        match amount_satoshis {
+            Some(amount_satoshis) => {
+                tx_builder.add_recipient(recipient_address.script_pubkey(), amount_satoshis);
+                // Without drain_wallet, drain_to only receives the change
+                if let Some(change_address) = &options.change_to {
+                    tx_builder.drain_to(change_address.script_pubkey());
+                }
+            }
+            None => {
+                // Chosen UTXOs are spent in full without pulling in the rest
+                if options.inputs.utxos.is_empty() {
+                    tx_builder.drain_wallet();
+                }
+                tx_builder.drain_to(recipient_address.script_pubkey());
+            }
+        }
+        options.inputs.finish(tx_builder)
+    }
+
+    /// Sign every input of `psbt` the wallet holds keys for
+    ///
+    /// Returns whether the PSBT is now fully finalized and ready to broadcast.
+    pub fn sign_psbt<D: BatchDatabase>(
+        wallet: &Wallet<D>,
+        psbt: &mut PartiallySignedTransaction,
+    ) -> Result<bool> {
+        Ok(wallet.sign(psbt, SignOptions::default())?)
+    }
+
+    /// Inputs of `psbt` the wallet holds a key for, in index order, and whether its signature is there
+    ///
+    /// Signs a copy with every signature stripped and looks where the wallet's
+    /// signatures land, so keys are matched exactly as `sign-psbt` would match
+    /// them. A finalized input counts as signed by every wallet with a key for it.
+    pub fn signable_inputs<D: BatchDatabase>(
+        wallet: &Wallet<D>,
+        psbt: &PartiallySignedTransaction,
+    ) -> Result<Vec<(usize, InputSignature)>> {
+        let mut probe = psbt.clone();
+        for input in &mut probe.inputs {
+            input.partial_sigs.clear();
+            input.tap_key_sig = None;
+            input.tap_script_sigs.clear();
+            input.final_script_sig = None;
+            input.final_script_witness = None;
+        }
+        // Only the copy is signed, so trusting the PSBT's utxos and sighash types is harmless
+        let probe_options = SignOptions {
+            trust_witness_utxo: true,
+            allow_all_sighashes: true,
+            try_finalize: false,
+            ..SignOptions::default()
+        };
+        wallet.sign(&mut probe, probe_options)?;
+
+        Ok(psbt
+            .inputs
+            .iter()
+            .zip(&probe.inputs)
+            .enumerate()
+            .filter(|(_, (_, probed))| {
+                !probed.partial_sigs.is_empty() || probed.tap_key_sig.is_some() || !probed.tap_script_sigs.is_empty()
+            })
+            .map(|(index, (original, probed))| {
+                let is_signed = original.final_script_witness.is_some()
+                    || original.final_script_sig.is_some()
+                    || probed.partial_sigs.keys().any(|key| original.partial_sigs.contains_key(key))
+                    || (probed.tap_key_sig.is_some() && original.tap_key_sig.is_some())
+                    || probed.tap_script_sigs.keys().any(|key| original.tap_script_sigs.contains_key(key));
+                let state = if is_signed { InputSignature::Present } else { InputSignature::Missing };
+                (index, state)
+            })
+            .collect())
+    }
+
+    /// Sign a freshly built PSBT with `sender_wallet` and broadcast it
+    /// (or print it under `--dry-run`, see [`publish_transaction`])
+    ///
+    /// On a Bitcoin Core backend the transaction first goes through
+    /// [`Self::test_mempool_accept`], so policy failures report the node's
+    /// reject reason instead of a bare broadcast error.
+    pub fn sign_and_broadcast<D: BatchDatabase>(
+        blockchain_client: &Backend,
+        sender_wallet: &Wallet<D>,
+        partially_signed_tx: PartiallySignedTransaction,
+        transaction_details: TransactionDetails,
+    ) -> Result<BroadcastResult> {
+        let signed = Self::sign_for_broadcast(sender_wallet, partially_signed_tx, transaction_details)?;
+        if matches!(blockchain_client, Backend::Core(_)) {
+            Self::test_mempool_accept(blockchain_client, &signed.transaction)?;
+        }
+
+        publish_transaction(blockchain_client, &signed.transaction, Some(signed.fee_sat))?;
+        Ok(signed)
+    }
+
+    /// Sign a freshly built PSBT and check the node would accept it, without broadcasting
+    pub fn sign_and_check<D: BatchDatabase>(
+        blockchain_client: &Backend,
+        sender_wallet: &Wallet<D>,
+        partially_signed_tx: PartiallySignedTransaction,
+        transaction_details: TransactionDetails,
+    ) -> Result<BroadcastResult> {
+        let signed = Self::sign_for_broadcast(sender_wallet, partially_signed_tx, transaction_details)?;
+        Self::test_mempool_accept(blockchain_client, &signed.transaction)?;
+        Ok(signed)
+    }
+
+    /// Ask the node whether `transaction` would be accepted into its mempool
+    ///
+    /// Uses Bitcoin Core's `testmempoolaccept`, so Electrum and Esplora
+    /// backends cannot run the check.
+    pub fn test_mempool_accept(blockchain_client: &Backend, transaction: &Transaction) -> Result<()> {
+        let Backend::Core(client) = blockchain_client else {
+            return Err(eyre!("Mempool acceptance checks need a Bitcoin Core backend"));
+        };
+        let results = client
+            .test_mempool_accept(&[transaction])
+            .map_err(|e| TransactionError::BroadcastFailed(format!("testmempoolaccept failed: {}", e)))?;
+        let result = results
+            .first()
+            .ok_or_else(|| eyre!("testmempoolaccept returned no result for {}", transaction.txid()))?;
+        Ok(check_mempool_acceptance(result)?)
+    }
+
+    fn sign_for_broadcast<D: BatchDatabase>(
+        sender_wallet: &Wallet<D>,
+        mut partially_signed_tx: PartiallySignedTransaction,
+        transaction_details: TransactionDetails,
+    ) -> Result<BroadcastResult> {
         let is_finalized = sender_wallet.sign(&mut partially_signed_tx, SignOptions::default())?;
         if !is_finalized {
             return Err(eyre!("Failed to sign and finalize transaction"));
         }
 
-        let transaction_id = partially_signed_tx.unsigned_tx.txid();
-        blockchain_client.broadcast(&partially_signed_tx.extract_tx())?;
-        Ok(transaction_id)
-    }
-}
-
-/// Send Bitcoin from one wallet to a specific address
-pub async fn send_bitcoin_to_address(
-    blockchain_client: &RpcBlockchain,
-    sender_wallet: &Wallet<MemoryDatabase>,
-    recipient_address: Address,
-    amount_satoshis: u64,
-) -> Result<Txid> {
-    TransactionUtils::create_and_broadcast(
-        blockchain_client,
-        sender_wallet,
-        recipient_address,
-        amount_satoshis,
-    ).await
+        let fee_sat = transaction_details
+            .fee
+            .ok_or_else(|| eyre!("Transaction builder did not report a fee"))?;
+        let signed_transaction = partially_signed_tx.extract_tx();
+        Ok(BroadcastResult {
+            txid: signed_transaction.txid(),
+            fee_sat,
+            vsize: signed_transaction.vsize(),
+            transaction: signed_transaction,
+            change_outpoint: None,
+        })
+    }
+
+    /// Replace an unconfirmed wallet transaction with one paying `new_fee_rate` (BIP125)
+    ///
+    /// The original must still be in the mempool and signal RBF, as sends,
+    /// batches and drains built by this wallet do.
+    pub async fn bump_fee<D: BatchDatabase>(
+        blockchain_client: &Backend,
+        wallet: &Wallet<D>,
+        txid: Txid,
+        new_fee_rate: FeeRate,
+    ) -> Result<Txid> {
+        wallet.sync(blockchain_client, SyncOptions::default())?;
+
+        let (mut partially_signed_tx, transaction_details) = {
+            let mut tx_builder = wallet
+                .build_fee_bump(txid)
+                .map_err(|e| eyre!("Cannot bump fee of {}: {}", txid, e))?;
+            tx_builder.fee_rate(new_fee_rate);
+            tx_builder.finish()?
+        };
+
+        let is_finalized = wallet.sign(&mut partially_signed_tx, SignOptions::default())?;
+        if !is_finalized {
+            return Err(eyre!("Failed to sign and finalize replacement transaction"));
+        }
+
+        let replacement = partially_signed_tx.extract_tx();
+        publish_transaction(blockchain_client, &replacement, transaction_details.fee)?;
+        Ok(replacement.txid())
+    }
+}
+
+/// Turn a rejected `testmempoolaccept` result into an error carrying the node's reason
+fn check_mempool_acceptance(result: &TestMempoolAcceptResult) -> Result<(), TransactionError> {
+    if result.allowed {
+        return Ok(());
+    }
+    let reason = result.reject_reason.as_deref().unwrap_or("no reason given");
+    Err(TransactionError::BroadcastFailed(format!(
+        "Transaction {} would be rejected by the mempool: {}",
+        result.txid, reason
+    )))
+}
+
+/// One `address,amount` line of a batch recipients file, amount in BTC
+#[derive(Debug, Deserialize)]
+struct RecipientEntry {
+    address: String,
+    amount: f64,
+}
+
+/// Load batch recipients from a JSON or CSV file
+///
+/// JSON files (by `.json` extension) hold an array of `{"address", "amount"}`
+/// objects; anything else is read as CSV with one `address,amount` pair per
+/// line. Amounts are in BTC. Blank lines and lines starting with `#` are
+/// skipped. Addresses are parsed but not checked against a network.
+pub fn load_recipients(path: &Path) -> Result<Vec<(Address, u64)>> {
+    let content = fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read recipients file {}", path.display()))?;
+
+    let entries: Vec<RecipientEntry> =
+        if path.extension().is_some_and(|extension| extension == "json") {
+            serde_json::from_str(&content)
+                .wrap_err_with(|| format!("Invalid recipients JSON in {}", path.display()))?
+        } else {
+            content
+                .lines()
+                .enumerate()
+                .map(|(index, line)| (index + 1, line.trim()))
+                .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+                .map(|(line_number, line)| {
+                    let (address, amount) = line.split_once(',').ok_or_else(|| {
+                        eyre!("Line {}: expected `address,amount`", line_number)
+                    })?;
+                    let amount = amount
+                        .trim()
+                        .parse()
+                        .map_err(|e| eyre!("Line {}: invalid amount: {}", line_number, e))?;
+                    Ok(RecipientEntry {
+                        address: address.trim().to_string(),
+                        amount,
+                    })
+                })
+                .collect::<Result<_>>()?
+        };
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let address = Address::from_str(&entry.address).map_err(|e| {
+                TransactionError::InvalidAddress(format!("{}: {}", entry.address, e))
+            })?;
+            let amount_satoshis = btc_to_satoshis(entry.amount).map_err(|e| {
+                TransactionError::InvalidAmount(format!("{} to {}", e, entry.address))
+            })?;
+            if amount_satoshis == 0 {
+                return Err(TransactionError::InvalidAmount(format!(
+                    "{} BTC to {}",
+                    entry.amount, entry.address
+                ))
+                .into());
+            }
+            Ok((address, amount_satoshis))
+        })
+        .collect()
+}
+
+/// Decode hex `OP_RETURN` data, rejecting payloads over the standardness limit
+pub fn parse_op_return_data(hex: &str) -> Result<Vec<u8>> {
+    let data = Vec::<u8>::from_hex(hex)
+        .map_err(|e| eyre!("Invalid OP_RETURN hex {:?}: {}", hex, e))?;
+    if data.len() > MAX_OP_RETURN_DATA_LEN {
+        return Err(eyre!(
+            "OP_RETURN data is {} bytes, at most {} bytes are relayed",
+            data.len(),
+            MAX_OP_RETURN_DATA_LEN
+        ));
+    }
+    Ok(data)
+}
+
+/// Parse a `--fee-rate` in sat/vB, which must be a finite number above zero
+pub fn parse_fee_rate(fee_rate: &str) -> std::result::Result<f32, String> {
+    let sat_per_vb: f32 = fee_rate
+        .trim()
+        .parse()
+        .map_err(|_| format!("{:?} is not a fee rate in sat/vB", fee_rate))?;
+    if !sat_per_vb.is_finite() || sat_per_vb <= 0.0 {
+        return Err(format!("Fee rate must be a positive number of sat/vB, got {}", fee_rate));
+    }
+    Ok(sat_per_vb)
+}
+
+/// Reader that counts the bytes handed out, to locate decoding failures
+struct CountingReader<'a> {
+    data: &'a [u8],
+    consumed: usize,
+}
+
+impl Read for CountingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = (&self.data[self.consumed..]).read(buf)?;
+        self.consumed += read;
+        Ok(read)
+    }
+}
+
+/// Decode a hex-encoded network transaction without asking a node
+///
+/// Errors name the position of the first bad hex character, or the byte
+/// offset at which the transaction data ran out or stopped making sense.
+/// Bytes left over after a complete transaction are rejected too.
+pub fn decode_raw_transaction(hex: &str) -> Result<Transaction> {
+    let hex = hex.trim();
+    if let Some((position, character)) = hex.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        return Err(eyre!(
+            "Invalid hex character {:?} at position {}",
+            character,
+            position
+        ));
+    }
+    if !hex.len().is_multiple_of(2) {
+        return Err(eyre!(
+            "Hex has an odd number of digits ({}), the last byte is incomplete",
+            hex.len()
+        ));
+    }
+    let bytes = Vec::<u8>::from_hex(hex).map_err(|e| eyre!("Invalid hex: {}", e))?;
+
+    let mut reader = CountingReader {
+        data: &bytes,
+        consumed: 0,
+    };
+    let transaction = Transaction::consensus_decode(&mut reader).map_err(|e| match e {
+        encode::Error::Io(ref io_error) if io_error.kind() == io::ErrorKind::UnexpectedEof => eyre!(
+            "Transaction is truncated: data ends at byte {} of {} while more was expected",
+            reader.consumed,
+            bytes.len()
+        ),
+        _ => eyre!(
+            "Transaction is invalid at byte {} of {}: {}",
+            reader.consumed,
+            bytes.len(),
+            e
+        ),
+    })?;
+    if reader.consumed != bytes.len() {
+        return Err(eyre!(
+            "{} unexpected bytes after the transaction, starting at byte {}",
+            bytes.len() - reader.consumed,
+            reader.consumed
+        ));
+    }
+    Ok(transaction)
+}
+
+/// Standard type of an output script, e.g. `p2wpkh`, or `op_return`/`nonstandard`
+pub fn script_type_name(script: &Script) -> String {
+    // The network only affects the address encoding, not its type
+    match Address::from_script(script, Network::Bitcoin) {
+        Ok(address) => address
+            .address_type()
+            .map_or_else(|| "nonstandard".to_string(), |address_type| address_type.to_string()),
+        Err(_) if script.is_op_return() => "op_return".to_string(),
+        Err(_) => "nonstandard".to_string(),
+    }
+}
+
+/// Whether a wallet has signed a PSBT input it holds a key for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputSignature {
+    /// The wallet's signature is in the PSBT, or the input is already finalized
+    Present,
+    /// The wallet can sign the input but has not yet
+    Missing,
+}
+
+/// Write `psbt` to `path` as base64
+pub fn write_psbt(path: &Path, psbt: &PartiallySignedTransaction) -> Result<()> {
+    fs::write(path, psbt.to_string())
+        .wrap_err_with(|| format!("Failed to write PSBT to {}", path.display()))
+}
+
+/// Read a base64 PSBT from `path`
+pub fn read_psbt(path: &Path) -> Result<PartiallySignedTransaction> {
+    let content = fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read PSBT from {}", path.display()))?;
+    PartiallySignedTransaction::from_str(content.trim())
+        .map_err(|e| eyre!("Invalid PSBT in {}: {}", path.display(), e))
+}
+
+/// Extract the network transaction from a fully signed PSBT
+///
+/// Fails, naming the offending input indices, if any input has neither a
+/// final witness nor a final scriptSig.
+pub fn extract_finalized_tx(psbt: PartiallySignedTransaction) -> Result<Transaction> {
+    let incomplete_inputs: Vec<String> = psbt
+        .inputs
+        .iter()
+        .enumerate()
+        .filter(|(_, input)| input.final_script_witness.is_none() && input.final_script_sig.is_none())
+        .map(|(index, _)| index.to_string())
+        .collect();
+    if !incomplete_inputs.is_empty() {
+        return Err(TransactionError::SigningFailed(format!(
+            "PSBT is not finalized, input(s) {} have no final witness",
+            incomplete_inputs.join(", ")
+        ))
+        .into());
+    }
+    Ok(psbt.extract_tx())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk::bitcoin::PackedLockTime;
+    use bdk::blockchain::EsploraBlockchain;
+
+    const ADDRESS_A: &str = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+    const ADDRESS_B: &str = "bcrt1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qzf4jry";
+
+    #[test]
+    fn dry_run_prints_instead_of_broadcasting() {
+        // Nothing listens on the discard port, so a real broadcast fails
+        let backend = Backend::Esplora(Box::new(EsploraBlockchain::new("http://127.0.0.1:9", 20)));
+        let transaction = Transaction {
+            version: 2,
+            lock_time: PackedLockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        assert!(publish_transaction(&backend, &transaction, Some(150)).is_err());
+
+        set_dry_run(true);
+        let dry_run_result = publish_transaction(&backend, &transaction, Some(150));
+        set_dry_run(false);
+        dry_run_result.unwrap();
+    }
+
+    #[test]
+    fn raw_transactions_decode_with_precise_errors() {
+        let address = Address::from_str(ADDRESS_A).unwrap();
+        let transaction = Transaction {
+            version: 2,
+            lock_time: PackedLockTime(150),
+            input: vec![bdk::bitcoin::TxIn::default()],
+            output: vec![bdk::bitcoin::TxOut {
+                value: 10_000,
+                script_pubkey: address.script_pubkey(),
+            }],
+        };
+        let hex = serialize_hex(&transaction);
+
+        let decoded = decode_raw_transaction(&format!(" {}\n", hex)).unwrap();
+        assert_eq!(decoded, transaction);
+        assert_eq!(script_type_name(&decoded.output[0].script_pubkey), "p2wpkh");
+
+        let bad_character = format!("{}zz{}", &hex[..10], &hex[10..]);
+        let err = decode_raw_transaction(&bad_character).unwrap_err().to_string();
+        assert!(err.contains("position 10"), "{}", err);
+
+        let err = decode_raw_transaction(&hex[..hex.len() - 1]).unwrap_err().to_string();
+        assert!(err.contains("odd number"), "{}", err);
+
+        // Cut off inside the output script
+        let truncated = &hex[..hex.len() - 20];
+        let err = decode_raw_transaction(truncated).unwrap_err().to_string();
+        assert!(err.contains("truncated"), "{}", err);
+        assert!(err.contains(&format!("of {}", truncated.len() / 2)), "{}", err);
+
+        let err = decode_raw_transaction(&format!("{}00", hex)).unwrap_err().to_string();
+        assert!(err.contains("1 unexpected bytes"), "{}", err);
+    }
+
+    #[test]
+    fn recipients_load_from_csv_and_json() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let csv_path = dir.path().join("payouts.csv");
+        fs::write(
+            &csv_path,
+            format!("# payouts\n{},0.5\n\n {} , 0.00001\n", ADDRESS_A, ADDRESS_B),
+        )
+        .unwrap();
+        let from_csv = load_recipients(&csv_path).unwrap();
+
+        let json_path = dir.path().join("payouts.json");
+        fs::write(
+            &json_path,
+            format!(
+                r#"[{{"address": "{}", "amount": 0.5}}, {{"address": "{}", "amount": 0.00001}}]"#,
+                ADDRESS_A, ADDRESS_B
+            ),
+        )
+        .unwrap();
+        let from_json = load_recipients(&json_path).unwrap();
+
+        assert_eq!(from_csv, from_json);
+        assert_eq!(from_csv.len(), 2);
+        assert_eq!(from_csv[0].0.to_string(), ADDRESS_A);
+        assert_eq!(from_csv[0].1, 50_000_000);
+        assert_eq!(from_csv[1].1, 1_000);
+    }
+
+    #[test]
+    fn psbt_file_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("send.psbt");
+        let address = Address::from_str(ADDRESS_A).unwrap();
+        let unsigned_tx = Transaction {
+            version: 2,
+            lock_time: bdk::bitcoin::PackedLockTime(0),
+            input: vec![bdk::bitcoin::TxIn::default()],
+            output: vec![bdk::bitcoin::TxOut {
+                value: 10_000,
+                script_pubkey: address.script_pubkey(),
+            }],
+        };
+        let psbt = PartiallySignedTransaction::from_unsigned_tx(unsigned_tx).unwrap();
+
+        write_psbt(&path, &psbt).unwrap();
+        assert_eq!(read_psbt(&path).unwrap(), psbt);
+        assert!(extract_finalized_tx(psbt.clone()).is_err());
+
+        fs::write(&path, "not a psbt").unwrap();
+        assert!(read_psbt(&path).is_err());
+    }
+
+    #[test]
+    fn extract_reports_incomplete_inputs() {
+        let unsigned_tx = Transaction {
+            version: 2,
+            lock_time: bdk::bitcoin::PackedLockTime(0),
+            input: vec![bdk::bitcoin::TxIn::default(); 3],
+            output: vec![],
+        };
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(unsigned_tx).unwrap();
+        psbt.inputs[0].final_script_witness = Some(bdk::bitcoin::Witness::from_vec(vec![vec![1]]));
+
+        let err = extract_finalized_tx(psbt.clone()).unwrap_err().to_string();
+        assert!(err.contains("input(s) 1, 2"), "{}", err);
+
+        for input in &mut psbt.inputs {
+            input.final_script_witness = Some(bdk::bitcoin::Witness::from_vec(vec![vec![1]]));
+        }
+        let transaction = extract_finalized_tx(psbt).unwrap();
+        assert_eq!(transaction.input[2].witness.to_vec(), vec![vec![1]]);
+    }
+
+    #[test]
+    fn op_return_data_is_validated() {
+        assert_eq!(parse_op_return_data("deadbeef").unwrap(), vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(parse_op_return_data(&"ab".repeat(80)).unwrap().len(), 80);
+        assert!(parse_op_return_data(&"ab".repeat(81)).is_err());
+        assert!(parse_op_return_data("xyz").is_err());
+        assert!(parse_op_return_data("abc").is_err());
+    }
+
+    #[test]
+    fn fee_rates_must_be_positive_and_finite() {
+        assert_eq!(parse_fee_rate("2.5"), Ok(2.5));
+        assert_eq!(parse_fee_rate("1"), Ok(1.0));
+        for invalid in ["0", "-1", "NaN", "inf", "fast", ""] {
+            assert!(parse_fee_rate(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn recipients_reject_bad_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("payouts.csv");
+
+        fs::write(&path, format!("{}\n", ADDRESS_A)).unwrap();
+        assert!(load_recipients(&path).is_err());
+
+        fs::write(&path, format!("{},-1\n", ADDRESS_A)).unwrap();
+        assert!(load_recipients(&path).is_err());
+
+        fs::write(&path, "not-an-address,0.1\n").unwrap();
+        assert!(load_recipients(&path).is_err());
+    }
+
+    #[test]
+    fn mempool_rejection_reports_the_reason() {
+        let txid = Txid::from_str("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b").unwrap();
+        let mut result = TestMempoolAcceptResult {
+            txid,
+            allowed: true,
+            reject_reason: None,
+            vsize: Some(141),
+            fees: None,
+        };
+        assert!(check_mempool_acceptance(&result).is_ok());
+
+        result.allowed = false;
+        result.reject_reason = Some("min relay fee not met".to_string());
+        let message = check_mempool_acceptance(&result).unwrap_err().to_string();
+        assert!(message.contains(&txid.to_string()));
+        assert!(message.contains("min relay fee not met"));
+    }
 }
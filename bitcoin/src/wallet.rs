@@ -1,35 +1,221 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
 use bdk::{
     Wallet,
     bitcoin::{
-        Network, Address,
+        Network, Address, PrivateKey,
         secp256k1::{PublicKey, Secp256k1, SecretKey},
-        util::bip32::DerivationPath,
+        util::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey, ExtendedPubKey},
     },
-    database::MemoryDatabase,
+    bitcoin::Txid,
+    blockchain::log_progress,
+    database::{AnyDatabase, Database, MemoryDatabase},
     keys::{DerivableKey, ExtendedKey},
-    SyncOptions,
+    wallet::{AddressInfo, wallet_name_from_descriptor},
+    Balance, FeeRate, KeychainKind, LocalUtxo, SyncOptions, TransactionDetails,
 };
-use bip39::Mnemonic;
+use bip39::{Language, Mnemonic};
 use eyre::{Result, eyre};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-use crate::blockchain::create_bitcoin_rpc_client;
-use crate::constants::SATOSHIS_PER_BTC;
+use crate::blockchain::{BitcoinRpcConfig, create_blockchain_backend_with_config};
+use crate::constants::{
+    MAINNET_BIP49_PATH, MAINNET_BIP84_PATH, MAINNET_BIP86_PATH, MAX_MONEY_SAT, REGTEST_BIP49_PATH,
+    REGTEST_BIP84_PATH, REGTEST_BIP86_PATH, SATOSHIS_PER_BTC, SWAP_SECRET_PURPOSE,
+};
+use crate::error::{self, ConfigFileError, TransactionError, WalletError};
+use crate::transaction::{SendOptions, TransactionUtils, amount_paid_to};
 
 /// Wallet configuration loaded from TOML files
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct WalletConfig {
+    #[serde(default)]
+    pub wallet: WalletSettings,
     pub keys: KeyConfiguration,
 }
 
+/// General wallet settings from the `[wallet]` section
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WalletSettings {
+    /// Bitcoin network the wallet operates on (defaults to regtest)
+    #[serde(default = "default_network")]
+    pub network: Network,
+    /// Optional sled database path for persisting wallet state between runs.
+    /// Relative paths are resolved against the config file's directory.
+    #[serde(default)]
+    pub db_path: Option<PathBuf>,
+    /// Descriptor type for the wallet's addresses (`wpkh`, `sh-wpkh` or `tr`)
+    #[serde(default)]
+    pub descriptor_type: DescriptorType,
+}
+
+impl Default for WalletSettings {
+    fn default() -> Self {
+        Self {
+            network: default_network(),
+            db_path: None,
+            descriptor_type: DescriptorType::default(),
+        }
+    }
+}
+
+fn default_network() -> Network {
+    Network::Regtest
+}
+
+impl WalletConfig {
+    /// Create a configuration around a freshly generated English mnemonic
+    ///
+    /// The derivation path is written out explicitly using the default for
+    /// the descriptor type, so the generated file documents where keys live.
+    pub fn generate(
+        network: Network,
+        descriptor_type: DescriptorType,
+        word_count: usize,
+    ) -> Result<Self> {
+        if !matches!(word_count, 12 | 24) {
+            return Err(eyre!("Mnemonic word count must be 12 or 24, got {}", word_count));
+        }
+
+        let mnemonic = Mnemonic::generate_in(Language::English, word_count)
+            .map_err(|e| eyre!("Failed to generate mnemonic: {}", e))?;
+
+        Ok(Self {
+            wallet: WalletSettings {
+                network,
+                db_path: None,
+                descriptor_type,
+            },
+            keys: KeyConfiguration {
+                mnemonic: Some(mnemonic.to_string()),
+                wif: None,
+                xpub: None,
+                passphrase: None,
+                derivation_path: Some(descriptor_type.default_derivation_path(network)),
+            },
+        })
+    }
+
+    /// Write the configuration as TOML, refusing to replace an existing file unless `overwrite` is set
+    ///
+    /// The file holds the mnemonic, so on Unix it is only readable by its owner.
+    pub fn save(&self, path: &Path, overwrite: bool) -> Result<()> {
+        let contents = toml::to_string(self)?;
+        let mut options = fs::OpenOptions::new();
+        // create_new checks for an existing file and creates it in one step
+        if overwrite {
+            options.write(true).create(true).truncate(true);
+        } else {
+            options.write(true).create_new(true);
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let mut file = options.open(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => {
+                eyre!("{} already exists; pass --force to overwrite it", path.display())
+            }
+            _ => eyre::Report::new(e).wrap_err(format!("Failed to create {}", path.display())),
+        })?;
+        file.write_all(contents.as_bytes())?;
+        Ok(())
+    }
+
+    /// Configured derivation path, or the default for the descriptor type and network
+    pub fn derivation_path(&self) -> Result<DerivationPath> {
+        let path = match &self.keys.derivation_path {
+            Some(path) => path.clone(),
+            None => self
+                .wallet
+                .descriptor_type
+                .default_derivation_path(self.wallet.network),
+        };
+
+        let derivation_path = DerivationPath::from_str(&path)
+            .map_err(|e| ConfigFileError::InvalidDerivationPath(e.to_string()))?;
+        Ok(derivation_path)
+    }
+}
+
 /// Key configuration containing mnemonic and derivation path
-#[derive(Debug, Deserialize)]
+///
+/// Exactly one of `mnemonic`, `wif` and `xpub` must be set; an `xpub` config
+/// is a watch-only wallet that can show balances and addresses but not sign.
+#[derive(Debug, Deserialize, Serialize)]
 pub struct KeyConfiguration {
-    pub mnemonic: String,
-    pub derivation_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mnemonic: Option<String>,
+    /// Single private key in WIF; the wallet has one address and no derivation,
+    /// so `passphrase` and `derivation_path` are ignored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wif: Option<String>,
+    /// Account-level extended public key of a watch-only wallet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xpub: Option<String>,
+    /// Optional BIP39 passphrase ("25th word"); empty is the same as none
+    #[serde(default)]
+    pub passphrase: Option<String>,
+    /// Account derivation path; defaults to the BIP matching the descriptor type
+    #[serde(default)]
+    pub derivation_path: Option<String>,
+}
+
+/// Output descriptor used for the wallet's receiving addresses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum DescriptorType {
+    /// Native segwit v0 (`wpkh`, BIP84)
+    #[default]
+    Wpkh,
+    /// Nested segwit (`sh(wpkh)`, BIP49)
+    ShWpkh,
+    /// Taproot key-path (`tr`, BIP86)
+    Tr,
+}
+
+impl DescriptorType {
+    /// BIP44-style purpose field of the default derivation path
+    #[cfg(test)]
+    pub fn purpose(&self) -> u32 {
+        match self {
+            DescriptorType::Wpkh => 84,
+            DescriptorType::ShWpkh => 49,
+            DescriptorType::Tr => 86,
+        }
+    }
+
+    /// Default account derivation path for this descriptor type on the given network
+    pub fn default_derivation_path(&self, network: Network) -> String {
+        let is_mainnet = network == Network::Bitcoin;
+        let path = match self {
+            DescriptorType::Wpkh if is_mainnet => MAINNET_BIP84_PATH,
+            DescriptorType::Wpkh => REGTEST_BIP84_PATH,
+            DescriptorType::ShWpkh if is_mainnet => MAINNET_BIP49_PATH,
+            DescriptorType::ShWpkh => REGTEST_BIP49_PATH,
+            DescriptorType::Tr if is_mainnet => MAINNET_BIP86_PATH,
+            DescriptorType::Tr => REGTEST_BIP86_PATH,
+        };
+        path.to_string()
+    }
+
+    /// Build a ranged wallet descriptor around an account-level extended key
+    pub fn wallet_descriptor(&self, account_key: &str) -> String {
+        self.single_key_descriptor(&format!("{}/*", account_key))
+    }
+
+    /// Build a descriptor around a single key, e.g. a WIF private key
+    pub fn single_key_descriptor(&self, key: &str) -> String {
+        match self {
+            DescriptorType::Wpkh => format!("wpkh({})", key),
+            DescriptorType::ShWpkh => format!("sh(wpkh({}))", key),
+            DescriptorType::Tr => format!("tr({})", key),
+        }
+    }
 }
 
 /// Wallet factory for creating and managing Bitcoin wallets
@@ -37,15 +223,22 @@ pub struct WalletFactory;
 
 /// Represents a Bitcoin wallet with associated operations
 pub struct BitcoinWallet {
-    pub wallet: Wallet<MemoryDatabase>,
+    pub wallet: Wallet<AnyDatabase>,
+}
+
+/// Wallet balance split at a minimum number of confirmations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MinConfBalance {
+    /// Satoshis in outputs with at least the requested confirmations
+    pub spendable: u64,
+    /// Satoshis in outputs with fewer confirmations, including unconfirmed ones
+    pub maturing: u64,
 }
 
 impl WalletFactory {
     /// Load a wallet from a configuration file
     pub async fn load_wallet<P: AsRef<Path>>(config_file_path: P) -> Result<BitcoinWallet> {
-        let path = config_file_path.as_ref();
-        let wallet = Self::create_wallet_from_config(path).await?;
-        
+        let wallet = Self::create_wallet_from_config(config_file_path.as_ref()).await?;
         Ok(BitcoinWallet { wallet })
     }
 
@@ -53,22 +246,85 @@ impl WalletFactory {
     pub fn extract_keypair<P: AsRef<Path>>(config_file_path: P) -> Result<(PublicKey, SecretKey)> {
         let path = config_file_path.as_ref();
         if !path.exists() {
-            return Err(eyre!("Wallet configuration file not found: {}", path.display()));
+            return Err(ConfigFileError::NotFound(path.display().to_string()).into());
         }
 
         let config = Self::load_config(path)?;
-        let (private_key, _) = Self::derive_keys_from_config(&config)?;
+        let private_key = match &config.keys.wif {
+            Some(wif) => parse_wif(wif, config.wallet.network)?.inner,
+            None => Self::derive_keys_from_config(&config)?.0,
+        };
         let secp_context = Secp256k1::new();
         let public_key = PublicKey::from_secret_key(&secp_context, &private_key);
 
         Ok((public_key, private_key))
     }
 
+    /// Private key of the wallet's receiving address, the one [`Self::get_address`] returns
+    ///
+    /// That is the first child of the account key, or the WIF key itself.
+    pub fn extract_receiving_key<P: AsRef<Path>>(config_file_path: P) -> Result<SecretKey> {
+        let path = config_file_path.as_ref();
+        if !path.exists() {
+            return Err(ConfigFileError::NotFound(path.display().to_string()).into());
+        }
+
+        let config = Self::load_config(path)?;
+        if let Some(wif) = &config.keys.wif {
+            return Ok(parse_wif(wif, config.wallet.network)?.inner);
+        }
+        let (account_private_key, _) = Self::derive_account_key(&config)?;
+        let receiving_key = account_private_key
+            .derive_priv(&Secp256k1::new(), &[ChildNumber::Normal { index: 0 }])
+            .map_err(|e| eyre!("Failed to derive key: {}", e))?;
+        Ok(receiving_key.private_key)
+    }
+
+    /// Root extended private key of a mnemonic wallet, before any account derivation
+    ///
+    /// Fails for WIF and watch-only wallets, which have no seed.
+    pub fn extract_master_key<P: AsRef<Path>>(config_file_path: P) -> Result<ExtendedPrivKey> {
+        let path = config_file_path.as_ref();
+        if !path.exists() {
+            return Err(ConfigFileError::NotFound(path.display().to_string()).into());
+        }
+
+        let config = Self::load_config(path)?;
+        if config.keys.wif.is_some() {
+            return Err(WalletError::KeyDerivationFailed(
+                "WIF wallet has no seed to derive from".to_string(),
+            )
+            .into());
+        }
+        Self::derive_root_key(&config)
+    }
+
     /// Get wallet address for a given config file
+    ///
+    /// Fails if the derived address does not belong to the configured network.
     pub async fn get_address<P: AsRef<Path>>(config_file_path: P) -> Result<Address> {
         let wallet = Self::load_wallet(config_file_path).await?;
         let address_str = wallet.get_receiving_address()?;
-        Ok(Address::from_str(&address_str)?)
+        let address = Address::from_str(&address_str)?;
+        ensure_address_network(&address, wallet.network())?;
+        Ok(address)
+    }
+
+    /// Reveal the next `count` receiving addresses of the wallet for a given config file
+    pub async fn reveal_addresses<P: AsRef<Path>>(config_file_path: P, count: u32) -> Result<Vec<AddressInfo>> {
+        let wallet = Self::load_wallet(config_file_path).await?;
+        (0..count).map(|_| wallet.reveal_next_address()).collect()
+    }
+
+    /// Resolve a send destination given as either a Bitcoin address or a wallet config file
+    ///
+    /// The string is parsed as an address first; anything else is treated as a
+    /// config path and resolved to that wallet's receiving address.
+    pub async fn resolve_address(destination: &str) -> Result<Address> {
+        match Address::from_str(destination) {
+            Ok(address) => Ok(address),
+            Err(_) => Self::get_address(destination).await,
+        }
     }
 
     /// Get wallet balance in satoshis for a given config file
@@ -77,60 +333,144 @@ impl WalletFactory {
         wallet.get_balance_satoshis().await
     }
 
-    fn load_config(config_file_path: &Path) -> Result<WalletConfig> {
+    /// Get the balances of several wallets at once, in the order given
+    ///
+    /// Wallet syncs block on network I/O, so each runs on its own blocking
+    /// task; all of them are joined, and one wallet failing leaves the
+    /// others' results intact.
+    pub async fn get_balances_satoshis(config_file_paths: &[PathBuf]) -> Vec<Result<u64>> {
+        let runtime = tokio::runtime::Handle::current();
+        let tasks: Vec<_> = config_file_paths
+            .iter()
+            .cloned()
+            .map(|path| {
+                let runtime = runtime.clone();
+                tokio::task::spawn_blocking(move || runtime.block_on(Self::get_balance_satoshis(path)))
+            })
+            .collect();
+
+        let mut balances = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            balances.push(task.await.unwrap_or_else(|e| Err(eyre!("Balance query panicked: {}", e))));
+        }
+        balances
+    }
+
+    /// Load a watch-only wallet for the account-level extended public key `xpub`
+    ///
+    /// Addresses come from the `wpkh(<xpub>/*)` descriptor and wallet state is
+    /// kept in memory.
+    #[cfg(test)]
+    pub fn load_watch_only(xpub: &str, network: Network) -> Result<BitcoinWallet> {
+        let account_public_key = parse_xpub(xpub, network)?;
+        let wallet_descriptor = DescriptorType::Wpkh.wallet_descriptor(&account_public_key.to_string());
+        let wallet = Wallet::new(
+            &wallet_descriptor,
+            None,
+            network,
+            AnyDatabase::Memory(MemoryDatabase::default()),
+        )?;
+        Ok(BitcoinWallet { wallet })
+    }
+
+    fn load_config(config_file_path: &Path) -> error::Result<WalletConfig> {
         let config_content = fs::read_to_string(config_file_path)?;
-        Ok(toml::from_str(&config_content)?)
+        let config: WalletConfig = toml::from_str(&config_content)?;
+        let keys = &config.keys;
+        let key_sources = [keys.mnemonic.is_some(), keys.wif.is_some(), keys.xpub.is_some()];
+        match key_sources.iter().filter(|is_set| **is_set).count() {
+            1 => Ok(config),
+            0 => Err(ConfigFileError::InvalidFormat(
+                "[keys] must set one of `mnemonic`, `wif` or `xpub` (xpub makes a watch-only wallet)".to_string(),
+            )
+            .into()),
+            _ => Err(ConfigFileError::InvalidFormat(
+                "[keys] must set only one of `mnemonic`, `wif` and `xpub`".to_string(),
+            )
+            .into()),
+        }
     }
 
     fn derive_keys_from_config(config: &WalletConfig) -> Result<(SecretKey, DerivationPath)> {
-        let mnemonic = Mnemonic::parse(&config.keys.mnemonic)?;
-        let extended_key: ExtendedKey = mnemonic.into_extended_key()?;
-        let root_private_key = extended_key
-            .into_xprv(Network::Regtest)
-            .ok_or_else(|| eyre!("Invalid private key"))?;
-
-        let derivation_path: DerivationPath = config
-            .keys
-            .derivation_path
-            .parse()
-            .map_err(|e| eyre!("Invalid derivation path: {}", e))?;
+        let (account_private_key, derivation_path) = Self::derive_account_key(config)?;
+        Ok((account_private_key.private_key, derivation_path))
+    }
+
+    /// Derive the root extended private key from the configured mnemonic and passphrase
+    fn derive_root_key(config: &WalletConfig) -> Result<ExtendedPrivKey> {
+        let Some(mnemonic) = &config.keys.mnemonic else {
+            return Err(WalletError::KeyDerivationFailed(
+                "watch-only wallet has no private key".to_string(),
+            )
+            .into());
+        };
+        let mnemonic =
+            Mnemonic::parse(mnemonic).map_err(|e| ConfigFileError::InvalidMnemonic(e.to_string()))?;
+        let extended_key: ExtendedKey = (mnemonic, config.keys.passphrase.clone()).into_extended_key()?;
+        extended_key
+            .into_xprv(config.wallet.network)
+            .ok_or_else(|| eyre!("Invalid private key"))
+    }
+
+    /// Derive the account-level extended private key described by the configuration
+    fn derive_account_key(config: &WalletConfig) -> Result<(ExtendedPrivKey, DerivationPath)> {
+        let root_private_key = Self::derive_root_key(config)?;
+
+        let derivation_path = config.derivation_path()?;
 
         let secp_context = Secp256k1::new();
         let derived_private_key = root_private_key
             .derive_priv(&secp_context, &derivation_path)
             .map_err(|e| eyre!("Failed to derive key: {}", e))?;
 
-        Ok((derived_private_key.private_key, derivation_path))
+        Ok((derived_private_key, derivation_path))
     }
 
-    async fn create_wallet_from_config(config_file_path: &Path) -> Result<Wallet<MemoryDatabase>> {
+    async fn create_wallet_from_config(config_file_path: &Path) -> Result<Wallet<AnyDatabase>> {
         if !config_file_path.exists() {
-            return Err(eyre!("Wallet configuration file not found: {}", config_file_path.display()));
+            return Err(ConfigFileError::NotFound(config_file_path.display().to_string()).into());
         }
 
         let config = Self::load_config(config_file_path)?;
-        let mnemonic = Mnemonic::parse(&config.keys.mnemonic)?;
-        let extended_key: ExtendedKey = mnemonic.into_extended_key()?;
-        let root_private_key = extended_key
-            .into_xprv(Network::Regtest)
-            .ok_or_else(|| eyre!("Invalid private key"))?;
-
-        let derivation_path: DerivationPath = config
-            .keys
-            .derivation_path
-            .parse()
-            .map_err(|e| eyre!("Invalid derivation path: {}", e))?;
+        let network = config.wallet.network;
+        let descriptor_type = config.wallet.descriptor_type;
+        let wallet_descriptor = match (&config.keys.xpub, &config.keys.wif) {
+            (Some(xpub), _) => descriptor_type.wallet_descriptor(&parse_xpub(xpub, network)?.to_string()),
+            (None, Some(wif)) => descriptor_type.single_key_descriptor(&parse_wif(wif, network)?.to_wif()),
+            (None, None) => {
+                descriptor_type.wallet_descriptor(&Self::derive_account_key(&config)?.0.to_string())
+            }
+        };
+        let wallet_database =
+            Self::open_database(config_file_path, &config, &wallet_descriptor, network)?;
+        let wallet = Wallet::new(&wallet_descriptor, None, network, wallet_database)?;
 
-        let secp_context = Secp256k1::new();
-        let derived_private_key = root_private_key
-            .derive_priv(&secp_context, &derivation_path)
-            .map_err(|e| eyre!("Failed to derive key: {}", e))?;
+        Ok(wallet)
+    }
 
-        let wallet_descriptor = format!("wpkh({}/*)", derived_private_key);
-        let wallet_database = MemoryDatabase::default();
-        let wallet = Wallet::new(&wallet_descriptor, None, Network::Regtest, wallet_database)?;
+    /// Open the configured sled database, falling back to an in-memory one
+    fn open_database(
+        config_file_path: &Path,
+        config: &WalletConfig,
+        wallet_descriptor: &str,
+        network: Network,
+    ) -> Result<AnyDatabase> {
+        let Some(db_path) = &config.wallet.db_path else {
+            return Ok(AnyDatabase::Memory(MemoryDatabase::default()));
+        };
 
-        Ok(wallet)
+        let db_path = match config_file_path.parent() {
+            Some(config_dir) if db_path.is_relative() => config_dir.join(db_path),
+            _ => db_path.clone(),
+        };
+
+        // One tree per descriptor so several wallets can share a database file
+        let tree_name =
+            wallet_name_from_descriptor(wallet_descriptor, None, network, &Secp256k1::new())?;
+        let database = bdk::sled::open(&db_path)
+            .map_err(|e| eyre!("Failed to open wallet database {}: {}", db_path.display(), e))?;
+
+        Ok(AnyDatabase::Sled(database.open_tree(tree_name)?))
     }
 }
 
@@ -140,28 +480,354 @@ impl BitcoinWallet {
         WalletFactory::load_wallet(config_file_path).await
     }
 
+    /// Get the network this wallet operates on
+    pub fn network(&self) -> Network {
+        self.wallet.network()
+    }
+
     /// Get the wallet's current balance in satoshis
     pub async fn get_balance_satoshis(&self) -> Result<u64> {
-        let blockchain_client = create_bitcoin_rpc_client()?;
-        self.wallet.sync(&blockchain_client, SyncOptions::default())?;
-        
-        let balance = self.wallet.get_balance()?;
+        let balance = self.get_detailed_balance().await?;
         Ok(balance.get_total())
     }
 
+    /// Get the wallet's balance split into confirmed, pending and immature buckets
+    pub async fn get_detailed_balance(&self) -> Result<Balance> {
+        self.sync()?;
+        Ok(self.wallet.get_balance()?)
+    }
+
+    /// Get the balance with only outputs that have `min_conf` confirmations counted as spendable
+    ///
+    /// Confirmations are the synced tip height minus the output's block
+    /// height, plus one; everything below the threshold is reported as maturing.
+    pub async fn get_balance_with_min_conf(&self, min_conf: u32) -> Result<MinConfBalance> {
+        self.sync()?;
+        Ok(self.min_conf_balance_as_of_last_sync(min_conf)?)
+    }
+
+    /// Same as [`Self::get_balance_with_min_conf`] without syncing first
+    pub fn min_conf_balance_as_of_last_sync(&self, min_conf: u32) -> error::Result<MinConfBalance> {
+        let mut balance = MinConfBalance::default();
+        for utxo in self.wallet.list_unspent()? {
+            if self.get_confirmations(&utxo.outpoint.txid)? >= min_conf {
+                balance.spendable += utxo.txout.value;
+            } else {
+                balance.maturing += utxo.txout.value;
+            }
+        }
+        Ok(balance)
+    }
+
+    /// Satoshis `to` would receive if the whole balance were sent at `fee_rate`
+    ///
+    /// Builds, but never signs, the transaction `send --max` would make, so
+    /// the fee is exact rather than estimated.
+    pub fn max_sendable(&self, fee_rate: FeeRate, to: &Address) -> Result<u64> {
+        self.sync()?;
+        self.max_sendable_as_of_last_sync(fee_rate, to)
+    }
+
+    /// Same as [`Self::max_sendable`] without syncing first
+    pub fn max_sendable_as_of_last_sync(&self, fee_rate: FeeRate, to: &Address) -> Result<u64> {
+        let (psbt, _) = TransactionUtils::build_psbt_from_synced_wallet(
+            &self.wallet,
+            to.clone(),
+            None,
+            fee_rate,
+            &SendOptions::default(),
+        )?;
+        Ok(amount_paid_to(&psbt.unsigned_tx, &to.script_pubkey()))
+    }
+
+    /// List the unspent outputs controlled by the wallet
+    pub async fn list_unspent(&self) -> Result<Vec<LocalUtxo>> {
+        self.sync()?;
+        Ok(self.wallet.list_unspent()?)
+    }
+
+    /// List the wallet's transactions, oldest first with unconfirmed ones last
+    ///
+    /// With `include_raw` each entry also carries the full transaction.
+    pub async fn list_transactions(&self, include_raw: bool) -> Result<Vec<TransactionDetails>> {
+        self.sync()?;
+        let mut transactions = self.wallet.list_transactions(include_raw)?;
+        sort_by_confirmation(&mut transactions);
+        Ok(transactions)
+    }
+
+    /// Get the number of confirmations of a wallet transaction as of the last sync
+    ///
+    /// Returns 0 for unconfirmed or unknown transactions.
+    pub fn get_confirmations(&self, txid: &Txid) -> error::Result<u32> {
+        let Some(sync_time) = self.wallet.database().get_sync_time()? else {
+            return Ok(0);
+        };
+
+        let confirmation_height = self
+            .wallet
+            .get_tx(txid, false)?
+            .and_then(|details| details.confirmation_time)
+            .map(|block_time| block_time.height);
+
+        Ok(match confirmation_height {
+            Some(height) => sync_time.block_time.height.saturating_sub(height) + 1,
+            None => 0,
+        })
+    }
+
+    /// Sync the wallet with the node for its configured network, logging bdk's progress
+    ///
+    /// Addresses up to the configured gap limit past the last revealed one are
+    /// scanned, so funds sent to any handed-out address are found.
+    fn sync(&self) -> Result<()> {
+        let config = BitcoinRpcConfig::resolve(self.network());
+        self.cache_gap(config.stop_gap)?;
+        let blockchain_client = create_blockchain_backend_with_config(config)?;
+        let sync_options = SyncOptions {
+            progress: Some(Box::new(log_progress())),
+        };
+        self.wallet.sync(&blockchain_client, sync_options)?;
+        Ok(())
+    }
+
+    /// Derive and store `gap_limit` addresses past the last revealed one
+    fn cache_gap(&self, gap_limit: usize) -> Result<()> {
+        let last_revealed = self.wallet.database().get_last_index(KeychainKind::External)?;
+        let gap_limit = u32::try_from(gap_limit).unwrap_or(u32::MAX);
+        let wanted = last_revealed.map_or(0, |index| index + 1).saturating_add(gap_limit);
+        self.wallet.ensure_addresses_cached(wanted)?;
+        Ok(())
+    }
+
+
+
+    /// Export the wallet's descriptor for `keychain` with its checksum, xpub only
+    ///
+    /// Safe to share: it reveals the wallet's addresses but cannot spend.
+    pub fn export_descriptor(&self, keychain: KeychainKind) -> Result<String> {
+        let descriptor = self
+            .wallet
+            .public_descriptor(keychain)?
+            .ok_or_else(|| eyre!("Wallet has no {:?} descriptor", keychain))?;
+        Ok(descriptor.to_string())
+    }
+
+    /// Export the wallet's descriptor for `keychain` with its checksum, including the xprv
+    ///
+    /// Anyone holding the result can spend the wallet's funds.
+    pub fn export_private_descriptor(&self, keychain: KeychainKind) -> Result<String> {
+        let descriptor = self
+            .wallet
+            .public_descriptor(keychain)?
+            .ok_or_else(|| eyre!("Wallet has no {:?} descriptor", keychain))?;
+        let key_map = self
+            .wallet
+            .get_signers(keychain)
+            .as_key_map(self.wallet.secp_ctx());
+        if key_map.is_empty() {
+            return Err(WalletError::KeyDerivationFailed(
+                "watch-only wallet has no private key".to_string(),
+            )
+            .into());
+        }
+        Ok(descriptor.to_string_with_secret(&key_map))
+    }
 
     /// Get the wallet's receiving address
     pub fn get_receiving_address(&self) -> Result<String> {
         let address_info = self.wallet.get_address(bdk::wallet::AddressIndex::Peek(0))?;
         Ok(address_info.address.to_string())
     }
+
+    /// Reveal the next unused receiving address and record its index
+    ///
+    /// With a `db_path` the index survives restarts, so each call hands out a
+    /// fresh address; an in-memory wallet starts again from index 0 next run.
+    pub fn reveal_next_address(&self) -> Result<AddressInfo> {
+        let address_info = self.wallet.get_address(bdk::wallet::AddressIndex::New)?;
+        ensure_address_network(&address_info.address, self.network())?;
+        Ok(address_info)
+    }
 }
 
 
+/// Parse an extended public key, checking it was encoded for `network`
+fn parse_xpub(xpub: &str, network: Network) -> Result<ExtendedPubKey> {
+    let account_public_key = ExtendedPubKey::from_str(xpub)
+        .map_err(|e| ConfigFileError::InvalidFormat(format!("Invalid xpub: {}", e)))?;
+    // tpubs are shared by testnet, signet and regtest
+    if (account_public_key.network == Network::Bitcoin) != (network == Network::Bitcoin) {
+        return Err(ConfigFileError::InvalidFormat(format!(
+            "xpub is for {} but the wallet is on {}",
+            account_public_key.network, network
+        ))
+        .into());
+    }
+    Ok(account_public_key)
+}
+
+/// Parse a WIF private key, checking it was encoded for `network`
+fn parse_wif(wif: &str, network: Network) -> Result<PrivateKey> {
+    let private_key = PrivateKey::from_wif(wif.trim())
+        .map_err(|e| ConfigFileError::InvalidFormat(format!("Invalid WIF private key: {}", e)))?;
+    // Testnet WIFs are shared by testnet, signet and regtest
+    if (private_key.network == Network::Bitcoin) != (network == Network::Bitcoin) {
+        return Err(ConfigFileError::InvalidFormat(format!(
+            "WIF private key is for {} but the wallet is on {}",
+            private_key.network, network
+        ))
+        .into());
+    }
+    if !private_key.compressed {
+        return Err(ConfigFileError::InvalidFormat(
+            "WIF private key must be compressed to be used in segwit descriptors".to_string(),
+        )
+        .into());
+    }
+    Ok(private_key)
+}
+
+/// Order transactions by confirmation height, unconfirmed ones last
+fn sort_by_confirmation(transactions: &mut [TransactionDetails]) {
+    transactions.sort_by_key(|details| {
+        details
+            .confirmation_time
+            .as_ref()
+            .map_or((u32::MAX, u64::MAX), |block_time| {
+                (block_time.height, block_time.timestamp)
+            })
+    });
+}
 
-/// Convert BTC amount to satoshis
-pub fn btc_to_satoshis(btc_amount: f64) -> u64 {
-    (btc_amount * SATOSHIS_PER_BTC as f64) as u64
+/// Swap secret number `index` derived from a wallet's master key
+///
+/// Uses hardened derivation at `m/<SWAP_SECRET_PURPOSE>h/<index>h`, so the
+/// mnemonic alone recovers the secret and the wallet's xpubs reveal nothing
+/// about it. Fails if `index` is 2^31 or more.
+pub fn derive_swap_secret(xprv: &ExtendedPrivKey, index: u32) -> Result<SecretKey> {
+    let path = [
+        ChildNumber::from_hardened_idx(SWAP_SECRET_PURPOSE)?,
+        ChildNumber::from_hardened_idx(index)
+            .map_err(|_| eyre!("Swap secret index {} must be below 2^31", index))?,
+    ];
+    let derived = xprv
+        .derive_priv(&Secp256k1::new(), &path)
+        .map_err(|e| eyre!("Failed to derive swap secret: {}", e))?;
+    Ok(derived.private_key)
+}
+
+/// Net effect of a transaction on the wallet balance in satoshis
+pub fn net_amount(details: &TransactionDetails) -> i64 {
+    details.received as i64 - details.sent as i64
+}
+
+/// Ensure an address belongs to the expected network
+pub fn ensure_address_network(address: &Address, network: Network) -> Result<()> {
+    if !address.is_valid_for_network(network) {
+        return Err(TransactionError::InvalidAddress(format!(
+            "{} is not valid for network {}",
+            address, network
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Parse a pasted address and ensure it belongs to `network`
+///
+/// Malformed addresses and addresses for another network are both reported
+/// as [`TransactionError::InvalidAddress`].
+pub fn parse_address_for_network(address: &str, network: Network) -> Result<Address> {
+    let address = address.trim();
+    let parsed = Address::from_str(address)
+        .map_err(|e| TransactionError::InvalidAddress(format!("{}: {}", address, e)))?;
+    ensure_address_network(&parsed, network)?;
+    Ok(parsed)
+}
+
+/// Short name of an address's type (`p2pkh`, `p2sh`, `p2wpkh`, `p2wsh`, `p2tr`),
+/// or `unknown` for witness versions without a standard type
+pub fn address_type_name(address: &Address) -> String {
+    address
+        .address_type()
+        .map_or_else(|| "unknown".to_string(), |address_type| address_type.to_string())
+}
+
+/// Convert BTC amount to satoshis, rounding to the nearest satoshi
+///
+/// Rejects negative, NaN and infinite amounts and anything above the
+/// 21 million BTC supply.
+pub fn btc_to_satoshis(btc_amount: f64) -> std::result::Result<u64, String> {
+    if !btc_amount.is_finite() {
+        return Err(format!("{} BTC is not a finite number", btc_amount));
+    }
+    if btc_amount < 0.0 {
+        return Err(format!("{} BTC is negative", btc_amount));
+    }
+    let satoshis = (btc_amount * SATOSHIS_PER_BTC as f64).round();
+    if satoshis > MAX_MONEY_SAT as f64 {
+        return Err(format!(
+            "{} BTC exceeds the {} BTC supply",
+            btc_amount,
+            MAX_MONEY_SAT / SATOSHIS_PER_BTC
+        ));
+    }
+    Ok(satoshis as u64)
+}
+
+/// Parse a command-line amount into satoshis
+///
+/// Accepts `150000000sat` (or `sats`), `1.5btc`, or a bare number taken as
+/// BTC; the unit is case-insensitive and may be separated by a space. BTC
+/// amounts are read as exact decimals, so more than 8 decimal places is an
+/// error rather than being rounded.
+pub fn parse_amount(amount: &str) -> std::result::Result<u64, String> {
+    let normalized = amount.trim().to_ascii_lowercase();
+    let (number, is_sats) = match normalized.strip_suffix("sats").or_else(|| normalized.strip_suffix("sat")) {
+        Some(number) => (number.trim_end(), true),
+        None => (normalized.strip_suffix("btc").unwrap_or(&normalized).trim_end(), false),
+    };
+
+    let satoshis = if is_sats {
+        number
+            .parse::<u64>()
+            .map_err(|_| format!("{:?} is not a whole number of satoshis", amount.trim()))?
+    } else {
+        parse_btc_decimal(number).ok_or_else(|| {
+            format!(
+                "{:?} is not a BTC amount with at most 8 decimal places (use e.g. 1.5btc or 150000000sat)",
+                amount.trim()
+            )
+        })?
+    };
+
+    if satoshis > MAX_MONEY_SAT {
+        return Err(format!(
+            "{} exceeds the {} BTC supply",
+            amount.trim(),
+            MAX_MONEY_SAT / SATOSHIS_PER_BTC
+        ));
+    }
+    Ok(satoshis)
+}
+
+/// Satoshis in a plain decimal BTC amount like `1.5` or `.001`, without floats
+fn parse_btc_decimal(number: &str) -> Option<u64> {
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let is_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if (whole.is_empty() && fraction.is_empty())
+        || fraction.len() > 8
+        || !is_digits(whole)
+        || !is_digits(fraction)
+    {
+        return None;
+    }
+
+    let whole_sats = if whole.is_empty() { 0 } else { whole.parse::<u64>().ok()? }
+        .checked_mul(SATOSHIS_PER_BTC)?;
+    let fraction_sats = format!("{:0<8}", fraction).parse::<u64>().ok()?;
+    whole_sats.checked_add(fraction_sats)
 }
 
 /// Format satoshis to a clean BTC string representation
@@ -175,3 +841,835 @@ pub fn format_satoshis_to_btc(satoshis: u64) -> String {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk::bitcoin::{AddressType, OutPoint, PackedLockTime, Transaction, TxIn, TxOut};
+    use bdk::database::{BatchOperations, SyncTime};
+    use crate::transaction::InputSignature;
+    use bdk::bitcoin::psbt::PartiallySignedTransaction;
+    use bdk::wallet::AddressIndex;
+    use bdk::{BlockTime, FeeRate, KeychainKind, LocalUtxo, SignOptions, TransactionDetails};
+    use crate::error::TransactionError;
+    use crate::transaction::{CoinSelection, InputSelection, SendOptions, TransactionUtils, drain_fee};
+
+    #[test]
+    fn parsed_addresses_report_their_type() {
+        let cases = [
+            ("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2", "p2pkh"),
+            ("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", "p2sh"),
+            ("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", "p2wpkh"),
+            ("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0", "p2tr"),
+        ];
+        for (address, expected_type) in cases {
+            let parsed = parse_address_for_network(address, Network::Bitcoin).unwrap();
+            assert_eq!(address_type_name(&parsed), expected_type, "{}", address);
+        }
+    }
+
+    #[test]
+    fn invalid_or_wrong_network_addresses_are_rejected() {
+        for (address, network) in [
+            ("not-an-address", Network::Regtest),
+            ("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", Network::Regtest),
+            ("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080", Network::Bitcoin),
+        ] {
+            let error = parse_address_for_network(address, network).unwrap_err();
+            assert!(
+                matches!(
+                    error.downcast_ref::<TransactionError>(),
+                    Some(TransactionError::InvalidAddress(_))
+                ),
+                "{}: {}",
+                address,
+                error
+            );
+        }
+    }
+
+    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn write_wallet_config(dir: &Path, wallet_section: &str) -> PathBuf {
+        write_wallet_config_with_keys(dir, wallet_section, "derivation_path = \"m/84h/1h/0h\"")
+    }
+
+    fn write_wallet_config_with_keys(dir: &Path, wallet_section: &str, extra_keys: &str) -> PathBuf {
+        let config_path = dir.join("wallet.toml");
+        let config = format!(
+            "[wallet]\n{}\n\n[keys]\nmnemonic = \"{}\"\n{}\n",
+            wallet_section, TEST_MNEMONIC, extra_keys
+        );
+        fs::write(&config_path, config).unwrap();
+        config_path
+    }
+
+    #[tokio::test]
+    async fn balances_report_each_wallet_failure_separately() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = [dir.path().join("admin.toml"), dir.path().join("maker.toml")];
+        fs::write(&paths[1], "not a wallet config").unwrap();
+
+        let balances = WalletFactory::get_balances_satoshis(&paths).await;
+        assert_eq!(balances.len(), 2);
+        let first = balances[0].as_ref().unwrap_err();
+        assert!(
+            matches!(first.downcast_ref::<ConfigFileError>(), Some(ConfigFileError::NotFound(_))),
+            "{:?}",
+            first
+        );
+        assert!(balances[1].is_err());
+    }
+
+    #[test]
+    fn swap_secrets_are_derived_from_the_seed_apart_from_account_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config(dir.path(), "");
+        let master_key = WalletFactory::extract_master_key(&config_path).unwrap();
+
+        let first = derive_swap_secret(&master_key, 7).unwrap();
+        assert_eq!(first, derive_swap_secret(&master_key, 7).unwrap());
+        assert_ne!(first, derive_swap_secret(&master_key, 8).unwrap());
+        assert_ne!(first, WalletFactory::extract_keypair(&config_path).unwrap().1);
+        assert!(derive_swap_secret(&master_key, 1 << 31).is_err());
+
+        let secret_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let wif = PrivateKey::new(secret_key, Network::Regtest).to_wif();
+        let wif_path = dir.path().join("wif.toml");
+        fs::write(&wif_path, format!("[wallet]\n\n[keys]\nwif = \"{}\"\n", wif)).unwrap();
+        assert!(WalletFactory::extract_master_key(&wif_path).is_err());
+    }
+
+    #[tokio::test]
+    async fn generated_config_loads_and_is_not_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("new.toml");
+
+        let config = WalletConfig::generate(Network::Testnet, DescriptorType::Tr, 24).unwrap();
+        assert_eq!(config.keys.mnemonic.as_ref().unwrap().split_whitespace().count(), 24);
+        assert_eq!(config.keys.derivation_path.as_deref(), Some("m/86h/1h/0h"));
+        config.save(&config_path, false).unwrap();
+
+        let address = WalletFactory::get_address(&config_path).await.unwrap();
+        assert_eq!(address.network, Network::Testnet);
+        assert_eq!(address.address_type(), Some(AddressType::P2tr));
+
+        let replacement = WalletConfig::generate(Network::Testnet, DescriptorType::Tr, 12).unwrap();
+        let err = replacement.save(&config_path, false).unwrap_err();
+        assert!(err.to_string().contains("pass --force"), "{}", err);
+        let saved = fs::read_to_string(&config_path).unwrap();
+        assert!(saved.contains(config.keys.mnemonic.as_deref().unwrap()));
+        replacement.save(&config_path, true).unwrap();
+        assert!(WalletConfig::generate(Network::Testnet, DescriptorType::Tr, 15).is_err());
+    }
+
+    async fn address_with_keys(extra_keys: &str) -> Address {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config_with_keys(dir.path(), "", extra_keys);
+        WalletFactory::get_address(&config_path).await.unwrap()
+    }
+
+    /// Record a UTXO confirmed at `confirmation_height` in the wallet's
+    /// database as a sync to tip 200 would, returning its outpoint
+    fn cache_confirmed_utxo(wallet: &BitcoinWallet, value: u64, confirmation_height: u32) -> OutPoint {
+        let address = wallet.wallet.get_address(AddressIndex::Peek(0)).unwrap().address;
+        let transaction = Transaction {
+            version: 2,
+            lock_time: PackedLockTime(0),
+            // A non-null previous output keeps the transaction from looking like a coinbase
+            input: vec![TxIn {
+                previous_output: OutPoint {
+                    vout: 0,
+                    ..OutPoint::null()
+                },
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value,
+                script_pubkey: address.script_pubkey(),
+            }],
+        };
+        let txid = transaction.txid();
+
+        let mut database = match &*wallet.wallet.database() {
+            AnyDatabase::Sled(tree) => tree.clone(),
+            _ => panic!("expected a sled-backed wallet"),
+        };
+        database
+            .set_sync_time(SyncTime {
+                block_time: BlockTime { height: 200, timestamp: 0 },
+            })
+            .unwrap();
+        database
+            .set_tx(&TransactionDetails {
+                transaction: Some(transaction.clone()),
+                txid,
+                received: value,
+                sent: 0,
+                fee: Some(0),
+                confirmation_time: Some(BlockTime { height: confirmation_height, timestamp: 0 }),
+            })
+            .unwrap();
+        database
+            .set_utxo(&LocalUtxo {
+                outpoint: OutPoint::new(txid, 0),
+                txout: transaction.output[0].clone(),
+                keychain: KeychainKind::External,
+                is_spent: false,
+            })
+            .unwrap();
+        database.flush().unwrap();
+        OutPoint::new(txid, 0)
+    }
+
+    #[tokio::test]
+    async fn sled_database_keeps_cached_utxos_between_loads() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config(dir.path(), "db_path = \"wallet.db\"");
+
+        {
+            let wallet = WalletFactory::load_wallet(&config_path).await.unwrap();
+            cache_confirmed_utxo(&wallet, 50_000, 150);
+        }
+
+        // A fresh load sees the cached UTXO without talking to a node
+        let reloaded = WalletFactory::load_wallet(&config_path).await.unwrap();
+        assert_eq!(reloaded.wallet.list_unspent().unwrap().len(), 1);
+        assert_eq!(reloaded.wallet.get_balance().unwrap().confirmed, 50_000);
+        assert!(dir.path().join("wallet.db").exists());
+    }
+
+    #[tokio::test]
+    async fn revealed_addresses_advance_and_widen_the_scanned_gap() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config(dir.path(), "db_path = \"wallet.db\"");
+
+        let first = WalletFactory::reveal_addresses(&config_path, 3).await.unwrap();
+        assert_eq!(first.iter().map(|info| info.index).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(
+            first[0].address,
+            WalletFactory::get_address(&config_path).await.unwrap(),
+            "index 0 is the address the plain command shows"
+        );
+
+        // The revealed index is persisted, so the next run hands out fresh addresses
+        let wallet = WalletFactory::load_wallet(&config_path).await.unwrap();
+        let next = wallet.reveal_next_address().unwrap();
+        assert_eq!(next.index, 3);
+        assert!(first.iter().all(|info| info.address != next.address));
+
+        // Sync watches a whole gap past index 3, beyond bdk's own batch of 100
+        let database = wallet.wallet.database();
+        assert!(database.get_script_pubkey_from_path(KeychainKind::External, 203).unwrap().is_none());
+        drop(database);
+        wallet.cache_gap(200).unwrap();
+        let database = wallet.wallet.database();
+        assert!(database.get_script_pubkey_from_path(KeychainKind::External, 203).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn min_conf_balance_reports_shallow_outputs_as_maturing() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config(dir.path(), "db_path = \"wallet.db\"");
+        let wallet = WalletFactory::load_wallet(&config_path).await.unwrap();
+        // 51 and 2 confirmations at the synced tip of 200
+        cache_confirmed_utxo(&wallet, 50_000, 150);
+        cache_confirmed_utxo(&wallet, 20_000, 199);
+
+        assert_eq!(
+            wallet.min_conf_balance_as_of_last_sync(6).unwrap(),
+            MinConfBalance { spendable: 50_000, maturing: 20_000 }
+        );
+        assert_eq!(
+            wallet.min_conf_balance_as_of_last_sync(2).unwrap(),
+            MinConfBalance { spendable: 70_000, maturing: 0 }
+        );
+        assert_eq!(
+            wallet.min_conf_balance_as_of_last_sync(52).unwrap(),
+            MinConfBalance { spendable: 0, maturing: 70_000 }
+        );
+    }
+
+    #[tokio::test]
+    async fn largest_first_spends_different_inputs_than_the_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config(dir.path(), "db_path = \"wallet.db\"");
+        let wallet = WalletFactory::load_wallet(&config_path).await.unwrap();
+        cache_confirmed_utxo(&wallet, 100_000, 150);
+        cache_confirmed_utxo(&wallet, 60_000, 160);
+        cache_confirmed_utxo(&wallet, 30_000, 170);
+        let recipient = wallet.wallet.get_address(AddressIndex::Peek(5)).unwrap().address;
+
+        let input_values = |coin_selection: CoinSelection| -> Vec<u64> {
+            // At 1 sat/vB the 30,000 sat UTXO pays this amount and its fee
+            // with no change, which branch and bound always finds
+            let (psbt, _) = TransactionUtils::build_psbt_from_synced_wallet(
+                &wallet.wallet,
+                recipient.clone(),
+                Some(29_875),
+                FeeRate::from_sat_per_vb(1.0),
+                &SendOptions {
+                    inputs: InputSelection {
+                        coin_selection,
+                        ..InputSelection::default()
+                    },
+                    ..SendOptions::default()
+                },
+            )
+            .unwrap();
+            psbt.inputs
+                .iter()
+                .map(|input| input.witness_utxo.as_ref().unwrap().value)
+                .collect()
+        };
+
+        assert_eq!(input_values(CoinSelection::Default), vec![30_000]);
+        assert_eq!(input_values(CoinSelection::LargestFirst), vec![100_000]);
+    }
+
+    #[tokio::test]
+    async fn psbt_inputs_are_signable_only_by_the_owning_wallet() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config(dir.path(), "db_path = \"wallet.db\"");
+        let wallet = WalletFactory::load_wallet(&config_path).await.unwrap();
+        cache_confirmed_utxo(&wallet, 100_000, 150);
+        let other_dir = tempfile::tempdir().unwrap();
+        let other_path =
+            write_wallet_config_with_keys(other_dir.path(), "", "derivation_path = \"m/84h/1h/1h\"");
+        let other = WalletFactory::load_wallet(&other_path).await.unwrap();
+
+        let recipient = other.wallet.get_address(AddressIndex::Peek(0)).unwrap().address;
+        let (mut psbt, _) = TransactionUtils::build_psbt_from_synced_wallet(
+            &wallet.wallet,
+            recipient,
+            Some(20_000),
+            FeeRate::from_sat_per_vb(1.0),
+            &SendOptions::default(),
+        )
+        .unwrap();
+
+        fn signable(wallet: &BitcoinWallet, psbt: &PartiallySignedTransaction) -> Vec<(usize, InputSignature)> {
+            TransactionUtils::signable_inputs(&wallet.wallet, psbt).unwrap()
+        }
+        assert_eq!(signable(&wallet, &psbt), [(0, InputSignature::Missing)]);
+        assert!(signable(&other, &psbt).is_empty());
+
+        assert!(TransactionUtils::sign_psbt(&wallet.wallet, &mut psbt).unwrap());
+        assert_eq!(signable(&wallet, &psbt), [(0, InputSignature::Present)]);
+        assert!(signable(&other, &psbt).is_empty());
+    }
+
+    #[tokio::test]
+    async fn selected_utxos_are_the_only_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config(dir.path(), "db_path = \"wallet.db\"");
+        let wallet = WalletFactory::load_wallet(&config_path).await.unwrap();
+        cache_confirmed_utxo(&wallet, 100_000, 150);
+        let small = cache_confirmed_utxo(&wallet, 30_000, 170);
+        let recipient = wallet.wallet.get_address(AddressIndex::Peek(5)).unwrap().address;
+        let only_small = SendOptions {
+            inputs: InputSelection {
+                utxos: vec![small],
+                ..InputSelection::default()
+            },
+            ..SendOptions::default()
+        };
+        let build = |amount_satoshis: Option<u64>| {
+            TransactionUtils::build_psbt_from_synced_wallet(
+                &wallet.wallet,
+                recipient.clone(),
+                amount_satoshis,
+                FeeRate::from_sat_per_vb(1.0),
+                &only_small,
+            )
+        };
+
+        let (psbt, _) = build(Some(20_000)).unwrap();
+        let spent: Vec<OutPoint> = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|input| input.previous_output)
+            .collect();
+        assert_eq!(spent, vec![small]);
+
+        // Sending everything drains the chosen UTXO, not the whole wallet
+        let (psbt, details) = build(None).unwrap();
+        assert_eq!(psbt.unsigned_tx.input.len(), 1);
+        assert_eq!(details.sent, 30_000);
+
+        let err = build(Some(50_000)).err().unwrap();
+        match err.downcast_ref::<TransactionError>() {
+            Some(TransactionError::InsufficientFunds(message)) => {
+                assert!(message.contains("30000 sats"), "{}", message);
+                assert!(message.contains("short"), "{}", message);
+            }
+            _ => panic!("expected insufficient funds, got {:?}", err),
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_signal_rbf_so_their_fee_can_be_bumped() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config(dir.path(), "db_path = \"wallet.db\"");
+        let wallet = WalletFactory::load_wallet(&config_path).await.unwrap();
+        let funding = cache_confirmed_utxo(&wallet, 100_000, 150);
+        let recipient = Address::from_str("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080").unwrap();
+
+        let (mut psbt, details) = TransactionUtils::build_psbt_from_synced_wallet(
+            &wallet.wallet,
+            recipient,
+            Some(40_000),
+            FeeRate::from_sat_per_vb(2.0),
+            &SendOptions::default(),
+        )
+        .unwrap();
+        assert!(psbt.unsigned_tx.input.iter().all(|input| input.sequence.0 <= 0xFFFFFFFD));
+        assert!(wallet.wallet.sign(&mut psbt, SignOptions::default()).unwrap());
+        let original = psbt.extract_tx();
+
+        // Record the send as unconfirmed, as a sync would after broadcasting it
+        let mut database = match &*wallet.wallet.database() {
+            AnyDatabase::Sled(tree) => tree.clone(),
+            _ => panic!("expected a sled-backed wallet"),
+        };
+        database
+            .set_tx(&TransactionDetails {
+                transaction: Some(original.clone()),
+                confirmation_time: None,
+                ..details.clone()
+            })
+            .unwrap();
+
+        let mut tx_builder = wallet.wallet.build_fee_bump(original.txid()).unwrap();
+        tx_builder.fee_rate(FeeRate::from_sat_per_vb(5.0));
+        let (replacement, replacement_details) = tx_builder.finish().unwrap();
+        assert_eq!(replacement.unsigned_tx.input[0].previous_output, funding);
+        assert!(replacement_details.fee.unwrap() > details.fee.unwrap());
+    }
+
+    #[tokio::test]
+    async fn max_sendable_is_the_balance_less_the_drain_fee() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config(dir.path(), "db_path = \"wallet.db\"");
+        let wallet = WalletFactory::load_wallet(&config_path).await.unwrap();
+        let recipient = Address::from_str("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080").unwrap();
+        let fee_rate = FeeRate::from_sat_per_vb(2.0);
+
+        let err = wallet.max_sendable_as_of_last_sync(fee_rate, &recipient).unwrap_err();
+        assert!(
+            matches!(err.downcast_ref::<TransactionError>(), Some(TransactionError::InsufficientFunds(_))),
+            "{:?}",
+            err
+        );
+
+        cache_confirmed_utxo(&wallet, 100_000, 150);
+        let max = wallet.max_sendable_as_of_last_sync(fee_rate, &recipient).unwrap();
+        let (_, details) = TransactionUtils::build_psbt_from_synced_wallet(
+            &wallet.wallet,
+            recipient.clone(),
+            None,
+            fee_rate,
+            &SendOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(max, 100_000 - details.fee.unwrap());
+        assert!(max < 100_000);
+    }
+
+    #[tokio::test]
+    async fn drains_whose_balance_goes_to_fees_fail_with_the_exact_shortfall() {
+        let recipient = Address::from_str("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080").unwrap();
+        let fee_rate = FeeRate::from_sat_per_vb(10.0);
+        let dust_limit = recipient.script_pubkey().dust_value().to_sat();
+        let dir = tempfile::tempdir().unwrap();
+        let wallet_holding = |name: &str, value: u64| {
+            let wallet_dir = dir.path().join(name);
+            fs::create_dir(&wallet_dir).unwrap();
+            let config_path = write_wallet_config(&wallet_dir, "db_path = \"wallet.db\"");
+            async move {
+                let wallet = WalletFactory::load_wallet(&config_path).await.unwrap();
+                cache_confirmed_utxo(&wallet, value, 150);
+                wallet
+            }
+        };
+        let drain = |wallet: &BitcoinWallet| {
+            TransactionUtils::build_psbt_from_synced_wallet(
+                &wallet.wallet,
+                recipient.clone(),
+                None,
+                fee_rate,
+                &SendOptions::default(),
+            )
+        };
+
+        // A single small UTXO is worth less than the fee of spending it
+        let wallet = wallet_holding("small", 1_000).await;
+        let utxos = wallet.wallet.list_unspent().unwrap();
+        let fee = drain_fee(&wallet.wallet, &utxos, &recipient.script_pubkey(), &[], fee_rate).unwrap();
+        assert!(fee > 1_000);
+        let err = drain(&wallet).err().unwrap();
+        match err.downcast_ref::<TransactionError>() {
+            Some(TransactionError::InsufficientFunds(message)) => assert!(
+                message.contains(&format!("({} sats short)", fee + dust_limit - 1_000)),
+                "{}",
+                message
+            ),
+            _ => panic!("expected insufficient funds, got {:?}", err),
+        }
+
+        // The computed fee is bdk's: one sat less than fee plus dust fails in bdk too
+        let wallet = wallet_holding("just_short", fee + dust_limit - 1).await;
+        let err = drain(&wallet).err().unwrap();
+        assert!(err.to_string().contains("(1 sats short)"), "{}", err);
+        let mut tx_builder = wallet.wallet.build_tx();
+        tx_builder
+            .fee_rate(fee_rate)
+            .drain_wallet()
+            .drain_to(recipient.script_pubkey());
+        assert!(matches!(tx_builder.finish(), Err(bdk::Error::InsufficientFunds { .. })));
+
+        let wallet = wallet_holding("exact", fee + dust_limit).await;
+        let (psbt, details) = drain(&wallet).unwrap();
+        assert_eq!(psbt.unsigned_tx.output[0].value, dust_limit);
+        assert_eq!(details.fee, Some(fee));
+    }
+
+    #[tokio::test]
+    async fn change_goes_back_to_the_wallet_unless_redirected() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config(dir.path(), "db_path = \"wallet.db\"");
+        let wallet = WalletFactory::load_wallet(&config_path).await.unwrap();
+        cache_confirmed_utxo(&wallet, 100_000, 150);
+        let recipient = Address::from_str("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080").unwrap();
+        let change_address =
+            Address::from_str("bcrt1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qzf4jry").unwrap();
+        let send_with = |options: &SendOptions| {
+            let (psbt, _) = TransactionUtils::build_psbt_from_synced_wallet(
+                &wallet.wallet,
+                recipient.clone(),
+                Some(20_000),
+                FeeRate::from_sat_per_vb(1.0),
+                options,
+            )
+            .unwrap();
+            psbt.unsigned_tx
+        };
+
+        let transaction = send_with(&SendOptions::default());
+        let change =
+            TransactionUtils::find_change_outpoint(&wallet.wallet, &transaction, &recipient.script_pubkey(), None)
+                .unwrap()
+                .unwrap();
+        assert_eq!(change.txid, transaction.txid());
+        let change_script = &transaction.output[change.vout as usize].script_pubkey;
+        assert!(wallet.wallet.is_mine(change_script).unwrap());
+
+        let redirected = SendOptions {
+            change_to: Some(change_address.clone()),
+            ..SendOptions::default()
+        };
+        let transaction = send_with(&redirected);
+        let change = TransactionUtils::find_change_outpoint(
+            &wallet.wallet,
+            &transaction,
+            &recipient.script_pubkey(),
+            Some(&change_address),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(transaction.output[change.vout as usize].script_pubkey, change_address.script_pubkey());
+        assert!(transaction.output.iter().all(|output| !wallet.wallet.is_mine(&output.script_pubkey).unwrap()));
+    }
+
+    #[tokio::test]
+    async fn descriptor_type_selects_address_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let cases = [
+            ("wpkh", AddressType::P2wpkh),
+            ("sh-wpkh", AddressType::P2sh),
+            ("tr", AddressType::P2tr),
+        ];
+
+        for (descriptor_type, expected_type) in cases {
+            let config_path = write_wallet_config(
+                dir.path(),
+                &format!("descriptor_type = \"{}\"", descriptor_type),
+            );
+            let address = WalletFactory::get_address(&config_path).await.unwrap();
+            assert_eq!(address.address_type(), Some(expected_type), "{}", descriptor_type);
+        }
+    }
+
+    #[tokio::test]
+    async fn omitted_derivation_path_matches_the_spelled_out_default() {
+        for (descriptor_type, default_path) in [("wpkh", "m/84h/1h/0h"), ("tr", "m/86h/1h/0h")] {
+            let dir = tempfile::tempdir().unwrap();
+            let wallet_section = format!("descriptor_type = \"{}\"", descriptor_type);
+            let implicit = write_wallet_config_with_keys(dir.path(), &wallet_section, "");
+            let implicit_address = WalletFactory::get_address(&implicit).await.unwrap();
+
+            let explicit = write_wallet_config_with_keys(
+                dir.path(),
+                &wallet_section,
+                &format!("derivation_path = \"{}\"", default_path),
+            );
+            let explicit_address = WalletFactory::get_address(&explicit).await.unwrap();
+
+            assert_eq!(implicit_address, explicit_address, "{}", descriptor_type);
+        }
+    }
+
+    #[test]
+    fn default_derivation_path_uses_coin_type_0_only_on_mainnet() {
+        let cases = [
+            (DescriptorType::Wpkh, Network::Bitcoin, MAINNET_BIP84_PATH),
+            (DescriptorType::Wpkh, Network::Testnet, REGTEST_BIP84_PATH),
+            (DescriptorType::ShWpkh, Network::Bitcoin, MAINNET_BIP49_PATH),
+            (DescriptorType::ShWpkh, Network::Signet, REGTEST_BIP49_PATH),
+            (DescriptorType::Tr, Network::Bitcoin, MAINNET_BIP86_PATH),
+            (DescriptorType::Tr, Network::Regtest, REGTEST_BIP86_PATH),
+        ];
+        for (descriptor_type, network, expected) in cases {
+            assert_eq!(descriptor_type.default_derivation_path(network), expected);
+            // Every default must parse and keep the descriptor type's purpose
+            let path: DerivationPath = expected.parse().unwrap();
+            assert_eq!(
+                path.into_iter().next(),
+                Some(&ChildNumber::Hardened { index: descriptor_type.purpose() })
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn passphrase_changes_derived_address() {
+        let path = "derivation_path = \"m/84h/1h/0h\"";
+        let without_passphrase = address_with_keys(path).await;
+        let empty_passphrase = address_with_keys(&format!("{}\npassphrase = \"\"", path)).await;
+        let first = address_with_keys(&format!("{}\npassphrase = \"first\"", path)).await;
+        let second = address_with_keys(&format!("{}\npassphrase = \"second\"", path)).await;
+
+        // Known address for the admin wallet without a passphrase
+        assert_eq!(
+            without_passphrase.to_string(),
+            "bcrt1qmflavul2k53n45lz360278cfgr4nzahh2f2f43"
+        );
+        assert_eq!(without_passphrase, empty_passphrase);
+        assert_ne!(without_passphrase, first);
+        assert_ne!(first, second);
+    }
+
+    #[tokio::test]
+    async fn wallet_without_db_path_uses_memory_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config(dir.path(), "network = \"regtest\"");
+
+        let wallet = WalletFactory::load_wallet(&config_path).await.unwrap();
+        assert!(matches!(&*wallet.wallet.database(), AnyDatabase::Memory(_)));
+    }
+
+    #[tokio::test]
+    async fn resolve_address_accepts_raw_addresses() {
+        let raw = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+        let address = WalletFactory::resolve_address(raw).await.unwrap();
+        assert_eq!(address.to_string(), raw);
+        assert!(ensure_address_network(&address, Network::Regtest).is_ok());
+
+        let err = ensure_address_network(&address, Network::Bitcoin).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::InvalidAddress(_))
+        ));
+
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config(dir.path(), "network = \"regtest\"");
+        let from_config = WalletFactory::resolve_address(config_path.to_str().unwrap())
+            .await
+            .unwrap();
+        assert!(from_config.is_valid_for_network(Network::Regtest));
+    }
+
+    #[test]
+    fn btc_to_satoshis_rounds_and_rejects_invalid_amounts() {
+        assert_eq!(btc_to_satoshis(0.0), Ok(0));
+        assert_eq!(btc_to_satoshis(1.5), Ok(150_000_000));
+        assert_eq!(btc_to_satoshis(0.000000009), Ok(1));
+        assert_eq!(btc_to_satoshis(0.000000004), Ok(0));
+        // 0.29 * 1e8 is 28999999.999999996 in floating point
+        assert_eq!(btc_to_satoshis(0.29), Ok(29_000_000));
+        assert_eq!(btc_to_satoshis(21_000_000.0), Ok(MAX_MONEY_SAT));
+
+        assert!(btc_to_satoshis(21_000_000.00000001).is_err());
+        assert!(btc_to_satoshis(1e20).is_err());
+        assert!(btc_to_satoshis(-0.1).is_err());
+        assert!(btc_to_satoshis(f64::NAN).is_err());
+        assert!(btc_to_satoshis(f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn amounts_parse_as_btc_or_sats_without_rounding() {
+        assert_eq!(parse_amount("1.5"), Ok(150_000_000));
+        assert_eq!(parse_amount("1.5btc"), Ok(150_000_000));
+        assert_eq!(parse_amount("0.29 BTC"), Ok(29_000_000));
+        assert_eq!(parse_amount(".00000001"), Ok(1));
+        assert_eq!(parse_amount("2."), Ok(200_000_000));
+        assert_eq!(parse_amount("150000000sat"), Ok(150_000_000));
+        assert_eq!(parse_amount("546 sats"), Ok(546));
+        assert_eq!(parse_amount("21000000"), Ok(MAX_MONEY_SAT));
+
+        for invalid in [
+            "",
+            ".",
+            "btc",
+            "0.000000001",
+            "1.5sat",
+            "-1",
+            "1e3",
+            "1,5",
+            "21000000.00000001",
+            "2100000000000001sat",
+            "99999999999999999999sat",
+        ] {
+            assert!(parse_amount(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn transactions_sort_by_confirmation_with_pending_last() {
+        let details = |byte: u8, height: Option<u32>| TransactionDetails {
+            transaction: None,
+            txid: Txid::from_hash(bdk::bitcoin::hashes::Hash::from_inner([byte; 32])),
+            received: 1_000,
+            sent: 3_000,
+            fee: Some(200),
+            confirmation_time: height.map(|height| BlockTime {
+                height,
+                timestamp: u64::from(height) * 600,
+            }),
+        };
+        let mut transactions = vec![details(1, None), details(2, Some(300)), details(3, Some(120))];
+
+        sort_by_confirmation(&mut transactions);
+
+        let order: Vec<u8> = transactions.iter().map(|tx| tx.txid[0]).collect();
+        assert_eq!(order, vec![3, 2, 1]);
+        assert_eq!(net_amount(&transactions[0]), -2_000);
+    }
+
+    #[test]
+    fn config_errors_keep_their_category_and_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.toml");
+        fs::write(&path, "[wallet\nnetwork = ").unwrap();
+        let err = WalletFactory::load_config(&path).unwrap_err();
+        assert!(matches!(err, error::BitcoinWalletError::Toml(_)), "{:?}", err);
+        assert_eq!(error::exit_code(&eyre::Report::new(err)), error::EXIT_CONFIG);
+
+        let err = WalletFactory::load_config(&dir.path().join("missing.toml")).unwrap_err();
+        assert!(matches!(err, error::BitcoinWalletError::Io(_)), "{:?}", err);
+
+        fs::write(&path, "[wallet]\nnetwork = \"regtest\"\n\n[keys]\n").unwrap();
+        let err = WalletFactory::load_config(&path).unwrap_err();
+        assert!(
+            matches!(err, error::BitcoinWalletError::ConfigFile(ConfigFileError::InvalidFormat(_))),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn xpub_config_is_watch_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let mnemonic_path = write_wallet_config(dir.path(), "network = \"regtest\"");
+        let config = WalletFactory::load_config(&mnemonic_path).unwrap();
+        let (account_private_key, _) = WalletFactory::derive_account_key(&config).unwrap();
+        let xpub = ExtendedPubKey::from_priv(&Secp256k1::new(), &account_private_key).to_string();
+
+        let watch_only_dir = tempfile::tempdir().unwrap();
+        let watch_only_path = watch_only_dir.path().join("watch.toml");
+        fs::write(
+            &watch_only_path,
+            format!("[wallet]\nnetwork = \"regtest\"\n\n[keys]\nxpub = \"{}\"\n", xpub),
+        )
+        .unwrap();
+
+        let expected = WalletFactory::get_address(&mnemonic_path).await.unwrap();
+        assert_eq!(WalletFactory::get_address(&watch_only_path).await.unwrap(), expected);
+        let from_xpub = WalletFactory::load_watch_only(&xpub, Network::Regtest).unwrap();
+        assert_eq!(from_xpub.get_receiving_address().unwrap(), expected.to_string());
+
+        let err = WalletFactory::extract_keypair(&watch_only_path).unwrap_err();
+        assert!(err.to_string().contains("watch-only wallet has no private key"), "{}", err);
+        assert!(WalletFactory::load_watch_only(&xpub, Network::Bitcoin).is_err());
+    }
+
+    fn write_wif_config(dir: &Path, network: &str, descriptor_type: &str, wif: &str) -> PathBuf {
+        let config_path = dir.join(format!("wif-{}-{}.toml", network, descriptor_type));
+        fs::write(
+            &config_path,
+            format!(
+                "[wallet]\nnetwork = \"{}\"\ndescriptor_type = \"{}\"\n\n[keys]\nwif = \"{}\"\n",
+                network, descriptor_type, wif
+            ),
+        )
+        .unwrap();
+        config_path
+    }
+
+    #[tokio::test]
+    async fn wif_config_uses_the_key_directly() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let testnet_wif = PrivateKey::new(secret_key, Network::Testnet).to_wif();
+        let secp = Secp256k1::new();
+        let public_key = bdk::bitcoin::PublicKey::new(secret_key.public_key(&secp));
+
+        let config_path = write_wif_config(dir.path(), "regtest", "wpkh", &testnet_wif);
+        let address = WalletFactory::get_address(&config_path).await.unwrap();
+        assert_eq!(address, Address::p2wpkh(&public_key, Network::Regtest).unwrap());
+        assert_eq!(
+            WalletFactory::extract_keypair(&config_path).unwrap(),
+            (public_key.inner, secret_key)
+        );
+
+        let taproot_path = write_wif_config(dir.path(), "regtest", "tr", &testnet_wif);
+        let taproot_address = WalletFactory::get_address(&taproot_path).await.unwrap();
+        assert_eq!(taproot_address.address_type(), Some(AddressType::P2tr));
+    }
+
+    #[tokio::test]
+    async fn wif_for_another_network_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        let mainnet_wif = PrivateKey::new(secret_key, Network::Bitcoin).to_wif();
+
+        let config_path = write_wif_config(dir.path(), "regtest", "wpkh", &mainnet_wif);
+        let err = WalletFactory::load_wallet(&config_path).await.err().unwrap();
+        assert!(err.to_string().contains("WIF private key is for bitcoin"), "{}", err);
+        assert!(WalletFactory::extract_keypair(&config_path).is_err());
+    }
+
+    #[tokio::test]
+    async fn descriptor_export_hides_the_xprv_unless_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = write_wallet_config(dir.path(), "network = \"regtest\"");
+        let wallet = WalletFactory::load_wallet(&config_path).await.unwrap();
+
+        let public = wallet.export_descriptor(KeychainKind::External).unwrap();
+        let private = wallet.export_private_descriptor(KeychainKind::External).unwrap();
+        for descriptor in [&public, &private] {
+            let (body, checksum) = descriptor.split_once('#').unwrap();
+            assert_eq!(checksum, bdk::descriptor::calc_checksum(body).unwrap());
+        }
+        assert!(public.starts_with("wpkh(") && public.contains("tpub") && !public.contains("tprv"));
+        assert!(private.contains("tprv"));
+
+        // The public descriptor alone rebuilds the same watch-only wallet
+        let watch_only = Wallet::new(&public, None, Network::Regtest, MemoryDatabase::default()).unwrap();
+        assert_eq!(
+            watch_only.get_address(AddressIndex::Peek(0)).unwrap().address.to_string(),
+            wallet.get_receiving_address().unwrap()
+        );
+        assert!(wallet.export_descriptor(KeychainKind::Internal).is_err());
+    }
+}
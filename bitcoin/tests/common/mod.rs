@@ -0,0 +1,242 @@
+// Each integration test binary compiles this module separately and only uses
+// part of it
+#![allow(dead_code)]
+
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+/// Address of the BDK admin wallet (wallet/admin.toml), funded at startup
+const BDK_ADMIN_ADDRESS: &str = "bcrt1qmflavul2k53n45lz360278cfgr4nzahh2f2f43";
+/// How long to wait for the node to start answering RPC calls
+const RPC_WAIT_TIMEOUT_SECS: u32 = 30;
+/// How long to wait for the node to exit after `stop` before killing it
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A throwaway Bitcoin Core regtest node for one integration test
+///
+/// Each harness runs its own `bitcoind` on free ports with a temporary data
+/// directory, so tests can run in parallel and never touch `./data`. Blocks
+/// are only mined when the test asks for them. The node is stopped when the
+/// harness is dropped, including when the test panics.
+pub struct RegtestHarness {
+    node: Child,
+    rpc_port: u16,
+    mining_address: String,
+    // Kept alive until the node has stopped; removed on drop
+    datadir: TempDir,
+}
+
+impl RegtestHarness {
+    /// Start a fresh node and fund the BDK admin wallet with 10 BTC
+    pub fn start() -> Self {
+        let datadir = tempfile::tempdir().expect("Failed to create regtest data directory");
+        let (rpc_port, p2p_port) = free_ports();
+
+        let node = Command::new("bitcoind")
+            .arg("-regtest")
+            .arg("-server")
+            .arg(format!("-datadir={}", datadir.path().display()))
+            .arg("-rpcuser=bitcoin")
+            .arg("-rpcpassword=bitcoin")
+            .arg(format!("-rpcport={}", rpc_port))
+            .arg(format!("-port={}", p2p_port))
+            .arg("-fallbackfee=0.0002")
+            .arg("-rpcallowip=127.0.0.1")
+            .arg("-txindex=1")
+            .arg("-printtoconsole=0")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to start bitcoind");
+
+        // Build the harness before any further setup so a failure below still
+        // stops the node
+        let mut harness = RegtestHarness {
+            node,
+            rpc_port,
+            mining_address: String::new(),
+            datadir,
+        };
+
+        harness.bitcoin_cli(&[
+            "-rpcwait",
+            &format!("-rpcwaittimeout={}", RPC_WAIT_TIMEOUT_SECS),
+            "getblockchaininfo",
+        ]);
+        harness.bitcoin_cli(&["createwallet", "admin"]);
+        harness.mining_address = harness.bitcoin_cli(&["-rpcwallet=admin", "getnewaddress"]);
+
+        // Mature the first coinbase, then fund the BDK admin wallet
+        harness.mine(101);
+        harness.bitcoin_cli(&["-rpcwallet=admin", "sendtoaddress", BDK_ADMIN_ADDRESS, "10"]);
+        harness.mine(1);
+
+        harness
+    }
+
+    /// RPC URL of this node, as passed to the CLI
+    pub fn rpc_url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.rpc_port)
+    }
+
+    /// Run `bitcoin-cli` against this node and return its trimmed stdout
+    pub fn bitcoin_cli(&self, args: &[&str]) -> String {
+        let output = Command::new("bitcoin-cli")
+            .arg("-regtest")
+            .arg(format!("-datadir={}", self.datadir.path().display()))
+            .arg("-rpcuser=bitcoin")
+            .arg("-rpcpassword=bitcoin")
+            .arg(format!("-rpcport={}", self.rpc_port))
+            .args(args)
+            .output()
+            .expect("Failed to run bitcoin-cli");
+
+        assert_success("bitcoin-cli", args, &output);
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    /// Run the wallet CLI with `--json` against this node without checking
+    /// its exit status
+    pub fn try_cli(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_chain-bitcoin"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .arg("--json")
+            .env("BITCOIN_RPC_URL", self.rpc_url())
+            .env("BITCOIN_RPC_USERNAME", "bitcoin")
+            .env("BITCOIN_RPC_PASSWORD", "bitcoin")
+            .args(args)
+            .output()
+            .expect("Failed to run chain-bitcoin")
+    }
+
+    /// Run the wallet CLI against this node and return its JSON result,
+    /// panicking if the command fails
+    pub fn cli(&self, args: &[&str]) -> Value {
+        let output = self.try_cli(args);
+        assert_success("chain-bitcoin", args, &output);
+        parse_json_output(&output)
+    }
+
+    /// Balance in BTC of the named wallet under `wallet/`
+    pub fn balance(&self, wallet: &str) -> f64 {
+        let wallet_path = wallet_config(wallet);
+        let result = self.cli(&["balance", "--wallet", &wallet_path]);
+        let total_sat = result["total_sat"]
+            .as_u64()
+            .unwrap_or_else(|| panic!("Balance of {} has no total_sat: {}", wallet, result));
+        total_sat as f64 / 100_000_000.0
+    }
+
+    /// Current block height of the node
+    pub fn height(&self) -> u32 {
+        self.bitcoin_cli(&["getblockcount"])
+            .parse()
+            .expect("getblockcount returned a non-numeric height")
+    }
+
+    /// Send `amount` BTC between two named wallets and confirm it in a block
+    pub fn send(&self, from: &str, to: &str, amount: f64) -> Value {
+        let from = wallet_config(from);
+        let to = wallet_config(to);
+        let amount = amount.to_string();
+        let output = self.cli(&["send", "--from", &from, "--to", &to, "--amount", &amount]);
+        self.mine(1);
+        output
+    }
+
+    /// Mine `blocks` blocks to the node's own mining wallet
+    pub fn mine(&self, blocks: u32) {
+        self.bitcoin_cli(&["generatetoaddress", &blocks.to_string(), &self.mining_address]);
+    }
+
+    /// Mine blocks until the chain reaches `height`
+    pub fn mine_to_height(&self, height: u32) {
+        let current = self.height();
+        if height > current {
+            self.mine(height - current);
+        }
+    }
+}
+
+impl Drop for RegtestHarness {
+    fn drop(&mut self) {
+        // Ask the node to shut down cleanly; this must not panic while the
+        // test is already unwinding
+        let _ = Command::new("bitcoin-cli")
+            .arg("-regtest")
+            .arg(format!("-datadir={}", self.datadir.path().display()))
+            .arg("-rpcuser=bitcoin")
+            .arg("-rpcpassword=bitcoin")
+            .arg(format!("-rpcport={}", self.rpc_port))
+            .arg("stop")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while Instant::now() < deadline {
+            match self.node.try_wait() {
+                Ok(Some(_)) | Err(_) => return,
+                Ok(None) => thread::sleep(Duration::from_millis(100)),
+            }
+        }
+
+        let _ = self.node.kill();
+        let _ = self.node.wait();
+    }
+}
+
+/// Path of a named wallet config, relative to the crate root
+pub fn wallet_config(wallet: &str) -> String {
+    Path::new("wallet")
+        .join(format!("{}.toml", wallet))
+        .display()
+        .to_string()
+}
+
+/// Parse the JSON object a `--json` command printed to stdout
+pub fn parse_json_output(output: &Output) -> Value {
+    serde_json::from_slice(&output.stdout).unwrap_or_else(|e| {
+        panic!(
+            "chain-bitcoin printed invalid JSON ({}): {}",
+            e,
+            String::from_utf8_lossy(&output.stdout)
+        )
+    })
+}
+
+/// Read a string field from a command's JSON result
+pub fn json_str(result: &Value, field: &str) -> String {
+    result[field]
+        .as_str()
+        .unwrap_or_else(|| panic!("Missing string field {} in {}", field, result))
+        .to_string()
+}
+
+/// Reserve two distinct free local ports for RPC and P2P
+fn free_ports() -> (u16, u16) {
+    let rpc = TcpListener::bind("127.0.0.1:0").expect("Failed to reserve RPC port");
+    let p2p = TcpListener::bind("127.0.0.1:0").expect("Failed to reserve P2P port");
+    // Both listeners are dropped on return so bitcoind can bind the ports
+    (
+        rpc.local_addr().expect("RPC listener has no address").port(),
+        p2p.local_addr().expect("P2P listener has no address").port(),
+    )
+}
+
+fn assert_success(program: &str, args: &[&str], output: &Output) {
+    if !output.status.success() {
+        panic!(
+            "{} {} failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
@@ -1,6 +1,6 @@
-use std::process::Command;
-use std::thread;
-use std::time::Duration;
+mod common;
+
+use common::{RegtestHarness, json_str, wallet_config};
 
 /// End-to-End HTLC Test
 ///
@@ -15,7 +15,7 @@ use std::time::Duration;
 ///
 /// Final balances:
 /// - Admin: 7 BTC
-/// - Maker: 1.5 BTC  
+/// - Maker: 1.5 BTC
 /// - Taker: 1.5 BTC
 #[test]
 fn test_htlc_claim() {
@@ -27,42 +27,16 @@ fn test_htlc_claim() {
     let timeout_block = 200;
     let admin_to_maker_amount = 3.0;
 
-    // Step 0: Clear the chain state
-    println!("🧹 Step 0: Clearing previous chain state...");
-    let clear_result = Command::new("just")
-        .arg("clean")
-        .output()
-        .expect("Failed to clear chain state");
-
-    if !clear_result.status.success() {
-        panic!(
-            "Failed to clear chain state: {}",
-            String::from_utf8_lossy(&clear_result.stderr)
-        );
-    }
-
-    // Step 1: Start fresh blockchain
+    // Step 1: Start a fresh node; it is stopped when `node` goes out of scope,
+    // even if an assertion below fails
     println!("\n📦 Step 1: Starting fresh Bitcoin regtest blockchain...");
-    let start_result = Command::new("just")
-        .arg("start-fast")
-        .output()
-        .expect("Failed to start blockchain");
-
-    if !start_result.status.success() {
-        panic!(
-            "Failed to start blockchain: {}",
-            String::from_utf8_lossy(&start_result.stderr)
-        );
-    }
-
-    // Wait for blockchain to be ready
-    thread::sleep(Duration::from_secs(5));
+    let node = RegtestHarness::start();
 
     // Step 2: Check initial balances
     println!("\n💰 Step 2: Checking initial balances...");
-    let admin_balance = get_wallet_balance("admin");
-    let maker_balance = get_wallet_balance("maker");
-    let taker_balance = get_wallet_balance("taker");
+    let admin_balance = node.balance("admin");
+    let maker_balance = node.balance("maker");
+    let taker_balance = node.balance("taker");
 
     println!("Initial balances:");
     println!("  Admin: {} BTC", admin_balance);
@@ -82,22 +56,11 @@ fn test_htlc_claim() {
         "\n💸 Step 3: Admin sending {} BTC to maker...",
         admin_to_maker_amount
     );
-    let send_result = Command::new("just")
-        .arg("send-admin-to-maker")
-        .arg(admin_to_maker_amount.to_string())
-        .output()
-        .expect("Failed to send Bitcoin");
-
-    if !send_result.status.success() {
-        panic!(
-            "Failed to send Bitcoin: {}",
-            String::from_utf8_lossy(&send_result.stderr)
-        );
-    }
+    node.send("admin", "maker", admin_to_maker_amount);
 
     // Verify balances after transfer
-    let admin_balance_after_send = get_wallet_balance("admin");
-    let maker_balance_after_send = get_wallet_balance("maker");
+    let admin_balance_after_send = node.balance("admin");
+    let maker_balance_after_send = node.balance("maker");
 
     println!("Balances after transfer:");
     println!("  Admin: {} BTC", admin_balance_after_send);
@@ -114,66 +77,71 @@ fn test_htlc_claim() {
         htlc_amount, secret
     );
 
-    let htlc_create_result = Command::new("just")
-        .arg("htlc-create-maker-to-taker")
-        .arg(htlc_amount.to_string())
-        .arg(secret)
-        .arg(timeout_block.to_string())
-        .output()
-        .expect("Failed to create HTLC");
-
-    if !htlc_create_result.status.success() {
-        panic!(
-            "Failed to create HTLC: {}",
-            String::from_utf8_lossy(&htlc_create_result.stderr)
-        );
-    }
-
-    let htlc_output = String::from_utf8_lossy(&htlc_create_result.stdout);
-    println!("HTLC creation output:\n{}", htlc_output);
-
-    // Extract contract ID from output
-    let contract_id = extract_contract_id(&htlc_output)
-        .expect("Failed to extract contract ID from HTLC creation output");
+    let htlc_result = node.cli(&[
+        "htlc-create",
+        "--from",
+        &wallet_config("maker"),
+        "--to",
+        &wallet_config("taker"),
+        "--amount",
+        &htlc_amount.to_string(),
+        "--secret",
+        secret,
+        "--timeout",
+        &timeout_block.to_string(),
+    ]);
+    println!("HTLC creation result: {}", htlc_result);
+
+    let contract_id = json_str(&htlc_result, "contract_txid");
 
     println!("📝 Contract ID: {}", contract_id);
 
-    // Wait for HTLC transaction to be mined
-    thread::sleep(Duration::from_secs(5));
+    // Confirm the HTLC transaction
+    node.mine(1);
 
     // Step 5: Taker claims HTLC with correct secret
     println!(
         "\n🔑 Step 5: Taker claiming HTLC with correct secret '{}'...",
         secret
     );
-    let claim_result = Command::new("just")
-        .arg("htlc-claim-taker")
-        .arg(&contract_id)
-        .arg(secret)
-        .arg(htlc_amount.to_string())
-        .arg(timeout_block.to_string())
-        .arg("wallet/maker.toml")
-        .output()
-        .expect("Failed to claim HTLC");
-
-    if !claim_result.status.success() {
-        panic!(
-            "Failed to claim HTLC: {}",
-            String::from_utf8_lossy(&claim_result.stderr)
-        );
-    }
-
-    let claim_output = String::from_utf8_lossy(&claim_result.stdout);
-    println!("HTLC claim output:\n{}", claim_output);
-
-    // Wait for claim transaction to be mined
-    thread::sleep(Duration::from_secs(5));
-
-    // Step 7: Verify final balances
+    let claim_result = node.cli(&[
+        "claim",
+        "--contract-txid",
+        &contract_id,
+        "--secret",
+        secret,
+        "--amount",
+        &htlc_amount.to_string(),
+        "--timeout",
+        &timeout_block.to_string(),
+        "--recipient-wallet",
+        &wallet_config("taker"),
+        "--sender-wallet",
+        &wallet_config("maker"),
+    ]);
+    println!("📝 Claim transaction: {}", json_str(&claim_result, "txid"));
+    // claim derives the address from the contract, htlc-create from deploy_htlc
+    assert_eq!(
+        json_str(&claim_result, "contract_address"),
+        json_str(&htlc_result, "contract_address"),
+        "Claim and creation should agree on the contract address"
+    );
+    // claim verified the funding output's script and amount on chain before spending it
+    let funding_vout = htlc_result["vout"].as_u64().expect("htlc-create reports the vout");
+    assert_eq!(
+        json_str(&claim_result, "contract_outpoint"),
+        format!("{}:{}", contract_id, funding_vout),
+        "Claim should spend the funding output it verified"
+    );
+
+    // Confirm the claim transaction
+    node.mine(1);
+
+    // Step 6: Verify final balances
     println!("\n🏁 Step 6: Verifying final balances...");
-    let final_admin_balance = get_wallet_balance("admin");
-    let final_maker_balance = get_wallet_balance("maker");
-    let final_taker_balance = get_wallet_balance("taker");
+    let final_admin_balance = node.balance("admin");
+    let final_maker_balance = node.balance("maker");
+    let final_taker_balance = node.balance("taker");
 
     println!("Final balances:");
     println!("  Admin: {} BTC", final_admin_balance);
@@ -196,55 +164,4 @@ fn test_htlc_claim() {
 
     println!("\n✅ End-to-End HTLC Test Completed Successfully!");
     println!("🎉 Atomic swap executed: Maker → Taker (1.5 BTC) via HTLC with secret revelation");
-
-    // Cleanup: Stop blockchain
-    println!("\n🧹 Cleaning up: Stopping blockchain...");
-    let _stop_result = Command::new("just")
-        .arg("stop")
-        .output()
-        .expect("Failed to stop blockchain");
-}
-
-/// Helper function to get wallet balance
-fn get_wallet_balance(wallet: &str) -> f64 {
-    let balance_result = Command::new("just")
-        .arg(format!("balance-{}", wallet))
-        .output()
-        .expect("Failed to get wallet balance");
-
-    if !balance_result.status.success() {
-        panic!(
-            "Failed to get {} balance: {}",
-            wallet,
-            String::from_utf8_lossy(&balance_result.stderr)
-        );
-    }
-
-    let output = String::from_utf8_lossy(&balance_result.stdout);
-
-    // Parse balance from output like "Balance: 10 BTC (1000000000 sats)"
-    for line in output.lines() {
-        if line.starts_with("Balance:") {
-            if let Some(btc_part) = line.split(" BTC ").next() {
-                if let Some(balance_str) = btc_part.split("Balance: ").nth(1) {
-                    return balance_str.parse::<f64>().unwrap_or(0.0);
-                }
-            }
-        }
-    }
-
-    0.0
-}
-
-/// Helper function to extract contract ID from HTLC creation output
-fn extract_contract_id(output: &str) -> Option<String> {
-    for line in output.lines() {
-        if line.starts_with("Contract ID:") {
-            return line
-                .split("Contract ID: ")
-                .nth(1)
-                .map(|s| s.trim().to_string());
-        }
-    }
-    None
 }
@@ -1,6 +1,6 @@
-use std::process::Command;
-use std::thread;
-use std::time::Duration;
+mod common;
+
+use common::{RegtestHarness, json_str, wallet_config};
 
 /// End-to-End HTLC Refund Test
 ///
@@ -15,7 +15,7 @@ use std::time::Duration;
 /// Admin (10 BTC) → sends 3 BTC → Maker (3 BTC) → creates 1.5 BTC HTLC → timeout expires → Maker refunds 1.5 BTC
 ///
 /// Final balances:
-/// - Admin: 7 BTC  
+/// - Admin: 7 BTC
 /// - Maker: 3 BTC (back to original after refund)
 /// - Taker: 0 BTC (never claimed)
 #[test]
@@ -27,43 +27,16 @@ fn test_htlc_refund() {
     let htlc_amount = 1.5;
     let admin_to_maker_amount = 3.0;
 
-    // Step 0: Clear the chain state
-    println!("🧹 Step 0: Clearing previous chain state...");
-    let clear_result = Command::new("just")
-        .arg("clean")
-        .output()
-        .expect("Failed to clear chain state");
-
-    if !clear_result.status.success() {
-        panic!(
-            "Failed to clear chain state: {}",
-            String::from_utf8_lossy(&clear_result.stderr)
-        );
-    }
-
-    // Step 1: Start fresh blockchain with fast mining (3s blocks)
-    println!("\n📦 Step 1: Starting fresh Bitcoin regtest blockchain with fast mining...");
-    let start_result = Command::new("just")
-        .arg("start-fast")
-        .arg("3") // 3 second blocks for faster timeout testing
-        .output()
-        .expect("Failed to start blockchain");
-
-    if !start_result.status.success() {
-        panic!(
-            "Failed to start blockchain: {}",
-            String::from_utf8_lossy(&start_result.stderr)
-        );
-    }
-
-    // Wait for blockchain to be ready
-    thread::sleep(Duration::from_secs(5));
+    // Step 1: Start a fresh node; it is stopped when `node` goes out of scope,
+    // even if an assertion below fails
+    println!("\n📦 Step 1: Starting fresh Bitcoin regtest blockchain...");
+    let node = RegtestHarness::start();
 
     // Step 2: Check initial balances
     println!("\n💰 Step 2: Checking initial balances...");
-    let admin_balance = get_wallet_balance("admin");
-    let maker_balance = get_wallet_balance("maker");
-    let taker_balance = get_wallet_balance("taker");
+    let admin_balance = node.balance("admin");
+    let maker_balance = node.balance("maker");
+    let taker_balance = node.balance("taker");
 
     println!("Initial balances:");
     println!("  Admin: {} BTC", admin_balance);
@@ -83,25 +56,11 @@ fn test_htlc_refund() {
         "\n💸 Step 3: Admin sending {} BTC to maker...",
         admin_to_maker_amount
     );
-    let send_result = Command::new("just")
-        .arg("send-admin-to-maker")
-        .arg(admin_to_maker_amount.to_string())
-        .output()
-        .expect("Failed to send Bitcoin");
-
-    if !send_result.status.success() {
-        panic!(
-            "Failed to send Bitcoin: {}",
-            String::from_utf8_lossy(&send_result.stderr)
-        );
-    }
-
-    // Wait for transaction to be mined
-    thread::sleep(Duration::from_secs(5));
+    node.send("admin", "maker", admin_to_maker_amount);
 
     // Verify balances after transfer
-    let admin_balance_after_send = get_wallet_balance("admin");
-    let maker_balance_after_send = get_wallet_balance("maker");
+    let admin_balance_after_send = node.balance("admin");
+    let maker_balance_after_send = node.balance("maker");
 
     println!("Balances after transfer:");
     println!("  Admin: {} BTC", admin_balance_after_send);
@@ -114,7 +73,7 @@ fn test_htlc_refund() {
 
     // Step 4: Get current block height and set short timeout
     println!("\n⏰ Step 4: Getting current block height for timeout calculation...");
-    let current_height = get_current_block_height();
+    let current_height = node.height();
     let timeout_block = current_height + 5; // Short timeout: 5 blocks from now
 
     println!("Current block height: {}", current_height);
@@ -126,69 +85,60 @@ fn test_htlc_refund() {
         htlc_amount, secret, timeout_block
     );
 
-    let htlc_create_result = Command::new("just")
-        .arg("htlc-create-maker-to-taker")
-        .arg(htlc_amount.to_string())
-        .arg(secret)
-        .arg(timeout_block.to_string())
-        .output()
-        .expect("Failed to create HTLC");
-
-    if !htlc_create_result.status.success() {
-        panic!(
-            "Failed to create HTLC: {}",
-            String::from_utf8_lossy(&htlc_create_result.stderr)
-        );
-    }
-
-    let htlc_output = String::from_utf8_lossy(&htlc_create_result.stdout);
-    println!("HTLC creation output:\n{}", htlc_output);
-
-    // Extract contract ID from output
-    let contract_id = extract_contract_id(&htlc_output)
-        .expect("Failed to extract contract ID from HTLC creation output");
+    let htlc_result = node.cli(&[
+        "htlc-create",
+        "--from",
+        &wallet_config("maker"),
+        "--to",
+        &wallet_config("taker"),
+        "--amount",
+        &htlc_amount.to_string(),
+        "--secret",
+        secret,
+        "--timeout",
+        &timeout_block.to_string(),
+    ]);
+    println!("HTLC creation result: {}", htlc_result);
+
+    let contract_id = json_str(&htlc_result, "contract_txid");
 
     println!("📝 Contract ID: {}", contract_id);
 
-    // Wait for HTLC transaction to be mined
-    thread::sleep(Duration::from_secs(5));
+    // Confirm the HTLC transaction
+    node.mine(1);
 
-    // Step 6: Wait for timeout to expire (monitor block height)
+    // Step 6: Mine until the timeout has expired
     println!(
-        "\n⏳ Step 6: Waiting for timeout to expire (block {})...",
+        "\n⏳ Step 6: Mining until timeout expires (block {})...",
         timeout_block
     );
-
-    loop {
-        let current_height = get_current_block_height();
-        println!(
-            "Current block height: {} (waiting for {})",
-            current_height, timeout_block
-        );
-
-        if current_height >= timeout_block {
-            println!(
-                "✅ Timeout reached! Block {} >= {}",
-                current_height, timeout_block
-            );
-            break;
-        }
-
-        // Wait for next block (3 second intervals)
-        thread::sleep(Duration::from_secs(4));
-    }
+    node.mine_to_height(timeout_block);
+    println!("✅ Timeout reached at block {}", node.height());
+
+    let refund_args = |timeout: u32| {
+        vec![
+            "refund".to_string(),
+            "--contract-txid".to_string(),
+            contract_id.clone(),
+            "--secret".to_string(),
+            secret.to_string(),
+            "--amount".to_string(),
+            htlc_amount.to_string(),
+            "--timeout".to_string(),
+            timeout.to_string(),
+            "--sender-wallet".to_string(),
+            wallet_config("maker"),
+            "--recipient-wallet".to_string(),
+            wallet_config("taker"),
+        ]
+    };
 
     // Step 7: Test that refund fails before adequate time (should not happen now, but let's be safe)
     println!("\n🚫 Step 7: Verifying timeout protection is working...");
-    let early_refund_result = Command::new("just")
-        .arg("htlc-refund-maker")
-        .arg(&contract_id)
-        .arg(secret)
-        .arg(htlc_amount.to_string())
-        .arg((timeout_block - 1).to_string()) // Try with a block height that hasn't been reached
-        .arg("wallet/taker.toml")
-        .output()
-        .expect("Failed to attempt early refund");
+    // Try with a block height that hasn't been reached
+    let early_args = refund_args(timeout_block - 1);
+    let early_refund_result =
+        node.try_cli(&early_args.iter().map(String::as_str).collect::<Vec<_>>());
 
     // This should fail because we're using timeout_block - 1
     if early_refund_result.status.success() {
@@ -207,34 +157,18 @@ fn test_htlc_refund() {
         "\n🔄 Step 8: Maker refunding HTLC after timeout with secret '{}'...",
         secret
     );
-    let refund_result = Command::new("just")
-        .arg("htlc-refund-maker")
-        .arg(&contract_id)
-        .arg(secret)
-        .arg(htlc_amount.to_string())
-        .arg(timeout_block.to_string())
-        .arg("wallet/taker.toml")
-        .output()
-        .expect("Failed to refund HTLC");
+    let args = refund_args(timeout_block);
+    let refund_result = node.cli(&args.iter().map(String::as_str).collect::<Vec<_>>());
+    println!("📝 Refund transaction: {}", json_str(&refund_result, "txid"));
 
-    if !refund_result.status.success() {
-        panic!(
-            "Failed to refund HTLC: {}",
-            String::from_utf8_lossy(&refund_result.stderr)
-        );
-    }
-
-    let refund_output = String::from_utf8_lossy(&refund_result.stdout);
-    println!("HTLC refund output:\n{}", refund_output);
-
-    // Wait for refund transaction to be mined
-    thread::sleep(Duration::from_secs(5));
+    // Confirm the refund transaction
+    node.mine(1);
 
     // Step 9: Verify final balances
     println!("\n🏁 Step 9: Verifying final balances...");
-    let final_admin_balance = get_wallet_balance("admin");
-    let final_maker_balance = get_wallet_balance("maker");
-    let final_taker_balance = get_wallet_balance("taker");
+    let final_admin_balance = node.balance("admin");
+    let final_maker_balance = node.balance("maker");
+    let final_taker_balance = node.balance("taker");
 
     println!("Final balances:");
     println!("  Admin: {} BTC", final_admin_balance);
@@ -258,79 +192,4 @@ fn test_htlc_refund() {
     println!("\n✅ End-to-End HTLC Refund Test Completed Successfully!");
     println!("🎉 Timeout refund executed: Maker → Created HTLC → Timeout → Refunded (1.5 BTC)");
     println!("⏰ This demonstrates that funds are safely returned to sender when timeout expires");
-
-    // Cleanup: Stop blockchain
-    println!("\n🧹 Cleaning up: Stopping blockchain...");
-    let _stop_result = Command::new("just")
-        .arg("stop")
-        .output()
-        .expect("Failed to stop blockchain");
-}
-
-/// Helper function to get wallet balance
-fn get_wallet_balance(wallet: &str) -> f64 {
-    let balance_result = Command::new("just")
-        .arg(format!("balance-{}", wallet))
-        .output()
-        .expect("Failed to get wallet balance");
-
-    if !balance_result.status.success() {
-        panic!(
-            "Failed to get {} balance: {}",
-            wallet,
-            String::from_utf8_lossy(&balance_result.stderr)
-        );
-    }
-
-    let output = String::from_utf8_lossy(&balance_result.stdout);
-
-    // Parse balance from output like "Balance: 10 BTC (1000000000 sats)"
-    for line in output.lines() {
-        if line.starts_with("Balance:") {
-            if let Some(btc_part) = line.split(" BTC ").next() {
-                if let Some(balance_str) = btc_part.split("Balance: ").nth(1) {
-                    return balance_str.parse::<f64>().unwrap_or(0.0);
-                }
-            }
-        }
-    }
-
-    0.0
-}
-
-/// Helper function to get current block height
-fn get_current_block_height() -> u32 {
-    let height_result = Command::new("bitcoin-cli")
-        .args([
-            "-regtest",
-            "-rpcuser=bitcoin",
-            "-rpcpassword=bitcoin",
-            "-rpcport=18443",
-            "getblockcount",
-        ])
-        .output()
-        .expect("Failed to get block height");
-
-    if !height_result.status.success() {
-        panic!(
-            "Failed to get block height: {}",
-            String::from_utf8_lossy(&height_result.stderr)
-        );
-    }
-
-    let height_str = String::from_utf8_lossy(&height_result.stdout);
-    height_str.trim().parse::<u32>().unwrap_or(0)
-}
-
-/// Helper function to extract contract ID from HTLC creation output
-fn extract_contract_id(output: &str) -> Option<String> {
-    for line in output.lines() {
-        if line.starts_with("Contract ID:") {
-            return line
-                .split("Contract ID: ")
-                .nth(1)
-                .map(|s| s.trim().to_string());
-        }
-    }
-    None
 }
@@ -1,13 +1,85 @@
-use std::str::FromStr;
+use std::fmt;
 
-use secp256k1::{Secp256k1, SecretKey};
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tiny_keccak::{Hasher, Keccak};
 
+/// Why a hex string could not be decoded
+pub use hex::FromHexError as HexError;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PublicParams {
     pub secret_hash: String,
     pub public_key: String,
+    pub amount_satoshis: u64,
+    pub timelock_duration_blocks: u32,
+    pub recipient_public_key: String,
+    /// sha256 of the secret (hex), the hash lock of a Bitcoin HTLC it opens, if committed
+    #[serde(default)]
+    pub btc_hash_lock: Option<String>,
+}
+
+impl PublicParams {
+    /// Public values for a swap secret with hash `secret_hash` and public key `public_key`
+    ///
+    /// The swap amount, timelock and recipient are copied from `swap_info`.
+    pub fn new(secret_hash: [u8; 32], public_key: &PublicKey, swap_info: &SwapInfo) -> Self {
+        Self {
+            secret_hash: hex::encode(secret_hash),
+            public_key: public_key.to_string(),
+            amount_satoshis: swap_info.amount_satoshis,
+            timelock_duration_blocks: swap_info.timelock_duration_blocks,
+            recipient_public_key: swap_info.recipient_public_key.clone(),
+            btc_hash_lock: None,
+        }
+    }
+
+    /// Names of the committed swap fields that differ from `swap_info`
+    pub fn mismatched_swap_fields(&self, swap_info: &SwapInfo) -> Vec<&'static str> {
+        let mut mismatches = Vec::new();
+        if self.amount_satoshis != swap_info.amount_satoshis {
+            mismatches.push("amount_satoshis");
+        }
+        if self.timelock_duration_blocks != swap_info.timelock_duration_blocks {
+            mismatches.push("timelock_duration_blocks");
+        }
+        if self.recipient_public_key != swap_info.recipient_public_key {
+            mismatches.push("recipient_public_key");
+        }
+        mismatches
+    }
+
+    /// Differences from caller-supplied expectations as `(field, expected, committed)`
+    ///
+    /// Fields without an expectation are skipped; hashes compare without `0x` or case.
+    pub fn diff_expected(
+        &self,
+        expected_secret_hash: Option<&str>,
+        expected_public_key: Option<&str>,
+        expected_btc_hash_lock: Option<&str>,
+    ) -> Vec<(&'static str, String, String)> {
+        let mut differences = Vec::new();
+        if let Some(expected) = expected_secret_hash {
+            let expected = expected.trim_start_matches("0x").to_ascii_lowercase();
+            if expected != self.secret_hash {
+                differences.push(("secret_hash", expected, self.secret_hash.clone()));
+            }
+        }
+        if let Some(expected) = expected_public_key {
+            if !expected.eq_ignore_ascii_case(&self.public_key) {
+                differences.push(("public_key", expected.to_string(), self.public_key.clone()));
+            }
+        }
+        if let Some(expected) = expected_btc_hash_lock {
+            let expected = expected.trim_start_matches("0x").to_ascii_lowercase();
+            if self.btc_hash_lock.as_deref() != Some(expected.as_str()) {
+                let committed = self.btc_hash_lock.clone().unwrap_or_else(|| "nothing".to_string());
+                differences.push(("btc_hash_lock", expected, committed));
+            }
+        }
+        differences
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,18 +98,117 @@ pub struct BitcoinSwap {
     pub contract_address: String,
     pub funding_txid: String,
     pub creation_timestamp: u64,
+    /// keccak256 of the swap secret as committed in the counterparty order (hex)
+    #[serde(default)]
+    pub secret_hash: Option<String>,
+}
+
+impl BitcoinSwap {
+    /// The hash to prove the secret against: `secret_hash` when the record has
+    /// one, else keccak256 of `swap_secret`, for records written before the
+    /// Bitcoin CLI stored it
+    pub fn expected_secret_hash(&self) -> Result<String, HexError> {
+        match &self.secret_hash {
+            Some(secret_hash) => Ok(secret_hash.clone()),
+            None => Ok(hex::encode(hash_secret_hex(&self.swap_secret)?)),
+        }
+    }
+}
+
+/// Reasons `make_process` rejects its inputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProcessError {
+    /// The secret is not 32 bytes (64 hex characters); holds the length received
+    InvalidLength(usize),
+    /// The secret contains non-hex characters
+    InvalidHex(String),
+    /// The secret is zero or not below the curve order
+    InvalidScalar,
+    /// keccak256 of the secret differs from the committed hash
+    SecretHashMismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessError::InvalidLength(len) => {
+                write!(f, "secret must be 64 hex characters, got {}", len)
+            }
+            ProcessError::InvalidHex(msg) => write!(f, "secret is not valid hex: {}", msg),
+            ProcessError::InvalidScalar => write!(f, "secret is not a valid secp256k1 scalar"),
+            ProcessError::SecretHashMismatch { expected, actual } => write!(
+                f,
+                "swap secret hashes to {} instead of the committed secret hash {}",
+                actual, expected
+            ),
+        }
+    }
 }
 
-pub fn make_process(secret_key_string: &str) -> PublicParams {
-    let secret_key = SecretKey::from_str(secret_key_string).unwrap();
+impl std::error::Error for ProcessError {}
+
+/// Parses a hex-encoded secp256k1 secret key
+fn parse_secret_key(secret_key_string: &str) -> Result<SecretKey, ProcessError> {
+    if secret_key_string.len() != 64 {
+        return Err(ProcessError::InvalidLength(secret_key_string.len()));
+    }
+    let secret_bytes =
+        hex::decode(secret_key_string).map_err(|e| ProcessError::InvalidHex(e.to_string()))?;
+    SecretKey::from_slice(&secret_bytes).map_err(|_| ProcessError::InvalidScalar)
+}
+
+/// Proves knowledge of the preimage of `expected_secret_hash` for the swap in `swap_info`.
+///
+/// The swap amount, timelock and recipient are copied into the public values so a
+/// verifier can bind the proof to one specific swap.
+///
+/// With `commit_btc_hash_lock` the secret's sha256 is committed as well, so a
+/// verifier can check it equals the hash lock of the Bitcoin HTLC the secret opens.
+///
+/// Fails with `SecretHashMismatch` if `keccak256(secret)` differs from the expected
+/// hash, so no proof can be produced for a secret that does not open the on-chain
+/// commitment.
+pub fn make_process(
+    secret_key_string: &str,
+    expected_secret_hash: &str,
+    swap_info: &SwapInfo,
+    commit_btc_hash_lock: bool,
+) -> Result<PublicParams, ProcessError> {
+    let secret_key = parse_secret_key(secret_key_string)?;
     let secp = Secp256k1::new();
     let pub_key = secret_key.public_key(&secp);
-    let secret_hash = keccak256(secret_key.as_ref());
+    let secret_hash_bytes = keccak256(secret_key.as_ref());
+    let secret_hash = hex::encode(secret_hash_bytes);
 
-    PublicParams {
-        secret_hash: hex::encode(secret_hash),
-        public_key: pub_key.to_string(),
+    let expected_secret_hash = expected_secret_hash
+        .trim_start_matches("0x")
+        .to_ascii_lowercase();
+    if secret_hash != expected_secret_hash {
+        return Err(ProcessError::SecretHashMismatch {
+            expected: expected_secret_hash,
+            actual: secret_hash,
+        });
     }
+
+    let mut public_params = PublicParams::new(secret_hash_bytes, &pub_key, swap_info);
+    if commit_btc_hash_lock {
+        public_params.btc_hash_lock = Some(hex::encode(sha256(secret_key.as_ref())));
+    }
+    Ok(public_params)
+}
+
+/// keccak256 of hex-encoded bytes, e.g. the commitment a proof for this secret asserts
+///
+/// Accepts an optional `0x` prefix. Unlike [`make_process`] it does not check
+/// that the bytes are a valid secret key.
+pub fn hash_secret_hex(secret_hex: &str) -> Result<[u8; 32], HexError> {
+    let bytes = hex::decode(secret_hex.trim_start_matches("0x"))?;
+    Ok(keccak256(bytes))
+}
+
+/// sha256 of `bytes`, as used for Bitcoin HTLC hash locks
+pub fn sha256<T: AsRef<[u8]>>(bytes: T) -> [u8; 32] {
+    Sha256::digest(bytes.as_ref()).into()
 }
 
 /// Simple interface to the [`keccak256`] hash function.
@@ -55,12 +226,142 @@ pub fn keccak256<T: AsRef<[u8]>>(bytes: T) -> [u8; 32] {
 mod tests {
     use super::*;
 
+    const SECRET_KEY: &str = "242b7a112ced4f1e688d117f358e3534e92f9e5fc89a5d0b2f843afebb9742f6";
+    const SECRET_HASH: &str = "0xe7df6c631fad9c95bdf7e16b37d3f92184d59eb59155b3e60204b75c3b5984b3";
+    /// sha256 of the SECRET_KEY bytes; bitcoin/src/contract.rs pins its HTLC hash lock to the same value
+    const BTC_HASH_LOCK: &str = "810c6ee49f812f99284b37fb0f367b37efa20fcdaf89da05689d3058dd289c17";
+
+    fn swap_info() -> SwapInfo {
+        SwapInfo {
+            recipient_public_key: "03c9ba9995df37f2baf2e45ae990d883583cef7c04eb74b0376a3713444317bf43".to_string(),
+            revocation_public_key: "03b88e0fbe3f646337ed93bc0c0f3b843fcf7d2589e5ec884754e6402027a890b4".to_string(),
+            timelock_duration_blocks: 144,
+            amount_satoshis: 100_000_000,
+        }
+    }
+
     #[test]
     fn test_make_process_with_valid_secret_key() {
-        let secret_key_string = "242b7a112ced4f1e688d117f358e3534e92f9e5fc89a5d0b2f843afebb9742f6";
-        let params = make_process(secret_key_string);
+        let params = make_process(SECRET_KEY, SECRET_HASH, &swap_info(), false).unwrap();
 
         println!("Public Params: {:?}", params);
+        assert_eq!(params.secret_hash, SECRET_HASH.trim_start_matches("0x"));
+        assert!(params.mismatched_swap_fields(&swap_info()).is_empty());
+    }
+
+    #[test]
+    fn test_public_params_detect_a_different_swap() {
+        let params = make_process(SECRET_KEY, SECRET_HASH, &swap_info(), false).unwrap();
+
+        let mut other_swap = swap_info();
+        other_swap.amount_satoshis += 1;
+        other_swap.recipient_public_key = other_swap.revocation_public_key.clone();
+        assert_eq!(
+            params.mismatched_swap_fields(&other_swap),
+            vec!["amount_satoshis", "recipient_public_key"]
+        );
+    }
+
+    #[test]
+    fn test_public_params_diff_against_expected_values() {
+        let params = make_process(SECRET_KEY, SECRET_HASH, &swap_info(), false).unwrap();
+
+        assert!(params
+            .diff_expected(Some(SECRET_HASH), Some(&params.public_key), None)
+            .is_empty());
+        assert!(params.diff_expected(None, None, None).is_empty());
+
+        let differences = params.diff_expected(Some(&"00".repeat(32)), None, None);
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].0, "secret_hash");
+        assert_eq!(differences[0].2, params.secret_hash);
+    }
+
+    #[test]
+    fn test_hash_secret_hex_matches_the_proved_commitment() {
+        let params = make_process(SECRET_KEY, SECRET_HASH, &swap_info(), false).unwrap();
+
+        let hash = hash_secret_hex(SECRET_KEY).unwrap();
+        assert_eq!(hex::encode(hash), params.secret_hash);
+        assert_eq!(hash_secret_hex(&format!("0x{}", SECRET_KEY)).unwrap(), hash);
+
+        assert_eq!(hash_secret_hex("abc").unwrap_err(), HexError::OddLength);
+        assert!(matches!(
+            hash_secret_hex("zz").unwrap_err(),
+            HexError::InvalidHexCharacter { .. }
+        ));
+    }
+
+    #[test]
+    fn test_public_params_from_components_match_make_process() {
+        let params = make_process(SECRET_KEY, SECRET_HASH, &swap_info(), false).unwrap();
+
+        let secret_key = parse_secret_key(SECRET_KEY).unwrap();
+        let public_key = secret_key.public_key(&Secp256k1::new());
+        let rebuilt = PublicParams::new(hash_secret_hex(SECRET_KEY).unwrap(), &public_key, &swap_info());
+        assert_eq!(rebuilt.secret_hash, params.secret_hash);
+        assert_eq!(rebuilt.public_key, params.public_key);
+        assert!(rebuilt.mismatched_swap_fields(&swap_info()).is_empty());
     }
 
+    #[test]
+    fn test_make_process_commits_the_bitcoin_hash_lock_on_request() {
+        let params = make_process(SECRET_KEY, SECRET_HASH, &swap_info(), false).unwrap();
+        assert_eq!(params.btc_hash_lock, None);
+
+        let without_lock = params;
+        let params = make_process(SECRET_KEY, SECRET_HASH, &swap_info(), true).unwrap();
+        assert_eq!(params.btc_hash_lock.as_deref(), Some(BTC_HASH_LOCK));
+        assert_eq!(params.secret_hash, SECRET_HASH.trim_start_matches("0x"));
+        assert_eq!(hex::encode(sha256(hex::decode(SECRET_KEY).unwrap())), BTC_HASH_LOCK);
+
+        assert!(params.diff_expected(None, None, Some(BTC_HASH_LOCK)).is_empty());
+        let differences = without_lock.diff_expected(None, None, Some(BTC_HASH_LOCK));
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].0, "btc_hash_lock");
+    }
+
+    #[test]
+    fn test_make_process_rejects_mismatched_secret_hash() {
+        let err = make_process(SECRET_KEY, &"00".repeat(32), &swap_info(), false).unwrap_err();
+        assert!(matches!(err, ProcessError::SecretHashMismatch { .. }));
+    }
+
+    #[test]
+    fn test_make_process_rejects_malformed_secret() {
+        assert_eq!(
+            make_process("242b", SECRET_HASH, &swap_info(), false).unwrap_err(),
+            ProcessError::InvalidLength(4)
+        );
+        assert!(matches!(
+            make_process(&"zz".repeat(32), SECRET_HASH, &swap_info(), false).unwrap_err(),
+            ProcessError::InvalidHex(_)
+        ));
+        assert_eq!(
+            make_process(&"00".repeat(32), SECRET_HASH, &swap_info(), false).unwrap_err(),
+            ProcessError::InvalidScalar
+        );
+    }
+
+    #[test]
+    fn test_expected_secret_hash_falls_back_to_the_secret() {
+        let swap = BitcoinSwap {
+            swap_info: swap_info(),
+            swap_secret: SECRET_KEY.to_string(),
+            descriptor_string: "tr(descriptor)".to_string(),
+            contract_address: "bcrt1p...".to_string(),
+            funding_txid: "00".repeat(32),
+            creation_timestamp: 0,
+            secret_hash: None,
+        };
+        let expected = swap.expected_secret_hash().unwrap();
+        assert_eq!(expected, SECRET_HASH.trim_start_matches("0x"));
+        assert!(make_process(SECRET_KEY, &expected, &swap_info(), false).is_ok());
+
+        let committed = BitcoinSwap {
+            secret_hash: Some(SECRET_HASH.to_string()),
+            ..swap
+        };
+        assert_eq!(committed.expected_secret_hash().unwrap(), SECRET_HASH);
+    }
 }
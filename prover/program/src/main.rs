@@ -3,6 +3,15 @@ sp1_zkvm::entrypoint!(main);
 
 pub fn main() {
     let secret_key = sp1_zkvm::io::read::<String>();
-    let public_params = proofimpl_atomic_swap::make_process(&secret_key);
+    let expected_secret_hash = sp1_zkvm::io::read::<String>();
+    let swap_info = sp1_zkvm::io::read::<proofimpl_atomic_swap::SwapInfo>();
+    let commit_btc_hash_lock = sp1_zkvm::io::read::<bool>();
+    let public_params = proofimpl_atomic_swap::make_process(
+        &secret_key,
+        &expected_secret_hash,
+        &swap_info,
+        commit_btc_hash_lock,
+    )
+    .expect("swap secret must be a valid key whose keccak256 matches the committed hash");
     sp1_zkvm::io::commit(&public_params);
 }
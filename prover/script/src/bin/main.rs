@@ -9,15 +9,185 @@
 //! ```shell
 //! RUST_LOG=info cargo run --release -- --prove
 //! ```
+//! or, to prove every swap record in a directory with a single setup
+//! ```shell
+//! RUST_LOG=info cargo run --release -- --prove --swaps-dir ../../bitcoin/swaps
+//! ```
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use proofimpl_atomic_swap::BitcoinSwap;
-use sp1_sdk::{include_elf, ProverClient, SP1ProofWithPublicValues, SP1Stdin, SP1VerifyingKey};
+use sp1_sdk::{
+    include_elf, CpuProver, CudaProver, EnvProver, NetworkProver, Prover, ProverClient, SP1Proof,
+    SP1ProofMode, SP1ProofWithPublicValues, SP1ProvingKey, SP1Stdin, SP1VerificationError,
+    SP1VerifyingKey,
+};
+use serde::{de::DeserializeOwned, Serialize};
 use std::fs;
+use std::path::{Path, PathBuf};
 
 /// The ELF (executable and linkable format) file for the Succinct RISC-V zkVM.
 pub const ATOMIC_SWAP_ELF: &[u8] = include_elf!("atomic-swap-program");
 
+/// Swap record written by the bitcoin CLI for the most recent swap.
+const DEFAULT_SWAP_JSON_PATH: &str = "../../bitcoin/swaps/swap_bitcoin.json";
+
+/// Public params written by --verify for the default proof file, read by the 1inch maker.
+const DEFAULT_PUBLIC_PARAMS_FILE: &str = "public_params.json";
+
+/// File name of the bitcoin CLI's copy of its most recent swap record.
+const LATEST_SWAP_FILE_NAME: &str = "swap_bitcoin.json";
+
+/// SP1 proof type produced by `--prove`.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ProofSystem {
+    /// Uncompressed STARK proof; fast to produce but large and only verifiable off-chain
+    Core,
+    /// Groth16 SNARK wrapping the proof; small and cheap to verify on-chain
+    Groth16,
+    /// PLONK SNARK wrapping the proof; no trusted setup, slightly larger than Groth16
+    Plonk,
+}
+
+impl From<ProofSystem> for SP1ProofMode {
+    fn from(proof_system: ProofSystem) -> Self {
+        match proof_system {
+            ProofSystem::Core => SP1ProofMode::Core,
+            ProofSystem::Groth16 => SP1ProofMode::Groth16,
+            ProofSystem::Plonk => SP1ProofMode::Plonk,
+        }
+    }
+}
+
+/// Encoding of proof and verifying key files, selected with `--format`.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum FileFormat {
+    /// Compact binary encoding; only readable by Rust verifiers
+    Bincode,
+    /// Pretty-printed JSON; human-inspectable and portable to other verifiers
+    Json,
+}
+
+impl FileFormat {
+    /// File extension for batch proof files in this format
+    fn extension(self) -> &'static str {
+        match self {
+            FileFormat::Bincode => "bin",
+            FileFormat::Json => "json",
+        }
+    }
+
+    fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            FileFormat::Bincode => bincode::serialize(value).map_err(|e| e.to_string()),
+            FileFormat::Json => serde_json::to_string_pretty(value)
+                .map(String::into_bytes)
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, String> {
+        match self {
+            FileFormat::Bincode => bincode::deserialize(bytes).map_err(|e| e.to_string()),
+            FileFormat::Json => {
+                let json = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+                serde_json::from_str(json).map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+/// Proving backend selected with `--prover`.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ProverKind {
+    /// Local CPU prover
+    Cpu,
+    /// Local GPU prover (requires a CUDA-capable machine)
+    Cuda,
+    /// Succinct prover network; reads NETWORK_PRIVATE_KEY from the environment
+    Network,
+}
+
+/// Environment variable holding the Succinct prover network key.
+const NETWORK_PRIVATE_KEY_ENV: &str = "NETWORK_PRIVATE_KEY";
+
+/// A prover client for whichever backend was selected.
+enum ProverBackend {
+    Cpu(CpuProver),
+    Cuda(CudaProver),
+    Network(NetworkProver),
+    /// Chosen by the `SP1_PROVER` environment variable when `--prover` is omitted
+    Env(EnvProver),
+}
+
+/// Calls the same `Prover` trait method on whichever backend is active.
+macro_rules! with_prover {
+    ($backend:expr, $prover:ident => $call:expr) => {
+        match $backend {
+            ProverBackend::Cpu($prover) => $call,
+            ProverBackend::Cuda($prover) => $call,
+            ProverBackend::Network($prover) => $call,
+            ProverBackend::Env($prover) => $call,
+        }
+    };
+}
+
+impl ProverBackend {
+    fn new(kind: Option<ProverKind>) -> Self {
+        match kind {
+            Some(ProverKind::Cpu) => {
+                println!("🖥️  Prover backend: cpu");
+                ProverBackend::Cpu(ProverClient::builder().cpu().build())
+            }
+            Some(ProverKind::Cuda) => {
+                println!("🎮 Prover backend: cuda");
+                ProverBackend::Cuda(ProverClient::builder().cuda().build())
+            }
+            Some(ProverKind::Network) => {
+                println!("🌐 Prover backend: network");
+                let private_key = std::env::var(NETWORK_PRIVATE_KEY_ENV).unwrap_or_else(|_| {
+                    eprintln!(
+                        "❌ Error: --prover network requires {} to be set",
+                        NETWORK_PRIVATE_KEY_ENV
+                    );
+                    std::process::exit(1);
+                });
+                ProverBackend::Network(
+                    ProverClient::builder()
+                        .network()
+                        .private_key(&private_key)
+                        .build(),
+                )
+            }
+            None => {
+                let from_env = std::env::var("SP1_PROVER").unwrap_or_else(|_| "cpu".to_string());
+                println!("⚙️  Prover backend: {} (from SP1_PROVER)", from_env);
+                ProverBackend::Env(ProverClient::from_env())
+            }
+        }
+    }
+
+    fn setup(&self, elf: &[u8]) -> (SP1ProvingKey, SP1VerifyingKey) {
+        with_prover!(self, prover => prover.setup(elf))
+    }
+
+    fn prove(
+        &self,
+        pk: &SP1ProvingKey,
+        stdin: &SP1Stdin,
+        mode: SP1ProofMode,
+    ) -> anyhow::Result<SP1ProofWithPublicValues> {
+        with_prover!(self, prover => Prover::prove(prover, pk, stdin, mode))
+    }
+
+    fn verify(
+        &self,
+        proof: &SP1ProofWithPublicValues,
+        vk: &SP1VerifyingKey,
+    ) -> Result<(), SP1VerificationError> {
+        with_prover!(self, prover => Prover::verify(prover, proof, vk))
+    }
+}
+
 /// The arguments for the command.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -36,6 +206,47 @@ struct Args {
 
     #[arg(long, default_value = "vkey.bin")]
     vkey_file: String,
+
+    /// Encoding of the proof and verifying key files, for both writing and reading
+    #[arg(long, value_enum, default_value_t = FileFormat::Bincode)]
+    format: FileFormat,
+
+    /// Proof system used by --prove; --verify detects it from the proof file
+    #[arg(long, value_enum, default_value_t = ProofSystem::Groth16)]
+    proof_system: ProofSystem,
+
+    /// Swap record JSON written by the bitcoin CLI (e.g. swaps/swap_<txid>.json)
+    #[arg(long, default_value = DEFAULT_SWAP_JSON_PATH)]
+    swap_file: PathBuf,
+
+    /// With --prove: prove every swap record in this directory, writing proof_<txid>.<bin|json> for each
+    #[arg(long, conflicts_with = "swap_file")]
+    swaps_dir: Option<PathBuf>,
+
+    /// With --verify: where to write the committed public params; defaults to public_params.json
+    /// for proof.bin/proof.json, and to <proof stem>.params.json next to any other proof file
+    #[arg(long)]
+    public_params_out: Option<PathBuf>,
+
+    /// With --verify: secret hash the proof must commit to
+    #[arg(long)]
+    expect_hash: Option<String>,
+
+    /// With --verify: public key the proof must commit to
+    #[arg(long)]
+    expect_pubkey: Option<String>,
+
+    /// With --execute or --prove: also commit sha256 of the secret, the Bitcoin HTLC hash lock
+    #[arg(long)]
+    btc_hash_lock: bool,
+
+    /// With --verify: Bitcoin HTLC hash lock (sha256 of the secret) the proof must commit to
+    #[arg(long)]
+    expect_hash_lock: Option<String>,
+
+    /// Proving backend; defaults to the SP1_PROVER environment variable
+    #[arg(long, value_enum)]
+    prover: Option<ProverKind>,
 }
 
 fn main() {
@@ -58,83 +269,230 @@ fn main() {
     println!("🚀 Starting Atomic Swap Prover!");
 
     // Setup the prover client.
-    let client = ProverClient::from_env();
+    let client = ProverBackend::new(args.prover);
 
     if args.verify {
-        verify_mode(&args);
+        verify_mode(&client, &args);
         return;
     }
 
-    // Load and parse the JSON file
-    let json_path = "../../bitcoin/swaps/swap_bitcoin.json";
-    println!("📂 Loading swap data from: {}", json_path);
-    let json_content = fs::read_to_string(json_path).expect("❌ Failed to read JSON file");
-    let bitcoin_swap: BitcoinSwap =
-        serde_json::from_str(&json_content).expect("❌ Failed to parse JSON");
+    if let Some(swaps_dir) = &args.swaps_dir {
+        if !args.prove {
+            eprintln!("❌ Error: --swaps-dir can only be used with --prove");
+            std::process::exit(1);
+        }
+        batch_prove_mode(&client, swaps_dir, &args);
+        return;
+    }
 
-    // Setup the inputs.
-    let mut stdin = SP1Stdin::new();
-    stdin.write(&bitcoin_swap.swap_secret);
+    // Load and parse the JSON file
+    let bitcoin_swap = load_bitcoin_swap(&args.swap_file);
+    let stdin = swap_stdin(&bitcoin_swap, args.btc_hash_lock).unwrap_or_else(|e| {
+        eprintln!("❌ Error: {}", e);
+        std::process::exit(1);
+    });
 
     if args.execute {
-        execute_mode(&client, &stdin);
+        execute_mode(&stdin);
     } else if args.prove {
         prove_mode(&client, &stdin, &args);
     }
 }
 
-fn execute_mode(client: &sp1_sdk::EnvProver, stdin: &SP1Stdin) {
+/// Program inputs proving knowledge of the swap's secret
+fn swap_stdin(bitcoin_swap: &BitcoinSwap, commit_btc_hash_lock: bool) -> Result<SP1Stdin, String> {
+    let secret_hash = bitcoin_swap
+        .expected_secret_hash()
+        .map_err(|e| format!("swap secret is not valid hex: {}", e))?;
+    println!("🔐 Expected secret hash: {}", secret_hash);
+
+    let mut stdin = SP1Stdin::new();
+    stdin.write(&bitcoin_swap.swap_secret);
+    stdin.write(&secret_hash);
+    stdin.write(&bitcoin_swap.swap_info);
+    stdin.write(&commit_btc_hash_lock);
+    Ok(stdin)
+}
+
+fn load_bitcoin_swap(json_path: &Path) -> BitcoinSwap {
+    read_bitcoin_swap(json_path).unwrap_or_else(|e| {
+        eprintln!("❌ Error: {}", e);
+        std::process::exit(1);
+    })
+}
+
+fn read_bitcoin_swap(json_path: &Path) -> Result<BitcoinSwap, String> {
+    let resolved_path = std::path::absolute(json_path).unwrap_or_else(|_| json_path.to_path_buf());
+    println!("📂 Loading swap data from: {}", resolved_path.display());
+    let json_content = fs::read_to_string(json_path)
+        .map_err(|e| format!("cannot read swap file {}: {}", resolved_path.display(), e))?;
+    serde_json::from_str(&json_content)
+        .map_err(|e| format!("{} is not a valid swap record: {}", resolved_path.display(), e))
+}
+
+fn execute_mode(stdin: &SP1Stdin) {
+    // Execution never generates a proof, so it always runs on the local executor
     println!("⚡ Executing program...");
+    let client = ProverClient::builder().cpu().build();
     let (_output, _report) = client.execute(ATOMIC_SWAP_ELF, stdin).run().unwrap();
     println!("✅ Program executed successfully! 🎉");
 }
 
-fn prove_mode(client: &sp1_sdk::EnvProver, stdin: &SP1Stdin, args: &Args) {
+fn prove_mode(client: &ProverBackend, stdin: &SP1Stdin, args: &Args) {
     println!("🔧 Setting up proving system...");
     let (pk, vk) = client.setup(ATOMIC_SWAP_ELF);
     println!("✅ Proving key and verifying key generated!");
 
     // Save the verifying key
-    println!("💾 Saving verifying key to: {}", args.vkey_file);
-    let vkey_bytes = bincode::serialize(&vk).expect("❌ Failed to serialize verifying key");
-    fs::write(&args.vkey_file, vkey_bytes).expect("❌ Failed to write verifying key file");
+    save_vkey(&vk, args);
 
     // Generate the proof
-    println!("🧮 Generating proof... (this may take a while)");
+    println!(
+        "🧮 Generating {:?} proof... (this may take a while)",
+        args.proof_system
+    );
     let proof = client
-        .prove(&pk, stdin)
-        .groth16()
-        .run()
+        .prove(&pk, stdin, args.proof_system.into())
         .expect("❌ Failed to generate proof");
 
     println!("✅ Successfully generated proof! 🎊");
 
     // Save the proof
     println!("💾 Saving proof to: {}", args.proof_file);
-    let proof_bytes = bincode::serialize(&proof).expect("❌ Failed to serialize proof");
+    let proof_bytes = args
+        .format
+        .encode(&proof)
+        .unwrap_or_else(|e| panic!("❌ Failed to serialize proof: {}", e));
     fs::write(&args.proof_file, proof_bytes).expect("❌ Failed to write proof file");
 }
 
-fn verify_mode(args: &Args) {
+fn save_vkey(vk: &SP1VerifyingKey, args: &Args) {
+    println!("💾 Saving verifying key to: {}", args.vkey_file);
+    let vkey_bytes = args
+        .format
+        .encode(vk)
+        .unwrap_or_else(|e| panic!("❌ Failed to serialize verifying key: {}", e));
+    fs::write(&args.vkey_file, vkey_bytes).expect("❌ Failed to write verifying key file");
+}
+
+/// Proves every swap record in `swaps_dir` after a single setup.
+///
+/// Each proof is written to `proof_<funding txid>.bin` in the current directory.
+/// A failing swap does not stop the batch; the process exits non-zero at the end
+/// if any swap could not be proved.
+fn batch_prove_mode(client: &ProverBackend, swaps_dir: &Path, args: &Args) {
+    let mut swap_files: Vec<PathBuf> = fs::read_dir(swaps_dir)
+        .unwrap_or_else(|e| {
+            eprintln!("❌ Error: cannot read {}: {}", swaps_dir.display(), e);
+            std::process::exit(1);
+        })
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        // The latest-swap copy duplicates one of the per-swap records
+        .filter(|path| path.file_name().is_some_and(|name| name != LATEST_SWAP_FILE_NAME))
+        .collect();
+    swap_files.sort();
+    if swap_files.is_empty() {
+        eprintln!("❌ Error: no swap records found in {}", swaps_dir.display());
+        std::process::exit(1);
+    }
+    println!("📚 Proving {} swaps from {}", swap_files.len(), swaps_dir.display());
+
+    println!("🔧 Setting up proving system...");
+    let (pk, vk) = client.setup(ATOMIC_SWAP_ELF);
+    println!("✅ Proving key and verifying key generated!");
+    save_vkey(&vk, args);
+
+    let mut proved = Vec::new();
+    let mut failed = Vec::new();
+    for swap_file in &swap_files {
+        match prove_swap_file(client, &pk, swap_file, args) {
+            Ok(proof_file) => {
+                println!("✅ {} -> {}", swap_file.display(), proof_file);
+                proved.push(proof_file);
+            }
+            Err(e) => {
+                eprintln!("❌ {}: {}", swap_file.display(), e);
+                failed.push((swap_file, e));
+            }
+        }
+    }
+
+    println!("📊 Batch summary: {} proved, {} failed", proved.len(), failed.len());
+    for (swap_file, e) in &failed {
+        eprintln!("   ❌ {}: {}", swap_file.display(), e);
+    }
+    if !failed.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Proves one swap record and writes the proof, returning the proof file name
+fn prove_swap_file(
+    client: &ProverBackend,
+    pk: &SP1ProvingKey,
+    swap_file: &Path,
+    args: &Args,
+) -> Result<String, String> {
+    let bitcoin_swap = read_bitcoin_swap(swap_file)?;
+    let stdin = swap_stdin(&bitcoin_swap, args.btc_hash_lock)?;
+
+    println!(
+        "🧮 Generating {:?} proof for {}...",
+        args.proof_system, bitcoin_swap.funding_txid
+    );
+    let proof = client
+        .prove(pk, &stdin, args.proof_system.into())
+        .map_err(|e| format!("proving failed: {}", e))?;
+
+    let proof_file = format!(
+        "proof_{}.{}",
+        bitcoin_swap.funding_txid,
+        args.format.extension()
+    );
+    let proof_bytes = args
+        .format
+        .encode(&proof)
+        .map_err(|e| format!("cannot serialize proof: {}", e))?;
+    fs::write(&proof_file, proof_bytes)
+        .map_err(|e| format!("cannot write {}: {}", proof_file, e))?;
+    Ok(proof_file)
+}
+
+/// Name of the proof system a saved proof was generated with.
+fn proof_system_name(proof: &SP1Proof) -> &'static str {
+    match proof {
+        SP1Proof::Core(_) => "core",
+        SP1Proof::Compressed(_) => "compressed",
+        SP1Proof::Plonk(_) => "plonk",
+        SP1Proof::Groth16(_) => "groth16",
+    }
+}
+
+fn verify_mode(client: &ProverBackend, args: &Args) {
     println!("🔍 Entering verification mode...");
 
     // Load the proof
     println!("📂 Loading proof from: {}", args.proof_file);
     let proof_bytes = fs::read(&args.proof_file).expect("❌ Failed to read proof file");
-    let proof: SP1ProofWithPublicValues =
-        bincode::deserialize(&proof_bytes).expect("❌ Failed to deserialize proof");
+    let proof: SP1ProofWithPublicValues = args
+        .format
+        .decode(&proof_bytes)
+        .unwrap_or_else(|e| panic!("❌ Failed to deserialize proof: {}", e));
     println!("✅ Proof loaded! 📋");
+    println!("🧾 Proof system: {}", proof_system_name(&proof.proof));
 
     // Load the verifying key
     println!("📂 Loading verifying key from: {}", args.vkey_file);
     let vkey_bytes = fs::read(&args.vkey_file).expect("❌ Failed to read verifying key file");
-    let vk: SP1VerifyingKey =
-        bincode::deserialize(&vkey_bytes).expect("❌ Failed to deserialize verifying key");
+    let vk: SP1VerifyingKey = args
+        .format
+        .decode(&vkey_bytes)
+        .unwrap_or_else(|e| panic!("❌ Failed to deserialize verifying key: {}", e));
     println!("✅ Verifying key loaded! 🔑");
 
     // Verify the proof
     println!("🔍 Verifying proof...");
-    let client = ProverClient::from_env();
     client
         .verify(&proof, &vk)
         .expect("❌ Proof verification failed!");
@@ -145,11 +503,112 @@ fn verify_mode(args: &Args) {
     let mut public_values = proof.public_values.clone();
     let public_params: proofimpl_atomic_swap::PublicParams = public_values.read();
     println!("🔍 Public params extracted: {:?}", public_params);
+    println!("📊 Amount: {} sats", public_params.amount_satoshis);
+    println!("⏰ Timelock: {} blocks", public_params.timelock_duration_blocks);
+    println!("🔑 Recipient public key: {}", public_params.recipient_public_key);
+    if let Some(btc_hash_lock) = &public_params.btc_hash_lock {
+        println!("🔒 Bitcoin HTLC hash lock: {}", btc_hash_lock);
+    }
+
+    // Check the committed values against what the counterparty expects
+    let differences = public_params.diff_expected(
+        args.expect_hash.as_deref(),
+        args.expect_pubkey.as_deref(),
+        args.expect_hash_lock.as_deref(),
+    );
+    if !differences.is_empty() {
+        eprintln!("❌ Proof does not commit to the expected values:");
+        for (field, expected, committed) in &differences {
+            eprintln!("   {}: expected {}, committed {}", field, expected, committed);
+        }
+        std::process::exit(1);
+    }
+    if args.expect_hash.is_some() || args.expect_pubkey.is_some() || args.expect_hash_lock.is_some() {
+        println!("✅ Proof commits to the expected values");
+    }
+
+    // Compare the committed swap against the local swap record before trusting the proof;
+    // a counterparty without the record relies on the --expect-* checks above
+    if args.swap_file.exists() {
+        let bitcoin_swap = load_bitcoin_swap(&args.swap_file);
+        let mismatches = public_params.mismatched_swap_fields(&bitcoin_swap.swap_info);
+        if mismatches.is_empty() {
+            println!(
+                "✅ Proof commits to the swap in {}",
+                args.swap_file.display()
+            );
+        } else {
+            eprintln!(
+                "❌ Proof does not match the swap in {}: {} differ",
+                args.swap_file.display(),
+                mismatches.join(", ")
+            );
+            std::process::exit(1);
+        }
+    } else {
+        println!(
+            "⚠️  No swap record at {}; swap fields were not compared",
+            args.swap_file.display()
+        );
+    }
 
     // Save public params as JSON
     let public_params_json =
         serde_json::to_string_pretty(&public_params).expect("❌ Failed to serialize public params");
-    let output_file = "public_params.json";
-    fs::write(output_file, public_params_json).expect("❌ Failed to write public params file");
-    println!("💾 Public parameters saved to: {} ✨", output_file);
+    let output_file = args
+        .public_params_out
+        .clone()
+        .unwrap_or_else(|| default_public_params_path(Path::new(&args.proof_file)));
+    write_creating_parents(&output_file, public_params_json.as_bytes()).unwrap_or_else(|e| {
+        eprintln!("❌ Error: {}", e);
+        std::process::exit(1);
+    });
+    println!("💾 Public parameters saved to: {} ✨", output_file.display());
+}
+
+/// Public params file for a proof when --public-params-out is omitted
+///
+/// The default `proof.bin` (or `proof.json`) keeps writing `public_params.json`,
+/// which the 1inch maker reads. Any other proof gets `<stem>.params.json` beside
+/// it, so verifying several proofs does not overwrite one file.
+fn default_public_params_path(proof_file: &Path) -> PathBuf {
+    match proof_file.file_stem().and_then(|stem| stem.to_str()) {
+        Some(stem) if stem != "proof" => proof_file.with_file_name(format!("{}.params.json", stem)),
+        _ => PathBuf::from(DEFAULT_PUBLIC_PARAMS_FILE),
+    }
+}
+
+/// Writes `contents` to `path`, creating missing parent directories first
+fn write_creating_parents(path: &Path, contents: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("cannot create directory {}: {}", parent.display(), e))?;
+    }
+    fs::write(path, contents).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A record as the Bitcoin CLI's `swap` command writes it
+    const CLI_RECORD: &str = include_str!("../../../../bitcoin/swaps/swap_bitcoin.json");
+
+    #[test]
+    fn cli_written_records_feed_the_program() {
+        let bitcoin_swap: BitcoinSwap = serde_json::from_str(CLI_RECORD).unwrap();
+        assert!(swap_stdin(&bitcoin_swap, false).is_ok());
+
+        // Records from before the CLI stored `secret_hash` still prove
+        let mut legacy = bitcoin_swap.clone();
+        legacy.secret_hash = None;
+        assert!(swap_stdin(&legacy, true).is_ok());
+        assert_eq!(
+            legacy.expected_secret_hash().unwrap(),
+            bitcoin_swap.expected_secret_hash().unwrap()
+        );
+
+        legacy.swap_secret = "not hex".to_string();
+        assert!(swap_stdin(&legacy, false).is_err());
+    }
 }